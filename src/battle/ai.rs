@@ -0,0 +1,482 @@
+//! Utility-based AI for combatants that are not controlled by the player
+//!
+//! Every combination of destination, ability and target is scored by a weighted sum of
+//! the expected damage, the threat posed by opponents at the destination, and how close
+//! the destination is to the preferred attack range. The weights are defined per enemy
+//! by its [`Personality`]. Damage taken from the terrain of the destination is always
+//! subtracted in full, and destinations where it would be lethal are never picked.
+
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::battle::board::{Board, Cover};
+use crate::battle::combat::{self, Ability, Combatant, Stats, Team};
+use crate::battle::command::{BattleCommand, IssuedCommand};
+use crate::battle::hex::{Hex, HexPosition};
+use crate::battle::replay::Replay;
+use crate::battle::status_effects::{StatModifiers, StatusEffects};
+use crate::battle::turn::{TurnStart, TurnState};
+use crate::rng::{GameRng, RngStream};
+
+/// Extra score for an attack that is expected to defeat its target
+const KILL_BONUS: f32 = 10.0;
+
+/// Upper bound of the random noise added to every score
+///
+/// Only large enough to decide between equally good options, which keeps the AI
+/// deterministic for a given seed without always picking the first option.
+const TIE_BREAK_NOISE: f32 = 0.01;
+
+/// Weights of the different concerns of the AI
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Personality {
+    /// How much dealing damage is valued
+    pub damage: f32,
+    /// How much ending the turn within reach of opponents is avoided
+    pub threat: f32,
+    /// How much staying at the preferred attack range from opponents is valued
+    pub positioning: f32,
+}
+
+/// All personalities, by name, as defined in `data/ai_personalities.ron`
+pub struct AiPersonalities(pub HashMap<String, Personality>);
+
+impl Default for AiPersonalities {
+    fn default() -> Self {
+        AiPersonalities(crate::data::load("ai_personalities.ron"))
+    }
+}
+
+impl AiPersonalities {
+    pub fn get(&self, name: &str) -> &Personality {
+        self.0
+            .get(name)
+            .unwrap_or_else(|| panic!("Unknown AI personality: {}", name))
+    }
+}
+
+/// Marks a combatant as controlled by the AI
+pub struct AiController {
+    pub personality: Personality,
+}
+
+/// What the AI knows about a combatant
+#[derive(Debug, Clone)]
+pub struct UnitView {
+    pub team: Team,
+    pub hex: Hex,
+    pub health: i32,
+    pub stats: Stats,
+    pub modifiers: StatModifiers,
+    pub abilities: Vec<Ability>,
+}
+
+impl UnitView {
+    pub fn new(combatant: &Combatant, hex: Hex, effects: &StatusEffects) -> Self {
+        UnitView {
+            team: combatant.team,
+            hex,
+            health: combatant.health,
+            stats: combatant.stats,
+            modifiers: effects.modifiers(),
+            abilities: combatant.abilities.clone(),
+        }
+    }
+
+    fn movement_range(&self) -> i32 {
+        combat::movement_range(&self.stats, &self.modifiers)
+    }
+
+    /// Damage this unit can expect to deal to `target` with `ability`, taking misses into account
+    fn expected_damage(&self, ability: &Ability, target: &UnitView, cover: Cover) -> f32 {
+        let damage = combat::damage(
+            ability.power,
+            &self.stats,
+            &self.modifiers,
+            &target.stats,
+            &target.modifiers,
+        );
+
+        cover.hit_chance(ability.accuracy) * damage.min(target.health) as f32
+    }
+
+    /// Like [`UnitView::expected_damage`], but also rewards attacks that can defeat the target
+    fn attack_value(&self, ability: &Ability, target: &UnitView, cover: Cover) -> f32 {
+        let lethal = combat::damage(
+            ability.power,
+            &self.stats,
+            &self.modifiers,
+            &target.stats,
+            &target.modifiers,
+        ) >= target.health;
+
+        let kill_value = if lethal {
+            cover.hit_chance(ability.accuracy) * KILL_BONUS
+        } else {
+            0.0
+        };
+
+        self.expected_damage(ability, target, cover) + kill_value
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlannedAction {
+    pub ability: usize,
+    pub target: Hex,
+}
+
+/// What the AI decided to do this turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Plan {
+    pub destination: Hex,
+    pub action: Option<PlannedAction>,
+}
+
+impl Plan {
+    /// The commands that carry out this plan for a combatant standing on `start`
+    pub fn commands(&self, start: Hex) -> Vec<BattleCommand> {
+        let mut commands = Vec::new();
+
+        if self.destination != start {
+            commands.push(BattleCommand::Move(self.destination));
+        }
+
+        if let Some(action) = self.action {
+            commands.push(BattleCommand::UseAbility {
+                ability: action.ability,
+                target: action.target,
+            });
+        }
+
+        commands.push(BattleCommand::EndTurn);
+        commands
+    }
+}
+
+/// Picks the best move, ability and target for `actor`
+///
+/// `others` are all other living combatants on the board, of both teams.
+pub fn plan_turn(
+    actor: &UnitView,
+    others: &[UnitView],
+    board: &Board,
+    personality: &Personality,
+    rng: &mut impl Rng,
+) -> Plan {
+    let opponents: Vec<&UnitView> = others.iter().filter(|u| u.team != actor.team).collect();
+    let occupied: HashSet<Hex> = others.iter().map(|u| u.hex).collect();
+
+    let mut best = Plan {
+        destination: actor.hex,
+        action: None,
+    };
+    let mut best_score = f32::NEG_INFINITY;
+
+    let mut consider = |plan: Plan, score: f32| {
+        let score = score + rng.gen::<f32>() * TIE_BREAK_NOISE;
+        if score > best_score {
+            best = plan;
+            best_score = score;
+        }
+    };
+
+    for destination in board.reachable(actor.hex, actor.movement_range(), &occupied) {
+        let terrain_damage = if destination == actor.hex {
+            0
+        } else {
+            board.tile(destination).terrain.damage_on_enter()
+        };
+        if terrain_damage > 0 && terrain_damage >= actor.health {
+            continue;
+        }
+
+        let base_score = personality.positioning * positioning(actor, destination, &opponents)
+            - personality.threat * threat(actor, destination, &opponents)
+            - terrain_damage as f32;
+
+        consider(
+            Plan {
+                destination,
+                action: None,
+            },
+            base_score,
+        );
+
+        for (index, ability) in actor.abilities.iter().enumerate() {
+            for target in opponents.iter().filter(|target| {
+                destination.distance(target.hex)
+                    <= board.attack_range(destination, target.hex, ability.range)
+            }) {
+                let cover = board.cover(destination, target.hex, &occupied);
+                if cover == Cover::Full {
+                    continue;
+                }
+
+                consider(
+                    Plan {
+                        destination,
+                        action: Some(PlannedAction {
+                            ability: index,
+                            target: target.hex,
+                        }),
+                    },
+                    base_score + personality.damage * actor.attack_value(ability, target, cover),
+                );
+            }
+        }
+    }
+
+    best
+}
+
+/// How much damage opponents could deal to `actor` next turn if it stood on `destination`
+///
+/// Cover is ignored, as opponents can move before attacking.
+fn threat(actor: &UnitView, destination: Hex, opponents: &[&UnitView]) -> f32 {
+    let actor_at_destination = UnitView {
+        hex: destination,
+        ..actor.clone()
+    };
+
+    opponents
+        .iter()
+        .map(|opponent| {
+            let distance = opponent.hex.distance(destination);
+
+            opponent
+                .abilities
+                .iter()
+                .filter(|ability| distance <= opponent.movement_range() + ability.range)
+                .map(|ability| {
+                    opponent.expected_damage(ability, &actor_at_destination, Cover::None)
+                })
+                .fold(0.0, f32::max)
+        })
+        .sum()
+}
+
+/// Penalizes standing further from or closer to the nearest opponent than the preferred range
+fn positioning(actor: &UnitView, destination: Hex, opponents: &[&UnitView]) -> f32 {
+    let preferred_range = actor.abilities.iter().map(|a| a.range).max().unwrap_or(1);
+
+    opponents
+        .iter()
+        .map(|opponent| opponent.hex.distance(destination))
+        .min()
+        .map_or(0.0, |nearest| -((nearest - preferred_range).abs() as f32))
+}
+
+/// Plans and issues the commands for AI controlled combatants when their turn starts
+#[allow(clippy::too_many_arguments)]
+pub fn enemy_ai_turns(
+    mut turn_starts: EventReader<TurnStart>,
+    turn: Res<TurnState>,
+    replay: Option<Res<Replay>>,
+    board: Res<Board>,
+    mut rng: ResMut<GameRng>,
+    controllers: Query<&AiController>,
+    units: Query<(Entity, &Combatant, &HexPosition, &StatusEffects)>,
+    mut battle_commands: EventWriter<IssuedCommand>,
+) {
+    // Replays issue the recorded commands instead
+    if replay.is_some() {
+        return;
+    }
+
+    for TurnStart(entity) in turn_starts.iter() {
+        if turn.active_entity() != Some(*entity) {
+            continue;
+        }
+
+        let controller = match controllers.get(*entity) {
+            Ok(controller) => controller,
+            Err(_) => continue,
+        };
+
+        let mut actor = None;
+        let mut others = Vec::new();
+        for (e, combatant, position, effects) in units.iter() {
+            let mut view = UnitView::new(combatant, position.0, effects);
+            view.modifiers = view.modifiers.combine(board.modifiers(position.0));
+
+            if e == *entity {
+                actor = Some(view);
+            } else if combatant.is_alive() {
+                others.push(view);
+            }
+        }
+
+        if let Some(actor) = actor {
+            let plan = plan_turn(
+                &actor,
+                &others,
+                &board,
+                &controller.personality,
+                rng.stream(RngStream::Ai),
+            );
+
+            for command in plan.commands(actor.hex) {
+                battle_commands.send(IssuedCommand {
+                    unit: *entity,
+                    command,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::battle::board::{Terrain, Tile};
+
+    fn unit(team: Team, hex: Hex, health: i32, movement: i32, range: i32) -> UnitView {
+        UnitView {
+            team,
+            hex,
+            health,
+            stats: Stats {
+                max_health: 20,
+                attack: 2,
+                defence: 1,
+                movement,
+            },
+            modifiers: StatModifiers::default(),
+            abilities: vec![Ability {
+                name: "Strike".to_string(),
+                power: 4,
+                range,
+                accuracy: 0.9,
+                effect: None,
+            }],
+        }
+    }
+
+    fn personality(damage: f32, threat: f32, positioning: f32) -> Personality {
+        Personality {
+            damage,
+            threat,
+            positioning,
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_plan() {
+        let actor = unit(Team::Enemy, Hex::ZERO, 20, 3, 1);
+        let others = [
+            unit(Team::Player, Hex::new(4, 0), 20, 2, 1),
+            unit(Team::Player, Hex::new(-4, 0), 20, 2, 1),
+            unit(Team::Player, Hex::new(0, 4), 20, 2, 1),
+        ];
+        let board = Board::default();
+        let brute = personality(1.0, 0.1, 0.5);
+
+        for seed in 0..10 {
+            let first = plan_turn(
+                &actor,
+                &others,
+                &board,
+                &brute,
+                &mut ChaCha8Rng::seed_from_u64(seed),
+            );
+            let second = plan_turn(
+                &actor,
+                &others,
+                &board,
+                &brute,
+                &mut ChaCha8Rng::seed_from_u64(seed),
+            );
+
+            assert_eq!(first, second);
+        }
+    }
+
+    #[test]
+    fn finishes_off_weak_targets() {
+        let actor = unit(Team::Enemy, Hex::ZERO, 20, 2, 1);
+        let others = [
+            unit(Team::Player, Hex::new(2, 0), 20, 2, 1),
+            unit(Team::Player, Hex::new(-2, 0), 3, 2, 1),
+        ];
+
+        let plan = plan_turn(
+            &actor,
+            &others,
+            &Board::default(),
+            &personality(1.0, 0.1, 0.5),
+            &mut ChaCha8Rng::seed_from_u64(0),
+        );
+
+        assert_eq!(plan.action.map(|a| a.target), Some(Hex::new(-2, 0)));
+        assert_eq!(plan.destination.distance(Hex::new(-2, 0)), 1);
+    }
+
+    #[test]
+    fn personality_changes_positioning() {
+        let actor = unit(Team::Enemy, Hex::ZERO, 20, 3, 1);
+        let others = [unit(Team::Player, Hex::new(6, 0), 20, 2, 1)];
+        let board = Board::default();
+
+        let brute = plan_turn(
+            &actor,
+            &others,
+            &board,
+            &personality(1.0, 0.0, 1.0),
+            &mut ChaCha8Rng::seed_from_u64(0),
+        );
+        let coward = plan_turn(
+            &actor,
+            &others,
+            &board,
+            &personality(0.0, 1.0, 0.0),
+            &mut ChaCha8Rng::seed_from_u64(0),
+        );
+
+        // The brute charges in as far as it can, the coward stays out of reach
+        assert_eq!(brute.destination.distance(Hex::new(6, 0)), 3);
+        assert!(coward.destination.distance(Hex::new(6, 0)) > 3);
+    }
+
+    #[test]
+    fn avoids_lava() {
+        let mut tiles = HashMap::new();
+        tiles.insert(
+            Hex::new(1, 0),
+            Tile {
+                terrain: Terrain::Lava,
+                elevation: 0,
+            },
+        );
+        let board = Board::new(3, tiles);
+
+        // Both (1, 0) and (1, -1) are next to the opponent
+        let actor = unit(Team::Enemy, Hex::ZERO, 20, 1, 1);
+        let others = [unit(Team::Player, Hex::new(2, -1), 20, 2, 1)];
+
+        for seed in 0..10 {
+            let plan = plan_turn(
+                &actor,
+                &others,
+                &board,
+                &personality(1.0, 0.1, 0.5),
+                &mut ChaCha8Rng::seed_from_u64(seed),
+            );
+
+            assert_eq!(plan.destination, Hex::new(1, -1));
+        }
+    }
+
+    #[test]
+    fn personalities_file_is_valid() {
+        let personalities = AiPersonalities::default();
+
+        assert!(!personalities.0.is_empty());
+    }
+}
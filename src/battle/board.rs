@@ -0,0 +1,455 @@
+//! The hexagonal board battles are fought on
+//!
+//! Every tile of the board has a [`Terrain`] and an elevation. Terrain makes tiles harder
+//! to cross, protects the combatants standing on it or hurts the ones walking into it.
+//! Standing higher than the target extends the range of abilities.
+//!
+//! Abilities also need a line of sight to their target. Rocks, tiles higher than both
+//! ends of the line and other combatants block it, while forests and lines grazing past
+//! an obstacle only give [`Cover::Partial`].
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::battle::encounter::BoardLayout;
+use crate::battle::hex::Hex;
+use crate::battle::status_effects::StatModifiers;
+
+/// Radius of the board mesh, in hexes
+pub const BOARD_RADIUS: i32 = 8;
+
+/// Height of one elevation level in the world
+pub const ELEVATION_HEIGHT: f32 = 0.4;
+
+/// How many elevation levels can be climbed in one step
+const MAX_CLIMB: i32 = 1;
+
+/// Factor applied to the accuracy of abilities used on targets in partial cover
+const PARTIAL_COVER_ACCURACY: f32 = 0.6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Terrain {
+    Grass,
+    Water,
+    Forest,
+    Rock,
+    Lava,
+}
+
+impl Default for Terrain {
+    fn default() -> Self {
+        Terrain::Grass
+    }
+}
+
+impl Terrain {
+    /// Movement points needed to walk onto the terrain, or `None` if it can't be walked on
+    pub fn movement_cost(self) -> Option<i32> {
+        match self {
+            Terrain::Grass | Terrain::Lava => Some(1),
+            Terrain::Forest => Some(2),
+            Terrain::Water => Some(3),
+            Terrain::Rock => None,
+        }
+    }
+
+    /// Defence granted to combatants standing on the terrain
+    pub fn defence_bonus(self) -> i32 {
+        match self {
+            Terrain::Forest => 2,
+            _ => 0,
+        }
+    }
+
+    /// Whether the terrain blocks lines of sight passing over it
+    pub fn blocks_sight(self) -> bool {
+        matches!(self, Terrain::Rock)
+    }
+
+    /// Whether the terrain gives cover to targets behind it
+    pub fn gives_cover(self) -> bool {
+        matches!(self, Terrain::Forest)
+    }
+
+    /// Damage taken when moving onto the terrain
+    pub fn damage_on_enter(self) -> i32 {
+        match self {
+            Terrain::Lava => 5,
+            _ => 0,
+        }
+    }
+}
+
+/// How well the line of sight to a target is obstructed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Cover {
+    None,
+    Partial,
+    /// There is no line of sight at all
+    Full,
+}
+
+impl Cover {
+    /// Chance of an ability with the given accuracy to hit a target in this cover
+    pub fn hit_chance(self, accuracy: f32) -> f32 {
+        let accuracy = accuracy.clamp(0.0, 1.0);
+
+        match self {
+            Cover::None => accuracy,
+            Cover::Partial => accuracy * PARTIAL_COVER_ACCURACY,
+            Cover::Full => 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tile {
+    pub terrain: Terrain,
+    pub elevation: i32,
+}
+
+/// Shape and terrain of the board of the current battle
+pub struct Board {
+    pub radius: i32,
+    /// Tiles that differ from the default flat grass
+    tiles: HashMap<Hex, Tile>,
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Board::new(BOARD_RADIUS, HashMap::new())
+    }
+}
+
+impl Board {
+    pub fn new(radius: i32, tiles: HashMap<Hex, Tile>) -> Self {
+        Board { radius, tiles }
+    }
+
+    pub fn from_layout(layout: &BoardLayout) -> Self {
+        Board::new(layout.radius, layout.tiles.clone())
+    }
+
+    pub fn contains(&self, hex: Hex) -> bool {
+        hex.distance(Hex::ZERO) <= self.radius
+    }
+
+    pub fn tile(&self, hex: Hex) -> Tile {
+        self.tiles.get(&hex).copied().unwrap_or_default()
+    }
+
+    /// Every tile of the board
+    pub fn tiles(&self) -> impl Iterator<Item = (Hex, Tile)> + '_ {
+        Hex::ZERO
+            .within(self.radius)
+            .map(move |hex| (hex, self.tile(hex)))
+    }
+
+    /// Height of the top of the tile in the world
+    pub fn height(&self, hex: Hex) -> f32 {
+        self.tile(hex).elevation as f32 * ELEVATION_HEIGHT
+    }
+
+    /// Stat modifiers for combatants standing on the hex
+    pub fn modifiers(&self, hex: Hex) -> StatModifiers {
+        StatModifiers {
+            defence: self.tile(hex).terrain.defence_bonus(),
+            ..Default::default()
+        }
+    }
+
+    /// Range of an ability used from `from` on `to`, which grows when attacking from above
+    pub fn attack_range(&self, from: Hex, to: Hex, range: i32) -> i32 {
+        let height_advantage = self.tile(from).elevation - self.tile(to).elevation;
+
+        range + height_advantage.max(0)
+    }
+
+    /// Cover of a target on `to` from an ability used on `from`
+    ///
+    /// Combatants standing on `occupied` hexes block the line of sight, except the ones on
+    /// either end. The result is the same when swapping `from` and `to`.
+    pub fn cover(&self, from: Hex, to: Hex, occupied: &HashSet<Hex>) -> Cover {
+        let eye_level = self.tile(from).elevation.max(self.tile(to).elevation);
+
+        let obstacle = |hex: Hex| {
+            let tile = self.tile(hex);
+            if tile.terrain.blocks_sight() || tile.elevation > eye_level || occupied.contains(&hex)
+            {
+                Cover::Full
+            } else if tile.terrain.gives_cover() {
+                Cover::Partial
+            } else {
+                Cover::None
+            }
+        };
+
+        from.line_candidates(to)
+            .filter(|(a, b)| ![from, to].contains(a) && ![from, to].contains(b))
+            .map(|(a, b)| match (obstacle(a), obstacle(b)) {
+                (Cover::Full, Cover::Full) => Cover::Full,
+                (Cover::None, Cover::None) => Cover::None,
+                // Lines running between two hexes are only blocked if both are obstacles
+                _ => Cover::Partial,
+            })
+            .max()
+            .unwrap_or(Cover::None)
+    }
+
+    /// Movement points needed to step from `from` onto the neighbouring hex `to`
+    ///
+    /// Climbing costs an extra point per elevation level. Returns `None` if the step is
+    /// not possible.
+    fn step_cost(&self, from: Hex, to: Hex) -> Option<i32> {
+        if !self.contains(to) {
+            return None;
+        }
+
+        let (from, to) = (self.tile(from), self.tile(to));
+        let climb = to.elevation - from.elevation;
+        if climb > MAX_CLIMB {
+            return None;
+        }
+
+        to.terrain.movement_cost().map(|cost| cost + climb.max(0))
+    }
+
+    /// All hexes that can be walked to from `start` with the given movement points
+    ///
+    /// Walking through `blocked` hexes is not possible, and walking through terrain that
+    /// deals damage on enter isn't either, so paths only enter it at their destination.
+    /// The result includes `start` and is ordered by the movement points needed.
+    pub fn reachable(&self, start: Hex, movement: i32, blocked: &HashSet<Hex>) -> Vec<Hex> {
+        let mut reachable = Vec::new();
+        let mut costs = HashMap::new();
+        let mut queue = BinaryHeap::new();
+
+        costs.insert(start, 0);
+        queue.push(Reverse((0, start.q, start.r)));
+
+        while let Some(Reverse((cost, q, r))) = queue.pop() {
+            let hex = Hex::new(q, r);
+            if costs[&hex] < cost {
+                // Already reached in fewer movement points
+                continue;
+            }
+            reachable.push(hex);
+
+            if hex != start && self.tile(hex).terrain.damage_on_enter() > 0 {
+                continue;
+            }
+
+            for next in hex.neighbours().iter().copied() {
+                if blocked.contains(&next) {
+                    continue;
+                }
+
+                let next_cost = match self.step_cost(hex, next) {
+                    Some(step) => cost + step,
+                    None => continue,
+                };
+
+                if next_cost <= movement && costs.get(&next).map_or(true, |c| next_cost < *c) {
+                    costs.insert(next, next_cost);
+                    queue.push(Reverse((next_cost, next.q, next.r)));
+                }
+            }
+        }
+
+        reachable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terrain_costs_movement() {
+        let mut tiles = HashMap::new();
+        tiles.insert(
+            Hex::new(1, 0),
+            Tile {
+                terrain: Terrain::Forest,
+                elevation: 0,
+            },
+        );
+        tiles.insert(
+            Hex::new(0, 1),
+            Tile {
+                terrain: Terrain::Rock,
+                elevation: 0,
+            },
+        );
+        tiles.insert(
+            Hex::new(-1, 0),
+            Tile {
+                terrain: Terrain::Grass,
+                elevation: 2,
+            },
+        );
+        let board = Board::new(3, tiles);
+
+        let reachable = board.reachable(Hex::ZERO, 1, &HashSet::new());
+
+        assert_eq!(reachable[0], Hex::ZERO);
+        // Forest costs two points, rock can't be entered and the hill is too steep
+        assert!(!reachable.contains(&Hex::new(1, 0)));
+        assert!(!reachable.contains(&Hex::new(0, 1)));
+        assert!(!reachable.contains(&Hex::new(-1, 0)));
+        assert_eq!(reachable.len(), 4);
+
+        assert!(board
+            .reachable(Hex::ZERO, 2, &HashSet::new())
+            .contains(&Hex::new(1, 0)));
+    }
+
+    #[test]
+    fn paths_go_around_lava() {
+        let mut tiles = HashMap::new();
+        tiles.insert(
+            Hex::new(1, 0),
+            Tile {
+                terrain: Terrain::Lava,
+                elevation: 0,
+            },
+        );
+        let board = Board::new(3, tiles);
+
+        // The lava can be walked onto, but not through to the hex behind it
+        let reachable = board.reachable(Hex::ZERO, 2, &HashSet::new());
+        assert!(reachable.contains(&Hex::new(1, 0)));
+        assert!(!reachable.contains(&Hex::new(2, 0)));
+
+        // Walking off lava is fine
+        assert!(board
+            .reachable(Hex::new(1, 0), 1, &HashSet::new())
+            .contains(&Hex::new(2, 0)));
+    }
+
+    #[test]
+    fn height_extends_range() {
+        let mut tiles = HashMap::new();
+        tiles.insert(
+            Hex::ZERO,
+            Tile {
+                terrain: Terrain::Grass,
+                elevation: 2,
+            },
+        );
+        let board = Board::new(3, tiles);
+
+        assert_eq!(board.attack_range(Hex::ZERO, Hex::new(3, 0), 1), 3);
+        assert_eq!(board.attack_range(Hex::new(3, 0), Hex::ZERO, 1), 1);
+    }
+
+    fn tile(terrain: Terrain, elevation: i32) -> Tile {
+        Tile { terrain, elevation }
+    }
+
+    #[test]
+    fn obstacles_block_sight() {
+        let mut tiles = HashMap::new();
+        tiles.insert(Hex::new(1, 0), tile(Terrain::Rock, 0));
+        tiles.insert(Hex::new(0, 1), tile(Terrain::Forest, 0));
+        tiles.insert(Hex::new(-1, 0), tile(Terrain::Grass, 1));
+        let board = Board::new(4, tiles);
+        let nobody = HashSet::new();
+
+        assert_eq!(board.cover(Hex::ZERO, Hex::new(3, 0), &nobody), Cover::Full);
+        assert_eq!(
+            board.cover(Hex::ZERO, Hex::new(0, 3), &nobody),
+            Cover::Partial
+        );
+        assert_eq!(
+            board.cover(Hex::ZERO, Hex::new(0, -3), &nobody),
+            Cover::None
+        );
+        // The hill only blocks the view if it is higher than both ends
+        assert_eq!(
+            board.cover(Hex::ZERO, Hex::new(-3, 0), &nobody),
+            Cover::Full
+        );
+
+        let mut occupied = HashSet::new();
+        occupied.insert(Hex::new(0, -2));
+        assert_eq!(
+            board.cover(Hex::ZERO, Hex::new(0, -3), &occupied),
+            Cover::Full
+        );
+        // The combatants on either end don't block the line
+        occupied.insert(Hex::ZERO);
+        occupied.insert(Hex::new(0, -1));
+        assert_eq!(
+            board.cover(Hex::ZERO, Hex::new(0, -1), &occupied),
+            Cover::None
+        );
+    }
+
+    #[test]
+    fn lines_between_hexes_are_only_blocked_by_two_obstacles() {
+        let mut tiles = HashMap::new();
+        // The line from the center to (1, -2) runs between these two hexes
+        tiles.insert(Hex::new(0, -1), tile(Terrain::Rock, 0));
+        let mut board = Board::new(3, tiles);
+        let nobody = HashSet::new();
+
+        assert_eq!(
+            board.cover(Hex::ZERO, Hex::new(1, -2), &nobody),
+            Cover::Partial
+        );
+
+        board.tiles.insert(Hex::new(1, -1), tile(Terrain::Rock, 0));
+        assert_eq!(
+            board.cover(Hex::ZERO, Hex::new(1, -2), &nobody),
+            Cover::Full
+        );
+    }
+
+    #[test]
+    fn line_of_sight_is_symmetric() {
+        for radius in 1..=6 {
+            let mut tiles = HashMap::new();
+            let mut occupied = HashSet::new();
+            for hex in Hex::ZERO.within(radius) {
+                // An irregular but reproducible scattering of obstacles
+                let obstacle = match (hex.q * 7 + hex.r * 13).rem_euclid(11) {
+                    0 => tile(Terrain::Rock, 0),
+                    1 | 2 => tile(Terrain::Forest, 0),
+                    3 => tile(Terrain::Grass, 1),
+                    4 => tile(Terrain::Water, 2),
+                    5 => {
+                        occupied.insert(hex);
+                        continue;
+                    }
+                    _ => continue,
+                };
+                tiles.insert(hex, obstacle);
+            }
+            let board = Board::new(radius, tiles);
+
+            for from in Hex::ZERO.within(radius) {
+                for to in Hex::ZERO.within(radius) {
+                    assert_eq!(
+                        board.cover(from, to, &occupied),
+                        board.cover(to, from, &occupied),
+                        "line of sight between {:?} and {:?} on a board of radius {}",
+                        from,
+                        to,
+                        radius
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cover_reduces_hit_chance() {
+        assert_eq!(Cover::None.hit_chance(0.8), 0.8);
+        assert!(Cover::Partial.hit_chance(0.8) < 0.8);
+        assert_eq!(Cover::Full.hit_chance(0.8), 0.0);
+    }
+}
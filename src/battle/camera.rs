@@ -1,16 +1,352 @@
-use bevy::{input::mouse::MouseMotion, prelude::*};
+//! The battle camera
+//!
+//! The camera is attached to a rig which pivots around the point the camera looks at.
+//! The rig pans to the active combatant when a turn starts, frames the hexes the selected
+//! ability would hit at the hovered target, and can be panned with the movement keys or by
+//! moving the cursor to the edge of the window. The mouse wheel zooms, and holding the
+//! rotate camera button, the right mouse button by default, while moving the mouse
+//! rotates the view.
 
-pub struct Camera;
+use bevy::{
+    input::mouse::{MouseMotion, MouseWheel},
+    prelude::*,
+    render::camera::Camera as RenderCamera,
+};
 
+use crate::battle::board::Board;
+use crate::battle::combat_log::{self, CombatLogPanel};
+use crate::battle::hex::{Hex, HexPosition, HEX_SIZE};
+use crate::battle::turn::TurnStart;
+use crate::user_config::KeyBinds;
+
+/// Distance of the camera when a battle starts
+const INITIAL_DISTANCE: f32 = 21.0;
+
+/// How close the camera can get to the point it looks at
+const MIN_DISTANCE: f32 = 6.0;
+
+/// Camera distance needed to see a board of the given extent, per world unit
+const DISTANCE_PER_EXTENT: f32 = 2.0;
+
+/// Camera distance needed to frame a group of hexes, per world unit of their spread
+const FRAMING_DISTANCE_PER_SPREAD: f32 = 3.0;
+
+/// How much one step of the mouse wheel zooms
+const ZOOM_STEP: f32 = 1.15;
+
+/// Panning speed in world units per second, per world unit of camera distance
+const PAN_SPEED: f32 = 0.8;
+
+/// How close to the edge of the window the cursor has to be to pan, in pixels
+const EDGE_SCROLL_MARGIN: f32 = 8.0;
+
+/// How quickly the camera catches up with where it should be
+const FOLLOW_RATE: f32 = 5.0;
+
+/// Asks the camera to show all of the given hexes
+pub struct FrameHexes(pub Vec<Hex>);
+
+/// The rig the battle camera is attached to
+pub struct Camera {
+    /// The point the rig is moving to
+    focus: Vec3,
+    distance: f32,
+    /// The distance the camera is zooming to
+    target_distance: f32,
+    min_distance: f32,
+    max_distance: f32,
+    /// How far from the center of the board the rig can move
+    bounds: f32,
+}
+
+impl Camera {
+    pub fn new(board: &Board) -> Self {
+        let (min_distance, max_distance) = zoom_limits(board.radius);
+        let distance = INITIAL_DISTANCE.clamp(min_distance, max_distance);
+
+        Camera {
+            focus: Vec3::ZERO,
+            distance,
+            target_distance: distance,
+            min_distance,
+            max_distance,
+            bounds: board_extent(board.radius),
+        }
+    }
+
+    /// Moves the point the rig is moving to, keeping it over the board
+    fn set_focus(&mut self, focus: Vec3) {
+        let horizontal = Vec3::new(focus.x, 0.0, focus.z);
+        let clamped = if horizontal.length() > self.bounds {
+            horizontal.normalize() * self.bounds
+        } else {
+            horizontal
+        };
+
+        self.focus = Vec3::new(clamped.x, focus.y, clamped.z);
+    }
+
+    fn zoom_to(&mut self, distance: f32) {
+        self.target_distance = distance.clamp(self.min_distance, self.max_distance);
+    }
+}
+
+/// Distance from the center of a board of the given radius to its outermost hex centers
+fn board_extent(radius: i32) -> f32 {
+    radius as f32 * HEX_SIZE * 3f32.sqrt()
+}
+
+/// How close and how far the camera can zoom on a board of the given radius
+pub fn zoom_limits(radius: i32) -> (f32, f32) {
+    let max_distance = board_extent(radius) * DISTANCE_PER_EXTENT;
+
+    (MIN_DISTANCE, max_distance.max(MIN_DISTANCE))
+}
+
+/// Direction from the point the camera looks at to the camera, before rotating the rig
+fn view_direction() -> Vec3 {
+    Vec3::new(0.0, 1.0, -1.0).normalize()
+}
+
+/// Where the camera looks when showing the given hex
+fn hex_focus(board: &Board, hex: Hex) -> Vec3 {
+    hex.to_world() + Vec3::new(0.0, board.height(hex), 0.0)
+}
+
+/// Rotates the camera with the mouse while the rotate button is held
 pub fn rotate_camera(
     mut query: Query<&mut Transform, With<Camera>>,
+    mouse: Res<Input<MouseButton>>,
+    keybinds: Res<KeyBinds>,
     mut mouse_events: EventReader<MouseMotion>,
     window: Res<WindowDescriptor>,
 ) {
     for event in mouse_events.iter() {
+        if !mouse.pressed(keybinds.rotate_camera) {
+            continue;
+        }
+
         let rotation = Quat::from_rotation_y(-4. * event.delta.x / window.width);
         for mut transform in query.iter_mut() {
             transform.rotate(rotation);
         }
     }
 }
+
+/// Pans to the combatant whose turn starts
+pub fn focus_active_unit(
+    mut turn_starts: EventReader<TurnStart>,
+    board: Res<Board>,
+    units: Query<&HexPosition>,
+    mut rigs: Query<&mut Camera>,
+) {
+    for TurnStart(entity) in turn_starts.iter() {
+        if let Ok(position) = units.get(*entity) {
+            for mut rig in rigs.iter_mut() {
+                rig.set_focus(hex_focus(&board, position.0));
+            }
+        }
+    }
+}
+
+/// Pans and zooms to show the requested hexes
+pub fn frame_hexes(
+    mut frames: EventReader<FrameHexes>,
+    board: Res<Board>,
+    mut rigs: Query<&mut Camera>,
+) {
+    for FrameHexes(hexes) in frames.iter() {
+        if hexes.is_empty() {
+            continue;
+        }
+
+        let points: Vec<Vec3> = hexes.iter().map(|hex| hex_focus(&board, *hex)).collect();
+        let center = points.iter().fold(Vec3::ZERO, |sum, p| sum + *p) / points.len() as f32;
+        let spread = points
+            .iter()
+            .map(|p| (*p - center).length())
+            .fold(0.0, f32::max);
+
+        for mut rig in rigs.iter_mut() {
+            rig.set_focus(center);
+            rig.zoom_to(spread * FRAMING_DISTANCE_PER_SPREAD);
+        }
+    }
+}
+
+/// Pans with the movement keys, or when the cursor is at the edge of the window
+pub fn pan_camera(
+    time: Res<Time>,
+    input: Res<Input<KeyCode>>,
+    keybinds: Res<KeyBinds>,
+    windows: Res<Windows>,
+    mut rigs: Query<(&mut Camera, &Transform)>,
+) {
+    let mut direction = Vec2::ZERO;
+
+    if input.pressed(keybinds.move_forward) {
+        direction.y += 1.0;
+    }
+    if input.pressed(keybinds.move_backward) {
+        direction.y -= 1.0;
+    }
+    if input.pressed(keybinds.move_right) {
+        direction.x += 1.0;
+    }
+    if input.pressed(keybinds.move_left) {
+        direction.x -= 1.0;
+    }
+
+    if let Some(window) = windows.get_primary() {
+        if let Some(cursor) = window.cursor_position() {
+            if cursor.x < EDGE_SCROLL_MARGIN {
+                direction.x -= 1.0;
+            } else if cursor.x > window.width() - EDGE_SCROLL_MARGIN {
+                direction.x += 1.0;
+            }
+            if cursor.y < EDGE_SCROLL_MARGIN {
+                direction.y -= 1.0;
+            } else if cursor.y > window.height() - EDGE_SCROLL_MARGIN {
+                direction.y += 1.0;
+            }
+        }
+    }
+
+    if direction == Vec2::ZERO {
+        return;
+    }
+
+    for (mut rig, transform) in rigs.iter_mut() {
+        // The camera looks along the rig's Z axis, so its right is the rig's -X axis
+        let forward = transform.rotation * Vec3::Z;
+        let right = transform.rotation * -Vec3::X;
+        let pan = (forward * direction.y + right * direction.x).normalize()
+            * PAN_SPEED
+            * rig.distance
+            * time.delta_seconds();
+
+        let focus = rig.focus + pan;
+        rig.set_focus(focus);
+    }
+}
+
+/// Zooms with the mouse wheel, unless the cursor is over the combat log
+pub fn zoom_camera(
+    mut wheel: EventReader<MouseWheel>,
+    windows: Res<Windows>,
+    panels: Query<(&Node, &GlobalTransform), With<CombatLogPanel>>,
+    mut rigs: Query<&mut Camera>,
+) {
+    let scrolled: f32 = wheel.iter().map(|event| event.y).sum();
+    if scrolled == 0.0 {
+        return;
+    }
+
+    // The combat log scrolls instead
+    if let Some(cursor) = windows.get_primary().and_then(|w| w.cursor_position()) {
+        if panels
+            .iter()
+            .any(|(node, transform)| combat_log::contains_cursor(node, transform, cursor))
+        {
+            return;
+        }
+    }
+
+    for mut rig in rigs.iter_mut() {
+        let distance = rig.target_distance * ZOOM_STEP.powf(-scrolled);
+        rig.zoom_to(distance);
+    }
+}
+
+/// Smoothly moves the rig to its focus, and the camera to its distance
+pub fn move_camera(
+    time: Res<Time>,
+    mut rigs: Query<(&mut Camera, &mut Transform, &Children)>,
+    mut cameras: Query<&mut Transform, Without<Camera>>,
+) {
+    let follow = 1.0 - (-FOLLOW_RATE * time.delta_seconds()).exp();
+
+    for (mut rig, mut transform, children) in rigs.iter_mut() {
+        transform.translation = transform.translation.lerp(rig.focus, follow);
+        rig.distance += (rig.target_distance - rig.distance) * follow;
+
+        for child in children.iter() {
+            if let Ok(mut camera) = cameras.get_mut(*child) {
+                camera.translation = view_direction() * rig.distance;
+                camera.look_at(Vec3::ZERO, Vec3::Y);
+            }
+        }
+    }
+}
+
+/// Finds the point on the board plane under the mouse cursor
+pub fn cursor_to_board(
+    windows: &Windows,
+    camera: &RenderCamera,
+    camera_transform: &GlobalTransform,
+) -> Option<Vec3> {
+    let window = windows.get_primary()?;
+    let cursor = window.cursor_position()?;
+    let ndc = cursor / Vec2::new(window.width(), window.height()) * 2.0 - Vec2::ONE;
+
+    // Cast a ray from the near plane to the far plane through the cursor
+    let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix.inverse();
+    let unproject = |depth: f32| {
+        let point = ndc_to_world * ndc.extend(depth).extend(1.0);
+        point.truncate() / point.w
+    };
+    let near = unproject(0.0);
+    let direction = unproject(1.0) - near;
+
+    if direction.y.abs() < f32::EPSILON {
+        return None;
+    }
+
+    Some(near - direction * (near.y / direction.y))
+}
+
+/// Finds where a point in the world appears on the screen, in pixels from the bottom left
+///
+/// Returns `None` for points behind the camera.
+pub fn world_to_screen(
+    windows: &Windows,
+    camera: &RenderCamera,
+    camera_transform: &GlobalTransform,
+    point: Vec3,
+) -> Option<Vec2> {
+    let window = windows.get_primary()?;
+
+    let world_to_ndc = camera.projection_matrix * camera_transform.compute_matrix().inverse();
+    let clip = world_to_ndc * point.extend(1.0);
+    if clip.w <= 0.0 {
+        return None;
+    }
+
+    let ndc = clip.truncate() / clip.w;
+    Some((ndc.truncate() + Vec2::ONE) / 2.0 * Vec2::new(window.width(), window.height()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn larger_boards_allow_zooming_further_out() {
+        let (min_small, max_small) = zoom_limits(2);
+        let (min_large, max_large) = zoom_limits(12);
+
+        assert_eq!(min_small, min_large);
+        assert!(max_small < max_large);
+        assert!(zoom_limits(0).1 >= zoom_limits(0).0);
+    }
+
+    #[test]
+    fn focus_stays_over_the_board() {
+        let mut camera = Camera::new(&Board::default());
+
+        camera.set_focus(Vec3::new(1000.0, 2.0, 0.0));
+
+        assert!((camera.focus.x - camera.bounds).abs() < 0.001);
+        assert_eq!(camera.focus.y, 2.0);
+    }
+}
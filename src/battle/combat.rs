@@ -0,0 +1,103 @@
+//! Combat stats and the formulas used to resolve attacks and movement
+
+use serde::{Deserialize, Serialize};
+
+use crate::battle::status_effects::{StatModifiers, StatusEffectKind};
+
+/// Which side of the battle a combatant fights for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Team {
+    Player,
+    Enemy,
+}
+
+/// Base stats of a combatant, before status effects are taken into account
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stats {
+    pub max_health: i32,
+    pub attack: i32,
+    pub defence: i32,
+    pub movement: i32,
+}
+
+/// An attack a combatant can use on its turn
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ability {
+    pub name: String,
+    pub power: i32,
+    /// Maximum distance to the target, in hexes
+    pub range: i32,
+    /// Chance to hit, between 0 and 1
+    pub accuracy: f32,
+    /// Status effect put on the combatants the ability hits, if any
+    #[serde(default)]
+    pub effect: Option<InflictedEffect>,
+}
+
+/// A status effect applied by an ability, for the given number of the target's turns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InflictedEffect {
+    pub kind: StatusEffectKind,
+    pub turns: u32,
+}
+
+/// A unit taking part in a battle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Combatant {
+    pub name: String,
+    pub team: Team,
+    pub stats: Stats,
+    pub health: i32,
+    pub abilities: Vec<Ability>,
+}
+
+impl Combatant {
+    pub fn new<S: Into<String>>(
+        name: S,
+        team: Team,
+        stats: Stats,
+        abilities: Vec<Ability>,
+    ) -> Self {
+        Combatant {
+            name: name.into(),
+            team,
+            health: stats.max_health,
+            stats,
+            abilities,
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.health > 0
+    }
+
+    /// Applies damage (or healing, if negative), keeping health within `0..=max_health`
+    pub fn take_damage(&mut self, amount: i32) {
+        self.health = (self.health - amount).clamp(0, self.stats.max_health);
+    }
+}
+
+/// Computes the damage an attack of the given `power` deals to the defender
+///
+/// Attacks always deal at least 1 damage, no matter how high the defence is.
+pub fn damage(
+    power: i32,
+    attacker: &Stats,
+    attacker_modifiers: &StatModifiers,
+    defender: &Stats,
+    defender_modifiers: &StatModifiers,
+) -> i32 {
+    let attack = (attacker.attack + attacker_modifiers.attack).max(0);
+    let defence = (defender.defence + defender_modifiers.defence).max(0);
+
+    (power + attack - defence).max(1)
+}
+
+/// How many hexes a combatant may move this turn
+pub fn movement_range(stats: &Stats, modifiers: &StatModifiers) -> i32 {
+    if modifiers.stunned {
+        0
+    } else {
+        (stats.movement + modifiers.movement).max(0)
+    }
+}
@@ -0,0 +1,392 @@
+//! A record of everything that happens in a battle
+//!
+//! Battle systems send [`CombatEvent`]s, which are collected in the [`CombatLog`] and shown
+//! in a scrollable panel. The log can be exported as plain text for bug reports.
+
+use std::fs;
+
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+
+use crate::battle::combat::{Combatant, Team};
+use crate::battle::hex::Hex;
+use crate::battle::status_effects::StatusEffectKind;
+use crate::battle::turn::TurnState;
+use crate::battle::StateCleanup;
+use crate::user_config::KeyBinds;
+use crate::UiAssets;
+
+/// How many entries fit in the combat log panel
+const VISIBLE_LINES: usize = 12;
+
+const COMBAT_LOG_FILE_NAME: &str = "combat_log.txt";
+
+/// The combatant an event is about, as it was when the event happened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogUnit {
+    pub name: String,
+    pub team: Team,
+}
+
+impl LogUnit {
+    pub fn new(combatant: &Combatant) -> Self {
+        LogUnit {
+            name: combatant.name.clone(),
+            team: combatant.team,
+        }
+    }
+}
+
+/// Something that happened in the battle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CombatEvent {
+    TurnStarted {
+        unit: LogUnit,
+    },
+    Moved {
+        unit: LogUnit,
+        from: Hex,
+        to: Hex,
+    },
+    MoveUndone {
+        unit: LogUnit,
+    },
+    AbilityUsed {
+        unit: LogUnit,
+        ability: String,
+        target: LogUnit,
+    },
+    Missed {
+        unit: LogUnit,
+        target: LogUnit,
+    },
+    ItemUsed {
+        unit: LogUnit,
+        item: String,
+        target: LogUnit,
+    },
+    Damaged {
+        unit: LogUnit,
+        hex: Hex,
+        amount: i32,
+    },
+    Healed {
+        unit: LogUnit,
+        hex: Hex,
+        amount: i32,
+    },
+    Defeated {
+        unit: LogUnit,
+    },
+    StatusApplied {
+        unit: LogUnit,
+        effect: StatusEffectKind,
+    },
+    StatusExpired {
+        unit: LogUnit,
+        effect: StatusEffectKind,
+    },
+    /// A status effect removed by an item before it wore off
+    StatusCured {
+        unit: LogUnit,
+        effect: StatusEffectKind,
+    },
+}
+
+impl CombatEvent {
+    /// The combatant the event is about, which decides its colour in the log
+    pub fn unit(&self) -> &LogUnit {
+        match self {
+            CombatEvent::TurnStarted { unit }
+            | CombatEvent::Moved { unit, .. }
+            | CombatEvent::MoveUndone { unit }
+            | CombatEvent::AbilityUsed { unit, .. }
+            | CombatEvent::Missed { unit, .. }
+            | CombatEvent::ItemUsed { unit, .. }
+            | CombatEvent::Damaged { unit, .. }
+            | CombatEvent::Healed { unit, .. }
+            | CombatEvent::Defeated { unit }
+            | CombatEvent::StatusApplied { unit, .. }
+            | CombatEvent::StatusExpired { unit, .. }
+            | CombatEvent::StatusCured { unit, .. } => unit,
+        }
+    }
+
+    /// Human readable description of the event
+    pub fn describe(&self) -> String {
+        match self {
+            CombatEvent::TurnStarted { unit } => format!("{}'s turn", unit.name),
+            CombatEvent::Moved { unit, from, to } => format!(
+                "{} moves from ({}, {}) to ({}, {})",
+                unit.name, from.q, from.r, to.q, to.r
+            ),
+            CombatEvent::MoveUndone { unit } => format!("{} takes back the move", unit.name),
+            CombatEvent::AbilityUsed {
+                unit,
+                ability,
+                target,
+            } => format!("{} uses {} on {}", unit.name, ability, target.name),
+            CombatEvent::Missed { unit, target } => {
+                format!("{} misses {}", unit.name, target.name)
+            }
+            CombatEvent::ItemUsed { unit, item, target } if unit == target => {
+                format!("{} uses {}", unit.name, item)
+            }
+            CombatEvent::ItemUsed { unit, item, target } => {
+                format!("{} gives {} to {}", unit.name, item, target.name)
+            }
+            CombatEvent::Damaged { unit, amount, .. } => {
+                format!("{} takes {} damage", unit.name, amount)
+            }
+            CombatEvent::Healed { unit, amount, .. } => {
+                format!("{} heals {} health", unit.name, amount)
+            }
+            CombatEvent::Defeated { unit } => format!("{} is defeated", unit.name),
+            CombatEvent::StatusApplied { unit, effect } => {
+                format!("{} is affected by {:?}", unit.name, effect)
+            }
+            CombatEvent::StatusExpired { unit, effect } => {
+                format!("{}'s {:?} wore off", unit.name, effect)
+            }
+            CombatEvent::StatusCured { unit, effect } => {
+                format!("{}'s {:?} was cured", unit.name, effect)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    /// Round of the battle, starting at 1
+    pub round: u32,
+    pub event: CombatEvent,
+}
+
+/// Every event of the current battle, in order
+#[derive(Debug, Default)]
+pub struct CombatLog {
+    pub entries: Vec<LogEntry>,
+}
+
+impl CombatLog {
+    /// The whole log, one event per line
+    pub fn to_plain_text(&self) -> String {
+        self.entries
+            .iter()
+            .map(|entry| format!("Round {}: {}\n", entry.round, entry.event.describe()))
+            .collect()
+    }
+}
+
+/// The panel showing the combat log
+#[derive(Default)]
+pub struct CombatLogPanel {
+    /// Number of entries hidden below the panel, so 0 shows the latest events
+    scroll: usize,
+    /// Number of log entries and scroll position when the panel was last drawn
+    shown: Option<(usize, usize)>,
+}
+
+pub fn setup_combat_log(mut commands: Commands, assets: Res<UiAssets>) {
+    commands.insert_resource(CombatLog::default());
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(320.0), Val::Px(VISIBLE_LINES as f32 * 20.0 + 16.0)),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::FlexEnd,
+                padding: Rect::all(Val::Px(8.0)),
+                ..Default::default()
+            },
+            material: assets.menu_panel_background.clone(),
+            ..Default::default()
+        })
+        .with(CombatLogPanel::default())
+        .with(StateCleanup)
+        .with_children(|panel| {
+            panel.spawn(TextBundle {
+                text: Text::default(),
+                ..Default::default()
+            });
+        });
+}
+
+/// Adds the events sent this frame to the combat log
+pub fn record_combat_events(
+    mut events: EventReader<CombatEvent>,
+    mut log: ResMut<CombatLog>,
+    turn: Res<TurnState>,
+) {
+    for event in events.iter() {
+        log.entries.push(LogEntry {
+            round: turn.round,
+            event: event.clone(),
+        });
+    }
+}
+
+/// Whether the cursor is over the given UI node
+pub fn contains_cursor(node: &Node, transform: &GlobalTransform, cursor: Vec2) -> bool {
+    let offset = cursor - transform.translation.truncate();
+
+    offset.x.abs() <= node.size.x / 2.0 && offset.y.abs() <= node.size.y / 2.0
+}
+
+/// Scrolls the combat log with the mouse wheel while the cursor is over it
+pub fn scroll_combat_log(
+    mut wheel: EventReader<MouseWheel>,
+    windows: Res<Windows>,
+    log: Res<CombatLog>,
+    mut panels: Query<(&mut CombatLogPanel, &Node, &GlobalTransform)>,
+) {
+    let scrolled: f32 = wheel.iter().map(|event| event.y).sum();
+    if scrolled == 0.0 {
+        return;
+    }
+
+    let cursor = match windows.get_primary().and_then(|w| w.cursor_position()) {
+        Some(cursor) => cursor,
+        None => return,
+    };
+
+    let max_scroll = log.entries.len().saturating_sub(VISIBLE_LINES);
+
+    for (mut panel, node, transform) in panels.iter_mut() {
+        if !contains_cursor(node, transform, cursor) {
+            continue;
+        }
+
+        // Scrolling up reveals older entries
+        panel.scroll = if scrolled > 0.0 {
+            (panel.scroll + 1).min(max_scroll)
+        } else {
+            panel.scroll.saturating_sub(1)
+        };
+    }
+}
+
+/// Shows the visible part of the combat log in the panel
+pub fn update_combat_log_panel(
+    log: Res<CombatLog>,
+    assets: Res<UiAssets>,
+    mut panels: Query<(&mut CombatLogPanel, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    for (mut panel, children) in panels.iter_mut() {
+        // Only redraw when new events came in, or when the panel scrolled
+        let shown = Some((log.entries.len(), panel.scroll));
+        if panel.shown == shown {
+            continue;
+        }
+        panel.shown = shown;
+
+        let end = log.entries.len().saturating_sub(panel.scroll);
+        let start = end.saturating_sub(VISIBLE_LINES);
+
+        let sections = log.entries[start..end]
+            .iter()
+            .map(|entry| TextSection {
+                value: format!("{}\n", entry.event.describe()),
+                style: TextStyle {
+                    font: assets.font_regular.clone(),
+                    font_size: 16.0,
+                    color: team_colour(entry.event.unit().team),
+                },
+            })
+            .collect();
+
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections = sections;
+                break;
+            }
+        }
+    }
+}
+
+/// Writes the combat log to a text file in the user data directory
+pub fn export_combat_log(input: Res<Input<KeyCode>>, keybinds: Res<KeyBinds>, log: Res<CombatLog>) {
+    if !input.just_pressed(keybinds.export_combat_log) {
+        return;
+    }
+
+    let path = crate::save::get_data_file_path(COMBAT_LOG_FILE_NAME);
+    match fs::write(&path, log.to_plain_text()) {
+        Ok(()) => info!("Exported combat log to {}", path.display()),
+        Err(e) => error!("Could not export combat log to {}: {}", path.display(), e),
+    }
+}
+
+fn team_colour(team: Team) -> Color {
+    match team {
+        Team::Player => Color::rgb(0.5, 0.7, 1.0),
+        Team::Enemy => Color::rgb(1.0, 0.5, 0.45),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_text_has_one_line_per_event() {
+        let hero = LogUnit {
+            name: "Hero".to_string(),
+            team: Team::Player,
+        };
+        let goblin = LogUnit {
+            name: "Goblin".to_string(),
+            team: Team::Enemy,
+        };
+
+        let log = CombatLog {
+            entries: vec![
+                LogEntry {
+                    round: 1,
+                    event: CombatEvent::AbilityUsed {
+                        unit: hero.clone(),
+                        ability: "Sword".to_string(),
+                        target: goblin.clone(),
+                    },
+                },
+                LogEntry {
+                    round: 1,
+                    event: CombatEvent::Damaged {
+                        unit: goblin.clone(),
+                        hex: Hex::new(1, 0),
+                        amount: 6,
+                    },
+                },
+                LogEntry {
+                    round: 2,
+                    event: CombatEvent::StatusExpired {
+                        unit: goblin,
+                        effect: StatusEffectKind::Poison,
+                    },
+                },
+                LogEntry {
+                    round: 2,
+                    event: CombatEvent::StatusCured {
+                        unit: hero,
+                        effect: StatusEffectKind::Stun,
+                    },
+                },
+            ],
+        };
+
+        assert_eq!(
+            log.to_plain_text(),
+            "Round 1: Hero uses Sword on Goblin\n\
+             Round 1: Goblin takes 6 damage\n\
+             Round 2: Goblin's Poison wore off\n\
+             Round 2: Hero's Stun was cured\n"
+        );
+    }
+}
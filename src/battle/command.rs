@@ -0,0 +1,684 @@
+//! Commands issued by the player or the AI to the combatant whose turn it is
+
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::battle::board::{Board, Cover};
+use crate::battle::combat::{self, Combatant};
+use crate::battle::combat_log::{CombatEvent, LogUnit};
+use crate::battle::hex::{Hex, HexPosition};
+use crate::battle::replay::{self, BattleRecording, RecordedCommand};
+use crate::battle::status_effects::StatusEffects;
+use crate::battle::turn::{TurnEnd, TurnState, UnitSnapshot};
+use crate::inventory::{Inventory, ItemEffect, Items};
+use crate::rng::{GameRng, RngStream};
+
+/// How far away a friendly combatant can be to be given an item
+pub const ITEM_RANGE: i32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BattleCommand {
+    /// Walk to the given hex
+    Move(Hex),
+    /// Use the ability with the given index on the combatant standing on `target`
+    UseAbility {
+        ability: usize,
+        target: Hex,
+    },
+    /// Use the item in the given inventory slot on the friendly combatant standing on
+    /// `target`
+    UseItem {
+        slot: usize,
+        target: Hex,
+    },
+    /// Take back the move made this turn, if no ability was used since
+    UndoMove,
+    EndTurn,
+    /// Run away from the battle, ending it without rewards
+    Flee,
+}
+
+/// A command for the given combatant, sent as an event
+///
+/// Commands for a combatant whose turn it is not are ignored, so commands queued for a
+/// combatant that was defeated can't be executed by the next one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IssuedCommand {
+    pub unit: Entity,
+    pub command: BattleCommand,
+}
+
+type UnitQuery<'a> = Query<
+    'a,
+    (
+        Entity,
+        &'a mut Combatant,
+        &'a mut HexPosition,
+        &'a mut Transform,
+        &'a mut StatusEffects,
+    ),
+>;
+
+/// Applies the issued commands to the active combatant
+///
+/// Invalid commands, like moving out of range or using an ability twice, are ignored, as
+/// are commands for a combatant whose turn it is not.
+#[allow(clippy::too_many_arguments)]
+pub fn execute_battle_commands(
+    mut commands: Commands,
+    mut battle_commands: EventReader<IssuedCommand>,
+    mut turn: ResMut<TurnState>,
+    mut rng: ResMut<GameRng>,
+    board: Res<Board>,
+    items: Res<Items>,
+    mut inventory: ResMut<Inventory>,
+    mut turn_ends: EventWriter<TurnEnd>,
+    mut combat_events: EventWriter<CombatEvent>,
+    mut recording: ResMut<BattleRecording>,
+    mut units: UnitQuery,
+) {
+    for issued in battle_commands.iter() {
+        let mut active = match turn.active {
+            Some(active) if active.entity == issued.unit => active,
+            _ => continue,
+        };
+
+        // The checksum is taken before the command is executed, but only commands that take
+        // effect are recorded
+        let recorded = recorded_command(active.entity, issued.command, &mut units);
+
+        let executed = match issued.command {
+            BattleCommand::Move(destination) if !active.has_moved => {
+                let snapshot = unit_snapshots(&mut units);
+                active.has_moved = move_unit(
+                    active.entity,
+                    destination,
+                    &board,
+                    &mut units,
+                    &mut commands,
+                    &mut combat_events,
+                );
+                if active.has_moved {
+                    turn.save_snapshot(snapshot);
+                }
+                active.has_moved
+            }
+            BattleCommand::UseAbility { ability, target } if !active.has_acted => {
+                let ability_use = AbilityUse {
+                    user: active.entity,
+                    ability,
+                    target,
+                };
+                active.has_acted = use_ability(
+                    ability_use,
+                    &board,
+                    rng.stream(RngStream::Combat),
+                    &mut units,
+                    &mut commands,
+                    &mut combat_events,
+                );
+                active.has_acted
+            }
+            BattleCommand::UseItem { slot, target } if !active.has_acted => {
+                active.has_acted = use_item(
+                    active.entity,
+                    slot,
+                    target,
+                    &items,
+                    &mut inventory,
+                    &mut units,
+                    &mut combat_events,
+                );
+                active.has_acted
+            }
+            BattleCommand::UndoMove => {
+                if let Some(snapshot) = turn.undo_move() {
+                    undo_move(
+                        active.entity,
+                        &snapshot,
+                        &board,
+                        &mut units,
+                        &mut combat_events,
+                    );
+                }
+                recording.commands.extend(recorded);
+                continue;
+            }
+            BattleCommand::EndTurn => {
+                turn.end_turn(&mut turn_ends);
+                recording.commands.extend(recorded);
+                continue;
+            }
+            BattleCommand::Flee => {
+                turn.fled = true;
+                recording.commands.extend(recorded);
+                continue;
+            }
+            _ => false,
+        };
+
+        if executed {
+            recording.commands.extend(recorded);
+        }
+
+        // Combatants can be defeated during their own turn, for example by walking into lava
+        let defeated = units
+            .get_mut(active.entity)
+            .map_or(true, |(_, combatant, ..)| !combatant.is_alive());
+
+        turn.active = Some(active);
+        if active.is_done() || defeated {
+            turn.end_turn(&mut turn_ends);
+        }
+    }
+}
+
+/// The command as it is recorded for replays, with a checksum of the combatants as they
+/// are now
+fn recorded_command(
+    entity: Entity,
+    command: BattleCommand,
+    units: &mut UnitQuery,
+) -> Option<RecordedCommand> {
+    let unit = match units.get_mut(entity) {
+        Ok((_, combatant, ..)) => combatant.name.clone(),
+        Err(_) => return None,
+    };
+
+    // Defeated combatants are only despawned at the end of the stage, so leave them out to
+    // get the same checksum no matter how many commands are executed in a frame
+    let checksum = replay::checksum(
+        units
+            .iter_mut()
+            .filter(|(_, combatant, ..)| combatant.is_alive())
+            .map(|(_, combatant, position, ..)| {
+                (combatant.name.clone(), combatant.health, position.0)
+            }),
+    );
+
+    Some(RecordedCommand {
+        unit,
+        command,
+        checksum,
+    })
+}
+
+/// Where every combatant stands and how healthy it is, to undo a move with
+fn unit_snapshots(units: &mut UnitQuery) -> Vec<UnitSnapshot> {
+    units
+        .iter_mut()
+        .map(|(entity, combatant, position, ..)| UnitSnapshot {
+            entity,
+            hex: position.0,
+            health: combatant.health,
+        })
+        .collect()
+}
+
+fn move_unit(
+    entity: Entity,
+    destination: Hex,
+    board: &Board,
+    units: &mut UnitQuery,
+    commands: &mut Commands,
+    combat_events: &mut EventWriter<CombatEvent>,
+) -> bool {
+    let occupied: HashSet<Hex> = units
+        .iter_mut()
+        .filter(|(e, combatant, ..)| *e != entity && combatant.is_alive())
+        .map(|(_, _, position, ..)| position.0)
+        .collect();
+
+    let (_, mut combatant, mut position, mut transform, effects) = match units.get_mut(entity) {
+        Ok(unit) => unit,
+        Err(_) => return false,
+    };
+
+    let range = combat::movement_range(&combatant.stats, &effects.modifiers());
+    if destination == position.0
+        || !board
+            .reachable(position.0, range, &occupied)
+            .contains(&destination)
+    {
+        return false;
+    }
+
+    combat_events.send(CombatEvent::Moved {
+        unit: LogUnit::new(&combatant),
+        from: position.0,
+        to: destination,
+    });
+
+    position.0 = destination;
+    transform.translation = super::unit_translation(board, destination);
+
+    let damage = board.tile(destination).terrain.damage_on_enter();
+    if damage > 0 {
+        combatant.take_damage(damage);
+
+        let unit = LogUnit::new(&combatant);
+        combat_events.send(CombatEvent::Damaged {
+            unit: unit.clone(),
+            hex: destination,
+            amount: damage,
+        });
+
+        if !combatant.is_alive() {
+            combat_events.send(CombatEvent::Defeated { unit });
+            commands.despawn_recursive(entity);
+        }
+    }
+
+    true
+}
+
+fn undo_move(
+    entity: Entity,
+    snapshot: &[UnitSnapshot],
+    board: &Board,
+    units: &mut UnitQuery,
+    combat_events: &mut EventWriter<CombatEvent>,
+) {
+    for unit in snapshot {
+        if let Ok((_, mut combatant, mut position, mut transform, _)) = units.get_mut(unit.entity) {
+            combatant.health = unit.health;
+            position.0 = unit.hex;
+            transform.translation = super::unit_translation(board, unit.hex);
+        }
+    }
+
+    if let Ok((_, combatant, ..)) = units.get_mut(entity) {
+        combat_events.send(CombatEvent::MoveUndone {
+            unit: LogUnit::new(&combatant),
+        });
+    }
+}
+
+/// A combatant using one of its abilities on the combatant standing on `target`
+struct AbilityUse {
+    user: Entity,
+    /// Index in the user's abilities
+    ability: usize,
+    target: Hex,
+}
+
+fn use_ability(
+    ability_use: AbilityUse,
+    board: &Board,
+    rng: &mut impl Rng,
+    units: &mut UnitQuery,
+    commands: &mut Commands,
+    combat_events: &mut EventWriter<CombatEvent>,
+) -> bool {
+    let AbilityUse {
+        user,
+        ability: ability_index,
+        target,
+    } = ability_use;
+
+    let (actor, attacker, attacker_modifiers, position, ability) = match units.get_mut(user) {
+        Ok((_, combatant, position, _, effects)) => match combatant.abilities.get(ability_index) {
+            Some(ability) => (
+                LogUnit::new(&combatant),
+                combatant.stats,
+                effects.modifiers(),
+                position.0,
+                ability.clone(),
+            ),
+            None => return false,
+        },
+        Err(_) => return false,
+    };
+
+    if position.distance(target) > board.attack_range(position, target, ability.range) {
+        return false;
+    }
+
+    let occupied: HashSet<Hex> = units
+        .iter_mut()
+        .filter(|(_, combatant, ..)| combatant.is_alive())
+        .map(|(_, _, position, ..)| position.0)
+        .collect();
+    let cover = board.cover(position, target, &occupied);
+    if cover == Cover::Full {
+        return false;
+    }
+
+    let target_entity = units
+        .iter_mut()
+        .find(|(_, combatant, position, ..)| {
+            position.0 == target && combatant.team != actor.team && combatant.is_alive()
+        })
+        .map(|(e, ..)| e);
+
+    let target_entity = match target_entity {
+        Some(e) => e,
+        None => return false,
+    };
+
+    let (_, mut defender, _, _, mut defender_effects) = units.get_mut(target_entity).unwrap();
+    let defender_unit = LogUnit::new(&defender);
+
+    combat_events.send(CombatEvent::AbilityUsed {
+        unit: actor.clone(),
+        ability: ability.name.clone(),
+        target: defender_unit.clone(),
+    });
+
+    if rng.gen::<f32>() < cover.hit_chance(ability.accuracy) {
+        let amount = combat::damage(
+            ability.power,
+            &attacker,
+            &attacker_modifiers,
+            &defender.stats,
+            &defender_effects
+                .modifiers()
+                .combine(board.modifiers(target)),
+        );
+        defender.take_damage(amount);
+
+        combat_events.send(CombatEvent::Damaged {
+            unit: defender_unit.clone(),
+            hex: target,
+            amount,
+        });
+
+        if !defender.is_alive() {
+            combat_events.send(CombatEvent::Defeated {
+                unit: defender_unit,
+            });
+            commands.despawn_recursive(target_entity);
+        } else if let Some(effect) = ability.effect {
+            if defender_effects.apply(effect.kind, effect.turns) {
+                combat_events.send(CombatEvent::StatusApplied {
+                    unit: defender_unit,
+                    effect: effect.kind,
+                });
+            }
+        }
+    } else {
+        combat_events.send(CombatEvent::Missed {
+            unit: actor,
+            target: defender_unit,
+        });
+    }
+
+    true
+}
+
+fn use_item(
+    entity: Entity,
+    slot: usize,
+    target: Hex,
+    items: &Items,
+    inventory: &mut Inventory,
+    units: &mut UnitQuery,
+    combat_events: &mut EventWriter<CombatEvent>,
+) -> bool {
+    let item = match inventory.stacks().get(slot) {
+        Some(stack) => stack.item.clone(),
+        None => return false,
+    };
+
+    let effect = match items.get(&item).effect {
+        Some(effect) if effect.in_battle() => effect,
+        _ => return false,
+    };
+
+    let (actor, position) = match units.get_mut(entity) {
+        Ok((_, combatant, position, ..)) => (LogUnit::new(&combatant), position.0),
+        Err(_) => return false,
+    };
+
+    if position.distance(target) > ITEM_RANGE {
+        return false;
+    }
+
+    let target_entity = units
+        .iter_mut()
+        .find(|(_, combatant, position, ..)| {
+            position.0 == target && combatant.team == actor.team && combatant.is_alive()
+        })
+        .map(|(e, ..)| e);
+
+    let target_entity = match target_entity {
+        Some(e) => e,
+        None => return false,
+    };
+
+    let (_, mut combatant, _, _, mut effects) = units.get_mut(target_entity).unwrap();
+    if !effect.can_use_on(&combatant, &effects) {
+        return false;
+    }
+
+    let unit = LogUnit::new(&combatant);
+    let result = match effect {
+        ItemEffect::Heal(amount) => {
+            let health = combatant.health;
+            combatant.take_damage(-amount);
+
+            CombatEvent::Healed {
+                unit: unit.clone(),
+                hex: target,
+                amount: combatant.health - health,
+            }
+        }
+        ItemEffect::Cure(kind) => {
+            effects.remove(kind);
+            CombatEvent::StatusCured {
+                unit: unit.clone(),
+                effect: kind,
+            }
+        }
+        // Only usable outside of battle
+        ItemEffect::GainExperience(_) => return false,
+    };
+
+    inventory.remove(&item, 1);
+
+    combat_events.send(CombatEvent::ItemUsed {
+        unit: actor,
+        item,
+        target: unit,
+    });
+    combat_events.send(result);
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battle::ai::{enemy_ai_turns, AiController, AiPersonalities};
+    use crate::battle::combat::{Stats, Team};
+    use crate::battle::encounter::Encounter;
+    use crate::battle::status_effects::{status_effects_turn_start, StatusEffectKind};
+    use crate::battle::turn::TurnStart;
+    use crate::battle::BattleSystem;
+
+    /// Inserts the resources needed to execute commands in a battle with the combatants
+    fn insert_battle_resources(world: &mut World, players: Vec<Combatant>) {
+        world.insert_resource(Events::<TurnStart>::default());
+        world.insert_resource(Events::<TurnEnd>::default());
+        world.insert_resource(Events::<CombatEvent>::default());
+        world.insert_resource(Events::<IssuedCommand>::default());
+        world.insert_resource(Board::default());
+        world.insert_resource(GameRng::new(1));
+        world.insert_resource(Items::default());
+        world.insert_resource(Inventory::default());
+        world.insert_resource(BattleRecording::new(
+            GameRng::new(1),
+            Encounter::load("bevytown_goblins"),
+            players,
+            Inventory::default(),
+        ));
+    }
+
+    fn spawn_poisoned_unit(world: &mut World, combatant: Combatant, hex: Hex) -> Entity {
+        let mut effects = StatusEffects::default();
+        effects.apply(StatusEffectKind::Poison, 2);
+
+        world
+            .spawn()
+            .insert_bundle((combatant, HexPosition(hex), Transform::default(), effects))
+            .id()
+    }
+
+    fn begin_turn(world: &mut World, unit: Entity) {
+        let mut turn = TurnState::new(vec![unit]);
+        turn.begin_turn(unit);
+        world.insert_resource(turn);
+        world
+            .get_resource_mut::<Events<TurnStart>>()
+            .unwrap()
+            .send(TurnStart(unit));
+    }
+
+    fn stats() -> Stats {
+        Stats {
+            max_health: 10,
+            attack: 1,
+            defence: 1,
+            movement: 3,
+        }
+    }
+
+    #[test]
+    fn undoing_a_move_keeps_poison_damage() {
+        let mut world = World::default();
+        let combatant = Combatant::new("Hero", Team::Player, stats(), Vec::new());
+        insert_battle_resources(&mut world, vec![combatant.clone()]);
+
+        let start = Hex::new(0, 0);
+        let unit = spawn_poisoned_unit(&mut world, combatant, start);
+        let poison_damage = world
+            .get::<StatusEffects>(unit)
+            .unwrap()
+            .turn_start_damage();
+        begin_turn(&mut world, unit);
+
+        let mut battle_commands = world.get_resource_mut::<Events<IssuedCommand>>().unwrap();
+        for command in [BattleCommand::Move(Hex::new(1, 0)), BattleCommand::UndoMove].iter() {
+            battle_commands.send(IssuedCommand {
+                unit,
+                command: *command,
+            });
+        }
+
+        SystemStage::parallel()
+            .with_system(
+                status_effects_turn_start
+                    .system()
+                    .label(BattleSystem::TurnStartEffects),
+            )
+            .with_system(
+                execute_battle_commands
+                    .system()
+                    .after(BattleSystem::TurnStartEffects),
+            )
+            .run(&mut world);
+
+        assert_eq!(world.get::<HexPosition>(unit).unwrap().0, start);
+        assert_eq!(
+            world.get::<Combatant>(unit).unwrap().health,
+            10 - poison_damage
+        );
+    }
+
+    #[test]
+    fn rejected_commands_are_not_recorded() {
+        let mut world = World::default();
+        let combatant = Combatant::new("Hero", Team::Player, stats(), Vec::new());
+        insert_battle_resources(&mut world, vec![combatant.clone()]);
+
+        let unit = world
+            .spawn()
+            .insert_bundle((
+                combatant,
+                HexPosition(Hex::new(0, 0)),
+                Transform::default(),
+                StatusEffects::default(),
+            ))
+            .id();
+        begin_turn(&mut world, unit);
+
+        // Out of range, then a valid move, then a second move in the same turn
+        let mut battle_commands = world.get_resource_mut::<Events<IssuedCommand>>().unwrap();
+        for destination in [Hex::new(5, 0), Hex::new(1, 0), Hex::new(2, 0)].iter() {
+            battle_commands.send(IssuedCommand {
+                unit,
+                command: BattleCommand::Move(*destination),
+            });
+        }
+
+        SystemStage::single(execute_battle_commands.system()).run(&mut world);
+
+        let recorded: Vec<_> = world
+            .get_resource::<BattleRecording>()
+            .unwrap()
+            .commands
+            .iter()
+            .map(|recorded| recorded.command)
+            .collect();
+        assert_eq!(recorded, vec![BattleCommand::Move(Hex::new(1, 0))]);
+    }
+
+    #[test]
+    fn units_defeated_by_poison_issue_no_commands() {
+        let mut world = World::default();
+        let hero = Combatant::new("Hero", Team::Player, stats(), Vec::new());
+        insert_battle_resources(&mut world, vec![hero.clone()]);
+
+        world.spawn().insert_bundle((
+            hero,
+            HexPosition(Hex::new(1, 0)),
+            Transform::default(),
+            StatusEffects::default(),
+        ));
+
+        let mut goblin = Combatant::new("Goblin", Team::Enemy, stats(), Vec::new());
+        goblin.health = 1;
+        let unit = spawn_poisoned_unit(&mut world, goblin, Hex::new(0, 0));
+        world.entity_mut(unit).insert(AiController {
+            personality: AiPersonalities::default().get("brute").clone(),
+        });
+        begin_turn(&mut world, unit);
+
+        SystemStage::parallel()
+            .with_system(
+                status_effects_turn_start
+                    .system()
+                    .label(BattleSystem::TurnStartEffects),
+            )
+            .with_system(
+                enemy_ai_turns
+                    .system()
+                    .label(BattleSystem::IssueCommands)
+                    .after(BattleSystem::TurnStartEffects),
+            )
+            .with_system(
+                execute_battle_commands
+                    .system()
+                    .after(BattleSystem::IssueCommands),
+            )
+            .run(&mut world);
+
+        assert!(world.get_entity(unit).is_none());
+        assert_eq!(
+            world
+                .get_resource_mut::<Events<IssuedCommand>>()
+                .unwrap()
+                .drain()
+                .count(),
+            0
+        );
+        assert!(world
+            .get_resource::<BattleRecording>()
+            .unwrap()
+            .commands
+            .is_empty());
+    }
+}
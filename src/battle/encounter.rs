@@ -0,0 +1,140 @@
+//! Encounter definitions, describing everything about a battle except the player's side
+//!
+//! Encounters are stored in `assets/data/encounters`.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::battle::board::Tile;
+use crate::battle::combat::{Ability, Stats};
+use crate::battle::hex::Hex;
+
+/// Extra maximum health of enemies per level
+const HEALTH_PER_LEVEL: i32 = 4;
+
+/// Levels needed for enemies to gain a point of attack
+const LEVELS_PER_ATTACK: i32 = 2;
+
+/// Levels needed for enemies to gain a point of defence
+const LEVELS_PER_DEFENCE: i32 = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Encounter {
+    /// Shown in the HUD area label when the battle starts
+    pub title: String,
+    pub board: BoardLayout,
+    pub enemies: Vec<EnemySpawn>,
+    /// Path of the music to play during the battle, relative to the assets directory
+    #[serde(default)]
+    pub music: Option<String>,
+    pub rewards: Rewards,
+}
+
+impl Encounter {
+    /// Loads an encounter from `assets/data/encounters`
+    pub fn load(name: &str) -> Self {
+        crate::data::load(&format!("encounters/{}.ron", name))
+    }
+
+    /// Makes the enemies stronger and the rewards larger for every level above the first
+    ///
+    /// Encounters are defined at level 1.
+    pub fn at_level(mut self, level: u32) -> Self {
+        let bonus_levels = level.saturating_sub(1) as i32;
+
+        for enemy in &mut self.enemies {
+            enemy.stats.max_health += bonus_levels * HEALTH_PER_LEVEL;
+            enemy.stats.attack += bonus_levels / LEVELS_PER_ATTACK;
+            enemy.stats.defence += bonus_levels / LEVELS_PER_DEFENCE;
+        }
+        self.rewards.experience += bonus_levels as u32 * self.rewards.experience / 2;
+        self.rewards.gold += bonus_levels as u32 * self.rewards.gold / 2;
+
+        self
+    }
+
+    /// How many members of the party can fight, one per player spawn
+    ///
+    /// The first members are placed on the spawns in order. The others sit the battle out,
+    /// and gain no experience from it.
+    pub fn max_fighters(&self) -> usize {
+        self.board.player_spawns.len()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoardLayout {
+    /// Radius of the board, in hexes
+    pub radius: i32,
+    /// Where the player's combatants are placed, in order
+    pub player_spawns: Vec<Hex>,
+    /// Tiles that are not flat grass
+    #[serde(default)]
+    pub tiles: HashMap<Hex, Tile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnemySpawn {
+    pub name: String,
+    pub position: Hex,
+    /// Name of the AI personality, as defined in `data/ai_personalities.ron`
+    pub personality: String,
+    pub stats: Stats,
+    pub abilities: Vec<Ability>,
+}
+
+/// What the player gets for winning the battle
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Rewards {
+    pub experience: u32,
+    /// Money added to the wallet
+    #[serde(default)]
+    pub gold: u32,
+    /// Names of the items that are dropped
+    #[serde(default)]
+    pub loot: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battle::ai::AiPersonalities;
+    use crate::inventory::Items;
+
+    #[test]
+    fn encounter_files_are_valid() {
+        let items = Items::default();
+        let personalities = AiPersonalities::default();
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/data/encounters");
+
+        for file in std::fs::read_dir(dir).unwrap() {
+            let path = file.unwrap().path();
+            let name = path.file_stem().unwrap().to_str().unwrap();
+            let encounter = Encounter::load(name);
+
+            assert!(!encounter.enemies.is_empty(), "{}", name);
+            assert!(!encounter.board.player_spawns.is_empty(), "{}", name);
+            for enemy in &encounter.enemies {
+                personalities.get(&enemy.personality);
+            }
+            for item in &encounter.rewards.loot {
+                items.get(item);
+            }
+        }
+    }
+
+    #[test]
+    fn higher_levels_are_stronger() {
+        let encounter = Encounter::load("bevytown_goblins");
+        let levelled = encounter.clone().at_level(3);
+
+        assert_eq!(
+            encounter.clone().at_level(1).enemies[0].stats,
+            encounter.enemies[0].stats
+        );
+        assert!(levelled.enemies[0].stats.max_health > encounter.enemies[0].stats.max_health);
+        assert!(levelled.rewards.experience > encounter.rewards.experience);
+        assert!(levelled.rewards.gold > encounter.rewards.gold);
+    }
+}
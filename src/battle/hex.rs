@@ -0,0 +1,219 @@
+//! Axial coordinates on the hexagonal battle board
+//!
+//! The board uses "pointy top" hexes, see <https://www.redblobgames.com/grids/hexagons/>
+//! for an explanation of the coordinate system.
+
+use std::ops::{Add, Sub};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Distance from the center of a hex to one of its corners, in world units
+///
+/// Matches the scale at which the board mesh is spawned.
+pub const HEX_SIZE: f32 = 0.8;
+
+/// The six directions to a hex's neighbours, starting east and going counter-clockwise
+const DIRECTIONS: [Hex; 6] = [
+    Hex::new(1, 0),
+    Hex::new(1, -1),
+    Hex::new(0, -1),
+    Hex::new(-1, 0),
+    Hex::new(-1, 1),
+    Hex::new(0, 1),
+];
+
+/// Offset added to the points of a line before rounding them to hexes
+///
+/// Keeps lines running exactly along the edge between two hexes from being rounded
+/// differently depending on floating point errors.
+const LINE_NUDGE: f32 = 1e-3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Hex {
+    pub q: i32,
+    pub r: i32,
+}
+
+impl Hex {
+    pub const ZERO: Hex = Hex::new(0, 0);
+
+    pub const fn new(q: i32, r: i32) -> Self {
+        Hex { q, r }
+    }
+
+    /// The implicit third cube coordinate
+    pub fn s(self) -> i32 {
+        -self.q - self.r
+    }
+
+    /// Number of steps needed to walk from this hex to `other`
+    pub fn distance(self, other: Hex) -> i32 {
+        let d = self - other;
+        (d.q.abs() + d.r.abs() + d.s().abs()) / 2
+    }
+
+    pub fn neighbours(self) -> [Hex; 6] {
+        let mut neighbours = DIRECTIONS;
+        for n in &mut neighbours {
+            *n = *n + self;
+        }
+        neighbours
+    }
+
+    /// All hexes at most `radius` steps away from this one, including itself
+    pub fn within(self, radius: i32) -> impl Iterator<Item = Hex> {
+        (-radius..=radius).flat_map(move |q| {
+            let r_min = (-radius).max(-q - radius);
+            let r_max = radius.min(-q + radius);
+            (r_min..=r_max).map(move |r| self + Hex::new(q, r))
+        })
+    }
+
+    /// The hexes a straight line from this hex to `other` passes through, including both ends
+    pub fn line(self, other: Hex) -> Vec<Hex> {
+        self.nudged_line(other, LINE_NUDGE)
+    }
+
+    /// For every hex of the line to `other`, the two hexes the line might pass through
+    ///
+    /// The two hexes only differ where the line runs exactly along the edge between them.
+    /// Swapping the ends of the line yields the same pairs in reverse order, although the
+    /// two hexes of a pair may be swapped.
+    pub fn line_candidates(self, other: Hex) -> impl Iterator<Item = (Hex, Hex)> {
+        self.nudged_line(other, LINE_NUDGE)
+            .into_iter()
+            .zip(self.nudged_line(other, -LINE_NUDGE))
+    }
+
+    fn nudged_line(self, other: Hex, nudge: f32) -> Vec<Hex> {
+        let steps = self.distance(other);
+        if steps == 0 {
+            return vec![self];
+        }
+
+        (0..=steps)
+            .map(|step| {
+                let t = step as f32 / steps as f32;
+                let q = self.q as f32 + (other.q - self.q) as f32 * t + nudge;
+                let r = self.r as f32 + (other.r - self.r) as f32 * t + 2.0 * nudge;
+                Hex::round(q, r)
+            })
+            .collect()
+    }
+
+    /// Center of the hex on the board plane
+    pub fn to_world(self) -> Vec3 {
+        let x = HEX_SIZE * 3f32.sqrt() * (self.q as f32 + self.r as f32 / 2.0);
+        let z = HEX_SIZE * 1.5 * self.r as f32;
+        Vec3::new(x, 0.0, z)
+    }
+
+    /// The hex containing the given point on the board plane
+    pub fn from_world(position: Vec3) -> Self {
+        let q = (3f32.sqrt() / 3.0 * position.x - position.z / 3.0) / HEX_SIZE;
+        let r = (2.0 / 3.0 * position.z) / HEX_SIZE;
+        Hex::round(q, r)
+    }
+
+    /// Rounds fractional axial coordinates to the nearest hex
+    fn round(q: f32, r: f32) -> Self {
+        let s = -q - r;
+        let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+        let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+
+        if dq > dr && dq > ds {
+            rq = -rr - rs;
+        } else if dr > ds {
+            rr = -rq - rs;
+        }
+
+        Hex::new(rq as i32, rr as i32)
+    }
+}
+
+impl Add for Hex {
+    type Output = Hex;
+
+    fn add(self, other: Hex) -> Hex {
+        Hex::new(self.q + other.q, self.r + other.r)
+    }
+}
+
+impl Sub for Hex {
+    type Output = Hex;
+
+    fn sub(self, other: Hex) -> Hex {
+        Hex::new(self.q - other.q, self.r - other.r)
+    }
+}
+
+/// Position of an entity on the battle board
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HexPosition(pub Hex);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn neighbours_are_one_step_away() {
+        let center = Hex::new(2, -1);
+
+        for n in center.neighbours().iter() {
+            assert_eq!(center.distance(*n), 1);
+        }
+    }
+
+    #[test]
+    fn within_covers_the_whole_area() {
+        for radius in 0..5 {
+            let hexes: Vec<Hex> = Hex::new(1, 1).within(radius).collect();
+
+            assert_eq!(hexes.len() as i32, 3 * radius * (radius + 1) + 1);
+            assert!(hexes.iter().all(|h| h.distance(Hex::new(1, 1)) <= radius));
+        }
+    }
+
+    #[test]
+    fn lines_are_connected() {
+        for from in Hex::ZERO.within(4) {
+            for to in Hex::ZERO.within(4) {
+                let line = from.line(to);
+
+                assert_eq!(line.len() as i32, from.distance(to) + 1);
+                assert_eq!(line.first(), Some(&from));
+                assert_eq!(line.last(), Some(&to));
+                assert!(line.windows(2).all(|w| w[0].distance(w[1]) == 1));
+            }
+        }
+    }
+
+    fn unordered((a, b): (Hex, Hex)) -> (Hex, Hex) {
+        if (a.q, a.r) <= (b.q, b.r) {
+            (a, b)
+        } else {
+            (b, a)
+        }
+    }
+
+    #[test]
+    fn line_candidates_are_symmetric() {
+        for from in Hex::ZERO.within(4) {
+            for to in Hex::ZERO.within(4) {
+                let forward: Vec<_> = from.line_candidates(to).map(unordered).collect();
+                let mut backward: Vec<_> = to.line_candidates(from).map(unordered).collect();
+                backward.reverse();
+
+                assert_eq!(forward, backward, "line from {:?} to {:?}", from, to);
+            }
+        }
+    }
+
+    #[test]
+    fn world_position_round_trips() {
+        for hex in Hex::ZERO.within(6) {
+            assert_eq!(Hex::from_world(hex.to_world()), hex);
+        }
+    }
+}
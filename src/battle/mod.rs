@@ -1,21 +1,90 @@
+use std::collections::HashMap;
+
 use bevy::prelude::*;
+use bevy::render::mesh::Indices;
+use bevy::render::pipeline::PrimitiveTopology;
 
-use self::camera::Camera;
+use self::ai::{AiController, AiPersonalities};
+use self::board::{Board, Terrain};
+use self::camera::{Camera, FrameHexes};
+use self::combat::{Combatant, Team};
+use self::combat_log::CombatEvent;
+use self::command::IssuedCommand;
+use self::encounter::Encounter;
+use self::hex::{Hex, HexPosition, HEX_SIZE};
+use self::overlay::OverlayAssets;
+use self::replay::Replay;
+use self::status_effects::StatusEffects;
+use self::turn::{TurnEnd, TurnStart, TurnState};
 
 use crate::hud_area_label::HudAreaLabel;
+use crate::inventory::Items;
+use crate::menu::{button, button_interact};
+use crate::party::{Characters, Party};
 use crate::AppState;
 use crate::Stage;
 
+pub mod ai;
+pub mod board;
 pub mod camera;
+pub mod combat;
+pub mod combat_log;
+pub mod command;
+pub mod encounter;
+pub mod hex;
+pub mod outcome;
+pub mod overlay;
+pub mod player_input;
+pub mod replay;
+pub mod status_effects;
+pub mod turn;
+
+/// How far tiles reach below the board, so that flat tiles have visible sides
+const TILE_DEPTH: f32 = 0.1;
 
 /// Marker for despawning when exiting `AppState::Battle`
 pub struct StateCleanup;
 
+/// The battle to set up when entering `AppState::Battle`
+pub struct BattleResource {
+    pub encounter: Encounter,
+    /// ID of the `BattleStarter` that started the battle, if any
+    pub starter_id: Option<String>,
+}
+
+/// Labels ordering the systems that play out a turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+pub enum BattleSystem {
+    StartTurn,
+    TurnStartEffects,
+    IssueCommands,
+    ExecuteCommands,
+    TurnEndEffects,
+    CheckOutcome,
+}
+
 pub struct BattlePlugin;
 impl Plugin for BattlePlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.on_state_enter(Stage::AppState, AppState::Battle, setup_battle.system())
+        app.add_event::<TurnStart>()
+            .add_event::<TurnEnd>()
+            .add_event::<IssuedCommand>()
+            .add_event::<CombatEvent>()
+            .add_event::<FrameHexes>()
+            .init_resource::<AiPersonalities>()
+            .init_resource::<OverlayAssets>()
+            .on_state_enter(Stage::AppState, AppState::Battle, setup_battle.system())
             .on_state_enter(Stage::AppState, AppState::Battle, show_area_title.system())
+            .on_state_enter(
+                Stage::AppState,
+                AppState::Battle,
+                combat_log::setup_combat_log.system(),
+            )
+            .on_state_enter(
+                Stage::AppState,
+                AppState::Battle,
+                replay::start_recording.system(),
+            )
             .on_state_update(
                 Stage::AppState,
                 AppState::Battle,
@@ -24,23 +93,208 @@ impl Plugin for BattlePlugin {
             .on_state_update(
                 Stage::AppState,
                 AppState::Battle,
-                back_to_overworld.system(),
+                camera::pan_camera.system(),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Battle,
+                camera::zoom_camera.system(),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Battle,
+                camera::focus_active_unit.system(),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Battle,
+                camera::frame_hexes.system(),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Battle,
+                camera::move_camera.system(),
+            )
+            // A turn is started, its start-of-turn effects resolved, and only then are the
+            // commands for it issued and executed
+            .on_state_update(
+                Stage::AppState,
+                AppState::Battle,
+                turn::start_next_turn
+                    .system()
+                    .label(BattleSystem::StartTurn),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Battle,
+                status_effects::status_effects_turn_start
+                    .system()
+                    .label(BattleSystem::TurnStartEffects)
+                    .after(BattleSystem::StartTurn),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Battle,
+                player_input::player_commands
+                    .system()
+                    .label(BattleSystem::IssueCommands)
+                    .after(BattleSystem::TurnStartEffects),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Battle,
+                replay::play_replay
+                    .system()
+                    .label(BattleSystem::IssueCommands)
+                    .after(BattleSystem::TurnStartEffects),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Battle,
+                ai::enemy_ai_turns
+                    .system()
+                    .label(BattleSystem::IssueCommands)
+                    .after(BattleSystem::TurnStartEffects),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Battle,
+                command::execute_battle_commands
+                    .system()
+                    .label(BattleSystem::ExecuteCommands)
+                    .after(BattleSystem::IssueCommands),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Battle,
+                replay::verify_replay
+                    .system()
+                    .after(BattleSystem::ExecuteCommands),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Battle,
+                status_effects::status_effects_turn_end
+                    .system()
+                    .label(BattleSystem::TurnEndEffects)
+                    .after(BattleSystem::ExecuteCommands),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Battle,
+                outcome::check_battle_outcome
+                    .system()
+                    .label(BattleSystem::CheckOutcome)
+                    .after(BattleSystem::ExecuteCommands),
+            )
+            // Events are logged once every system of the turn has sent them, so they are
+            // logged with the round they happened in
+            .on_state_update(
+                Stage::AppState,
+                AppState::Battle,
+                combat_log::record_combat_events
+                    .system()
+                    .after(BattleSystem::ExecuteCommands)
+                    .after(BattleSystem::TurnEndEffects)
+                    .after(BattleSystem::CheckOutcome),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Battle,
+                combat_log::scroll_combat_log.system(),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Battle,
+                combat_log::update_combat_log_panel.system(),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Battle,
+                combat_log::export_combat_log.system(),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Battle,
+                overlay::update_health_bars.system(),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Battle,
+                overlay::spawn_floating_numbers.system(),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Battle,
+                overlay::update_floating_numbers.system(),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Battle,
+                button_interact::<button::LeaveBattle>
+                    .system()
+                    .chain(outcome::button_leave_battle.system()),
+            )
+            .on_state_exit(
+                Stage::AppState,
+                AppState::Battle,
+                replay::save_recording.system(),
             )
             .on_state_exit(
                 Stage::AppState,
                 AppState::Battle,
                 crate::despawn_all::<StateCleanup>.system(),
             );
+
+        replay::setup_replay(app);
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn setup_battle(
     mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut asset_server: ResMut<AssetServer>,
+    audio: Res<Audio>,
+    battle: Res<BattleResource>,
+    personalities: Res<AiPersonalities>,
+    overlay_assets: Res<OverlayAssets>,
+    party: Res<Party>,
+    characters: Res<Characters>,
+    items: Res<Items>,
+    replay: Option<Res<Replay>>,
 ) {
-    spawn_board(&mut commands, &mut asset_server, &mut materials);
-    let _camera_entity = spawn_camera(&mut commands);
+    let encounter = &battle.encounter;
+
+    let board = Board::from_layout(&encounter.board);
+
+    spawn_board(
+        &mut commands,
+        &mut asset_server,
+        &mut meshes,
+        &mut materials,
+        &board,
+    );
+    let _camera_entity = spawn_camera(&mut commands, &board);
+
+    let combatant_meshes = CombatantMeshes::new(&mut meshes, &mut materials);
+    let turn_order = spawn_combatants(
+        &mut commands,
+        &combatant_meshes,
+        &board,
+        player_combatants(&party, &characters, &items, replay.as_deref()),
+        encounter,
+        &personalities,
+        &overlay_assets,
+    );
+
+    commands.insert_resource(board);
+    commands.insert_resource(TurnState::new(turn_order));
+
+    if let Some(music) = &encounter.music {
+        audio.play(asset_server.load(music.as_str()));
+    }
 
     commands
         .spawn(LightBundle {
@@ -57,21 +311,233 @@ fn setup_battle(
 fn spawn_board(
     commands: &mut Commands,
     asset_server: &mut AssetServer,
+    meshes: &mut Assets<Mesh>,
     materials: &mut Assets<StandardMaterial>,
+    board: &Board,
 ) {
     let mesh = asset_server.load("meshes/hex.gltf#Mesh0/Primitive0");
 
+    // The outline sits just above the flat tiles
+    let mut transform = Transform::from_xyz(0.0, 0.01, 0.0);
+    transform.scale = Vec3::splat(0.8); // More magic numbers
+
     commands
         .spawn(PbrBundle {
             mesh,
-            transform: Transform::from_scale(Vec3::splat(0.8)), // More magic numbers
+            transform,
             material: materials.add(Color::WHITE.into()),
             ..Default::default()
         })
         .with(StateCleanup);
+
+    let tile_mesh = meshes.add(hex_prism(HEX_SIZE * 0.95));
+    let mut tile_materials = HashMap::new();
+
+    for (hex, tile) in board.tiles() {
+        let material = tile_materials
+            .entry(tile.terrain)
+            .or_insert_with(|| materials.add(terrain_color(tile.terrain).into()))
+            .clone();
+
+        // Tiles are prisms of height 1, stretched to reach from below the board to their top
+        let depth = board.height(hex) + TILE_DEPTH;
+        let mut transform = Transform::from_translation(hex.to_world());
+        transform.translation.y = board.height(hex) - depth;
+        transform.scale.y = depth;
+
+        commands
+            .spawn(PbrBundle {
+                mesh: tile_mesh.clone(),
+                material,
+                transform,
+                ..Default::default()
+            })
+            .with(StateCleanup);
+    }
 }
 
-fn spawn_camera(commands: &mut Commands) -> Entity {
+fn terrain_color(terrain: Terrain) -> Color {
+    match terrain {
+        Terrain::Grass => Color::rgb(0.35, 0.6, 0.3),
+        Terrain::Water => Color::rgb(0.2, 0.4, 0.8),
+        Terrain::Forest => Color::rgb(0.15, 0.4, 0.2),
+        Terrain::Rock => Color::rgb(0.5, 0.5, 0.5),
+        Terrain::Lava => Color::rgb(0.9, 0.35, 0.1),
+    }
+}
+
+/// A pointy-top hexagonal prism with the given corner radius, reaching from y = 0 to y = 1
+fn hex_prism(size: f32) -> Mesh {
+    let corners: Vec<Vec3> = (0..6)
+        .map(|i| {
+            let angle = (60.0 * i as f32 - 30.0).to_radians();
+            Vec3::new(size * angle.cos(), 0.0, size * angle.sin())
+        })
+        .collect();
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut indices = Vec::new();
+
+    // Top face, as a fan around the centre
+    positions.push([0.0, 1.0, 0.0]);
+    normals.push([0.0, 1.0, 0.0]);
+    for corner in &corners {
+        positions.push([corner.x, 1.0, corner.z]);
+        normals.push([0.0, 1.0, 0.0]);
+    }
+    for i in 0..6 {
+        indices.extend_from_slice(&[0, 1 + (i + 1) % 6, 1 + i]);
+    }
+
+    // Sides, each with its own vertices for flat shading
+    for i in 0..6 {
+        let (a, b) = (corners[i], corners[(i + 1) % 6]);
+        let normal = ((a + b) / 2.0).normalize();
+        let start = positions.len() as u32;
+
+        for &(corner, y) in &[(a, 0.0), (a, 1.0), (b, 0.0), (b, 1.0)] {
+            positions.push([corner.x, y, corner.z]);
+            normals.push([normal.x, normal.y, normal.z]);
+        }
+        indices.extend_from_slice(&[start, start + 1, start + 2, start + 2, start + 1, start + 3]);
+    }
+
+    let uvs = vec![[0.0, 0.0]; positions.len()];
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+/// Mesh and team materials shared by all combatants
+struct CombatantMeshes {
+    mesh: Handle<Mesh>,
+    player_material: Handle<StandardMaterial>,
+    enemy_material: Handle<StandardMaterial>,
+}
+
+impl CombatantMeshes {
+    fn new(meshes: &mut Assets<Mesh>, materials: &mut Assets<StandardMaterial>) -> Self {
+        CombatantMeshes {
+            mesh: meshes.add(Mesh::from(shape::Box::new(0.6, 1.0, 0.6))),
+            player_material: materials.add(Color::rgb(0.2, 0.4, 0.9).into()),
+            enemy_material: materials.add(Color::rgb(0.9, 0.2, 0.2).into()),
+        }
+    }
+}
+
+/// Spawns the player's combatants and the enemies of the encounter, and returns them in turn order
+fn spawn_combatants(
+    commands: &mut Commands,
+    combatant_meshes: &CombatantMeshes,
+    board: &Board,
+    players: Vec<Combatant>,
+    encounter: &Encounter,
+    personalities: &AiPersonalities,
+    overlay_assets: &OverlayAssets,
+) -> Vec<Entity> {
+    let mut turn_order = Vec::new();
+
+    // Members without a spawn point sit the battle out, see `Encounter::max_fighters`
+    for (combatant, spawn) in players.into_iter().zip(&encounter.board.player_spawns) {
+        turn_order.push(spawn_combatant(
+            commands,
+            overlay_assets,
+            board,
+            combatant_meshes.mesh.clone(),
+            combatant_meshes.player_material.clone(),
+            combatant,
+            *spawn,
+        ));
+    }
+
+    for enemy in &encounter.enemies {
+        let combatant = Combatant::new(
+            enemy.name.clone(),
+            Team::Enemy,
+            enemy.stats,
+            enemy.abilities.clone(),
+        );
+
+        let entity = spawn_combatant(
+            commands,
+            overlay_assets,
+            board,
+            combatant_meshes.mesh.clone(),
+            combatant_meshes.enemy_material.clone(),
+            combatant,
+            enemy.position,
+        );
+        commands.insert_one(
+            entity,
+            AiController {
+                personality: personalities.get(&enemy.personality).clone(),
+            },
+        );
+        turn_order.push(entity);
+    }
+
+    turn_order
+}
+
+/// Spawns a combatant on the board, along with its health bar
+fn spawn_combatant(
+    commands: &mut Commands,
+    overlay_assets: &OverlayAssets,
+    board: &Board,
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+    combatant: Combatant,
+    hex: Hex,
+) -> Entity {
+    let team = combatant.team;
+
+    let entity = commands
+        .spawn(PbrBundle {
+            mesh,
+            material,
+            transform: Transform::from_translation(unit_translation(board, hex)),
+            ..Default::default()
+        })
+        .with(combatant)
+        .with(HexPosition(hex))
+        .with(StatusEffects::default())
+        .with(StateCleanup)
+        .current_entity()
+        .unwrap();
+
+    overlay::spawn_health_bar(commands, overlay_assets, entity, team);
+
+    entity
+}
+
+/// The player's side of the battle, which is the party unless a recording is replayed
+pub fn player_combatants(
+    party: &Party,
+    characters: &Characters,
+    items: &Items,
+    replay: Option<&Replay>,
+) -> Vec<Combatant> {
+    match replay {
+        Some(replay) => replay.players().to_vec(),
+        None => party
+            .members
+            .iter()
+            .map(|member| member.combatant(characters, items))
+            .collect(),
+    }
+}
+
+/// Where a combatant standing on `hex` is placed in the world
+pub fn unit_translation(board: &Board, hex: Hex) -> Vec3 {
+    hex.to_world() + Vec3::new(0.0, board.height(hex) + 0.5, 0.0)
+}
+
+fn spawn_camera(commands: &mut Commands, board: &Board) -> Entity {
     let mut transform = Transform::from_translation(Vec3::new(0., 15., -15.));
     transform.look_at(Vec3::ZERO, Vec3::Y);
 
@@ -79,7 +545,8 @@ fn spawn_camera(commands: &mut Commands) -> Entity {
         .spawn(())
         .with(Transform::default())
         .with(GlobalTransform::default())
-        .with(Camera)
+        .with(Camera::new(board))
+        .with(StateCleanup)
         .current_entity()
         .unwrap();
 
@@ -97,12 +564,6 @@ fn spawn_camera(commands: &mut Commands) -> Entity {
     root
 }
 
-fn show_area_title(mut hud: ResMut<HudAreaLabel>) {
-    hud.show_area_title("The battle of Bevytown");
-}
-
-fn back_to_overworld(mut state: ResMut<State<AppState>>, input: Res<Input<KeyCode>>) {
-    if input.just_pressed(KeyCode::Escape) {
-        state.set_next(AppState::Overworld).unwrap();
-    }
+fn show_area_title(mut hud: ResMut<HudAreaLabel>, battle: Res<BattleResource>) {
+    hud.show_area_title(battle.encounter.title.clone());
 }
@@ -0,0 +1,268 @@
+//! Deciding who won the battle, and the results screen shown afterwards
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+
+use crate::battle::combat::{Combatant, Team};
+use crate::battle::encounter::Encounter;
+use crate::battle::replay::Replay;
+use crate::battle::turn::TurnState;
+use crate::battle::{BattleResource, StateCleanup};
+use crate::inventory::{Inventory, Items};
+use crate::menu::{button, spawn_button};
+use crate::overworld::interactables::battle_starter::DefeatedBattleStarters;
+use crate::party::Party;
+use crate::quest::QuestEvent;
+use crate::shop::Wallet;
+use crate::AppState;
+use crate::UiAssets;
+
+/// How the battle ended, inserted as a resource once it is decided
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BattleOutcome {
+    Victory,
+    Defeat,
+    Fled,
+}
+
+impl BattleOutcome {
+    fn title(self) -> &'static str {
+        match self {
+            BattleOutcome::Victory => "VICTORY",
+            BattleOutcome::Defeat => "DEFEAT",
+            BattleOutcome::Fled => "ESCAPED",
+        }
+    }
+}
+
+/// Ends the battle once a team is wiped out, or when the player flees
+#[allow(clippy::too_many_arguments)]
+pub fn check_battle_outcome(
+    mut commands: Commands,
+    outcome: Option<Res<BattleOutcome>>,
+    assets: Res<UiAssets>,
+    battle: Res<BattleResource>,
+    party: Res<Party>,
+    items: Res<Items>,
+    inventory: Res<Inventory>,
+    mut turn: ResMut<TurnState>,
+    units: Query<&Combatant>,
+) {
+    if outcome.is_some() {
+        return;
+    }
+
+    let team_alive = |team| units.iter().any(|c| c.team == team && c.is_alive());
+
+    let outcome = if !team_alive(Team::Player) {
+        BattleOutcome::Defeat
+    } else if !team_alive(Team::Enemy) {
+        BattleOutcome::Victory
+    } else if turn.fled {
+        BattleOutcome::Fled
+    } else {
+        return;
+    };
+
+    // No more turns are played once the battle is over
+    turn.active = None;
+
+    spawn_results_screen(
+        &mut commands,
+        &assets,
+        outcome,
+        &party,
+        &items,
+        &inventory,
+        &battle.encounter,
+    );
+    commands.insert_resource(outcome);
+}
+
+/// Applies the results of the battle and returns to the overworld
+#[allow(clippy::too_many_arguments)]
+pub fn button_leave_battle(
+    In(clicked): In<bool>,
+    mut commands: Commands,
+    outcome: Option<Res<BattleOutcome>>,
+    battle: Res<BattleResource>,
+    mut party: ResMut<Party>,
+    items: Res<Items>,
+    mut inventory: ResMut<Inventory>,
+    mut wallet: ResMut<Wallet>,
+    mut defeated: ResMut<DefeatedBattleStarters>,
+    mut quest_events: EventWriter<QuestEvent>,
+    mut state: ResMut<State<AppState>>,
+    replay: Option<Res<Replay>>,
+    mut app_exit: EventWriter<AppExit>,
+) {
+    let outcome = match outcome {
+        Some(outcome) if clicked => *outcome,
+        _ => return,
+    };
+
+    // A replay only shows the battle, without affecting the game
+    if replay.is_some() {
+        app_exit.send(AppExit);
+        return;
+    }
+
+    if outcome == BattleOutcome::Victory {
+        let rewards = &battle.encounter.rewards;
+        party.gain_battle_experience(rewards.experience, battle.encounter.max_fighters());
+        wallet.add(rewards.gold);
+
+        for item in &rewards.loot {
+            if inventory.add(&items, item, 1) > 0 {
+                warn!("No room in the inventory for {}", item);
+            }
+        }
+
+        if let Some(starter_id) = &battle.starter_id {
+            defeated.0.insert(starter_id.clone());
+        }
+
+        quest_events.send(QuestEvent::BattleWon(battle.encounter.title.clone()));
+    }
+
+    commands.remove_resource::<BattleOutcome>();
+    state.set_next(AppState::Overworld).unwrap();
+}
+
+fn spawn_results_screen(
+    commands: &mut Commands,
+    assets: &UiAssets,
+    outcome: BattleOutcome,
+    party: &Party,
+    items: &Items,
+    inventory: &Inventory,
+    encounter: &Encounter,
+) {
+    let rewards = &encounter.rewards;
+    let text_style = TextStyle {
+        font: assets.font_light.clone(),
+        font_size: 18.0,
+        color: Color::rgb(0.9, 0.9, 0.95),
+    };
+
+    let mut lines = Vec::new();
+    if outcome == BattleOutcome::Victory {
+        lines.push(format!("Experience: {}", rewards.experience));
+        lines.push(format!("Gold: {}", rewards.gold));
+
+        for (name, level) in party.level_ups(rewards, encounter.max_fighters()) {
+            lines.push(format!("{} reached level {}", name, level));
+        }
+
+        // Loot is only added when leaving, so find out now what won't fit
+        let mut inventory = inventory.clone();
+        for item in &rewards.loot {
+            if inventory.add(items, item, 1) == 0 {
+                lines.push(format!("Found {}", item));
+            } else {
+                lines.push(format!("Found {}, but there is no room for it", item));
+            }
+        }
+    } else {
+        lines.push("No rewards".to_string());
+    }
+
+    commands
+        // Container
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: assets.transparent.clone(),
+            ..Default::default()
+        })
+        .with(StateCleanup)
+        .with_children(|root| {
+            root
+                // Results panel
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(300.0), Val::Auto),
+                        flex_direction: FlexDirection::ColumnReverse,
+                        justify_content: JustifyContent::FlexStart,
+                        align_items: AlignItems::Center,
+                        padding: Rect::all(Val::Px(8.0)),
+                        ..Default::default()
+                    },
+                    material: assets.menu_panel_background.clone(),
+                    ..Default::default()
+                })
+                .with_children(|panel| {
+                    // Title
+                    panel
+                        .spawn(TextBundle {
+                            text: Text::with_section(
+                                outcome.title(),
+                                TextStyle {
+                                    font: assets.font_bold.clone(),
+                                    font_size: 25.0,
+                                    color: Color::rgb(0.9, 0.9, 0.95),
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        // Spacer
+                        .spawn(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Auto, Val::Px(16.0)),
+                                ..Default::default()
+                            },
+                            material: assets.transparent.clone(),
+                            ..Default::default()
+                        });
+
+                    for line in &lines {
+                        panel.spawn(TextBundle {
+                            text: Text::with_section(
+                                line.as_str(),
+                                text_style.clone(),
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        });
+                    }
+
+                    // Spacer
+                    panel.spawn(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Auto, Val::Px(16.0)),
+                            ..Default::default()
+                        },
+                        material: assets.transparent.clone(),
+                        ..Default::default()
+                    });
+
+                    spawn_button(
+                        panel,
+                        assets,
+                        &Style {
+                            size: Size::new(Val::Auto, Val::Auto),
+                            margin: Rect::all(Val::Px(5.0)),
+                            padding: Rect {
+                                left: Val::Px(12.0),
+                                right: Val::Px(12.0),
+                                top: Val::Px(8.0),
+                                bottom: Val::Px(8.0),
+                            },
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        &text_style,
+                        "Continue",
+                        button::LeaveBattle,
+                    );
+                });
+        });
+}
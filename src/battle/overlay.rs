@@ -0,0 +1,232 @@
+//! Health bars and floating damage numbers drawn over the combatants
+//!
+//! The overlays are UI nodes, positioned each frame by projecting their anchor point in
+//! the world onto the screen through the battle camera.
+
+use bevy::{prelude::*, render::camera::Camera as RenderCamera};
+
+use crate::battle::board::Board;
+use crate::battle::camera;
+use crate::battle::combat::{Combatant, Team};
+use crate::battle::combat_log::CombatEvent;
+use crate::battle::StateCleanup;
+
+/// Width of a health bar in pixels
+const HEALTH_BAR_WIDTH: f32 = 40.0;
+
+/// Height of a health bar in pixels
+const HEALTH_BAR_HEIGHT: f32 = 5.0;
+
+/// How far above a combatant's origin the overlays are anchored
+const OVERLAY_HEIGHT: f32 = 0.8;
+
+/// How quickly a health bar catches up with the actual health
+const HEALTH_BAR_TWEEN_RATE: f32 = 6.0;
+
+/// How long a floating number is visible, in seconds
+const FLOATING_NUMBER_LIFETIME: f32 = 1.2;
+
+/// How far a floating number rises during its lifetime, in pixels
+const FLOATING_NUMBER_RISE: f32 = 40.0;
+
+/// How large the floating numbers should be
+const FLOATING_NUMBER_FONT_SIZE: f32 = 20.0;
+
+/// Loads the assets required for the battle overlays
+pub struct OverlayAssets {
+    health_bar_background: Handle<ColorMaterial>,
+    health_bar_player: Handle<ColorMaterial>,
+    health_bar_enemy: Handle<ColorMaterial>,
+    font_bold: Handle<Font>,
+}
+
+impl FromWorld for OverlayAssets {
+    fn from_world(world: &mut World) -> Self {
+        let world = world.cell();
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        let assets = world.get_resource_mut::<AssetServer>().unwrap();
+
+        OverlayAssets {
+            health_bar_background: materials.add(Color::rgba(0.0, 0.0, 0.0, 0.7).into()),
+            health_bar_player: materials.add(Color::rgb(0.3, 0.8, 0.4).into()),
+            health_bar_enemy: materials.add(Color::rgb(0.9, 0.25, 0.2).into()),
+
+            font_bold: assets.load("fonts/sansation/Sansation-Bold.ttf"),
+        }
+    }
+}
+
+/// A health bar following a combatant
+pub struct HealthBar {
+    unit: Entity,
+    /// The fraction of health currently shown, which trails the actual health
+    shown: f32,
+}
+
+/// The coloured part of a health bar
+pub struct HealthBarFill;
+
+/// A damage or heal number rising from where it happened
+pub struct FloatingNumber {
+    anchor: Vec3,
+    age: f32,
+    color: Color,
+}
+
+/// Spawns a health bar for the given combatant
+pub fn spawn_health_bar(commands: &mut Commands, assets: &OverlayAssets, unit: Entity, team: Team) {
+    let fill = match team {
+        Team::Player => assets.health_bar_player.clone(),
+        Team::Enemy => assets.health_bar_enemy.clone(),
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(HEALTH_BAR_WIDTH), Val::Px(HEALTH_BAR_HEIGHT)),
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            material: assets.health_bar_background.clone(),
+            ..Default::default()
+        })
+        .with(HealthBar { unit, shown: 1.0 })
+        .with(StateCleanup)
+        .with_children(|bar| {
+            bar.spawn(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    ..Default::default()
+                },
+                material: fill,
+                ..Default::default()
+            })
+            .with(HealthBarFill);
+        });
+}
+
+/// Moves the health bars along with their combatants, and animates health changes
+pub fn update_health_bars(
+    mut commands: Commands,
+    time: Res<Time>,
+    windows: Res<Windows>,
+    cameras: Query<(&RenderCamera, &GlobalTransform), With<StateCleanup>>,
+    units: Query<(&Combatant, &GlobalTransform)>,
+    mut bars: Query<(Entity, &mut HealthBar, &mut Style, &Children), Without<HealthBarFill>>,
+    mut fills: Query<&mut Style, With<HealthBarFill>>,
+) {
+    let (camera, camera_transform) = match cameras.iter().next() {
+        Some(camera) => camera,
+        None => return,
+    };
+
+    for (entity, mut bar, mut style, children) in bars.iter_mut() {
+        let (combatant, transform) = match units.get(bar.unit) {
+            Ok(unit) => unit,
+            Err(_) => {
+                // The combatant was defeated
+                commands.despawn_recursive(entity);
+                continue;
+            }
+        };
+
+        let anchor = transform.translation + Vec3::new(0.0, OVERLAY_HEIGHT, 0.0);
+        if let Some(screen) = camera::world_to_screen(&windows, camera, camera_transform, anchor) {
+            style.position.left = Val::Px(screen.x - HEALTH_BAR_WIDTH / 2.0);
+            style.position.bottom = Val::Px(screen.y);
+        }
+
+        let health = combatant.health as f32 / combatant.stats.max_health as f32;
+        bar.shown = tween(bar.shown, health, time.delta_seconds());
+
+        for child in children.iter() {
+            if let Ok(mut fill) = fills.get_mut(*child) {
+                fill.size.width = Val::Percent(bar.shown * 100.0);
+            }
+        }
+    }
+}
+
+/// Spawns a floating number for every damage and heal in the battle
+pub fn spawn_floating_numbers(
+    mut commands: Commands,
+    assets: Res<OverlayAssets>,
+    board: Res<Board>,
+    mut combat_events: EventReader<CombatEvent>,
+) {
+    for event in combat_events.iter() {
+        let (text, hex, color) = match event {
+            CombatEvent::Damaged { amount, hex, .. } => {
+                (format!("-{}", amount), *hex, Color::rgb(1.0, 0.35, 0.3))
+            }
+            CombatEvent::Healed { amount, hex, .. } => {
+                (format!("+{}", amount), *hex, Color::rgb(0.4, 1.0, 0.5))
+            }
+            _ => continue,
+        };
+
+        commands
+            .spawn(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    text,
+                    TextStyle {
+                        font: assets.font_bold.clone(),
+                        font_size: FLOATING_NUMBER_FONT_SIZE,
+                        color,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            })
+            .with(FloatingNumber {
+                anchor: super::unit_translation(&board, hex) + Vec3::new(0.0, OVERLAY_HEIGHT, 0.0),
+                age: 0.0,
+                color,
+            })
+            .with(StateCleanup);
+    }
+}
+
+/// Makes the floating numbers rise and fade out
+pub fn update_floating_numbers(
+    mut commands: Commands,
+    time: Res<Time>,
+    windows: Res<Windows>,
+    cameras: Query<(&RenderCamera, &GlobalTransform), With<StateCleanup>>,
+    mut numbers: Query<(Entity, &mut FloatingNumber, &mut Style, &mut Text)>,
+) {
+    let (camera, camera_transform) = match cameras.iter().next() {
+        Some(camera) => camera,
+        None => return,
+    };
+
+    for (entity, mut number, mut style, mut text) in numbers.iter_mut() {
+        number.age += time.delta_seconds();
+        if number.age >= FLOATING_NUMBER_LIFETIME {
+            commands.despawn_recursive(entity);
+            continue;
+        }
+
+        let progress = number.age / FLOATING_NUMBER_LIFETIME;
+
+        if let Some(screen) =
+            camera::world_to_screen(&windows, camera, camera_transform, number.anchor)
+        {
+            style.position.left = Val::Px(screen.x - FLOATING_NUMBER_FONT_SIZE / 2.0);
+            style.position.bottom = Val::Px(screen.y + FLOATING_NUMBER_RISE * progress);
+        }
+
+        let mut color = number.color;
+        color.set_a(1.0 - progress);
+        text.sections[0].style.color = color;
+    }
+}
+
+/// Moves `current` towards `target`, covering most of the distance within a few frames
+fn tween(current: f32, target: f32, delta_seconds: f32) -> f32 {
+    current + (target - current) * (1.0 - (-HEALTH_BAR_TWEEN_RATE * delta_seconds).exp())
+}
@@ -0,0 +1,278 @@
+//! Turns mouse and keyboard input into commands for the player's combatants
+//!
+//! Clicking an opponent uses the selected ability on it, clicking any other hex moves
+//! there. The number keys select which ability to use, and hovering an opponent it can
+//! reach frames the hexes it would affect. The use item key selects the next item that
+//! can be used in battle and frames the friendly combatants within reach that it would
+//! help, the active one included. The next click uses the item on the clicked one.
+//! Escape flees from the battle.
+
+use std::collections::HashSet;
+
+use bevy::{prelude::*, render::camera::Camera as RenderCamera};
+
+use crate::battle::board::{Board, Cover};
+use crate::battle::camera::{self, FrameHexes};
+use crate::battle::combat::{Combatant, Team};
+use crate::battle::command::{BattleCommand, IssuedCommand, ITEM_RANGE};
+use crate::battle::hex::{Hex, HexPosition};
+use crate::battle::replay::Replay;
+use crate::battle::status_effects::StatusEffects;
+use crate::battle::turn::{TurnStart, TurnState};
+use crate::battle::StateCleanup;
+use crate::inventory::{Inventory, Items};
+use crate::user_config::KeyBinds;
+
+/// Keys that select the ability with the same index
+const ABILITY_KEYS: [KeyCode; 4] = [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4];
+
+#[allow(clippy::too_many_arguments)]
+pub fn player_commands(
+    input: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    keybinds: Res<KeyBinds>,
+    windows: Res<Windows>,
+    turn: Res<TurnState>,
+    board: Res<Board>,
+    items: Res<Items>,
+    inventory: Res<Inventory>,
+    replay: Option<Res<Replay>>,
+    mut turn_starts: EventReader<TurnStart>,
+    mut selected_ability: Local<usize>,
+    mut selected_item: Local<Option<usize>>,
+    mut previewed_hex: Local<Option<Hex>>,
+    cameras: Query<(&RenderCamera, &GlobalTransform), With<StateCleanup>>,
+    units: Query<(&Combatant, &HexPosition, &StatusEffects)>,
+    mut battle_commands: EventWriter<IssuedCommand>,
+    mut frames: EventWriter<FrameHexes>,
+) {
+    // Replays issue the recorded commands instead
+    if replay.is_some() {
+        return;
+    }
+
+    // Every combatant starts its turn with its first ability selected
+    if turn_starts.iter().count() > 0 {
+        *selected_ability = 0;
+        *selected_item = None;
+        *previewed_hex = None;
+    }
+
+    let active = match turn.active_entity() {
+        Some(entity) => entity,
+        None => return,
+    };
+
+    let (actor, position) = match units.get(active) {
+        Ok((combatant, position, _)) => (combatant, position.0),
+        Err(_) => return,
+    };
+    let team = actor.team;
+
+    if team != Team::Player {
+        return;
+    }
+
+    let issue = |command| IssuedCommand {
+        unit: active,
+        command,
+    };
+
+    for (index, key) in ABILITY_KEYS.iter().enumerate() {
+        if input.just_pressed(*key) {
+            *selected_ability = index;
+            *selected_item = None;
+            // Preview the new ability on the hovered hex
+            *previewed_hex = None;
+        }
+    }
+
+    let hovered = cameras
+        .iter()
+        .next()
+        .and_then(|(camera, transform)| camera::cursor_to_board(&windows, camera, transform))
+        .map(Hex::from_world);
+
+    if selected_item.is_none() && hovered != *previewed_hex {
+        *previewed_hex = hovered;
+
+        let affected = hovered
+            .zip(actor.abilities.get(*selected_ability))
+            .map_or_else(Vec::new, |(target, ability)| {
+                affected_hexes(&board, position, target, ability.range, team, &units)
+            });
+        if !affected.is_empty() {
+            frames.send(FrameHexes(
+                std::iter::once(position).chain(affected).collect(),
+            ));
+        }
+    }
+
+    if input.just_pressed(KeyCode::Escape) {
+        battle_commands.send(issue(BattleCommand::Flee));
+        return;
+    }
+
+    if input.just_pressed(keybinds.end_turn) {
+        *selected_item = None;
+        battle_commands.send(issue(BattleCommand::EndTurn));
+        return;
+    }
+
+    if input.just_pressed(keybinds.undo_move) {
+        battle_commands.send(issue(BattleCommand::UndoMove));
+        return;
+    }
+
+    if input.just_pressed(keybinds.use_item) {
+        *selected_item = next_battle_item(&items, &inventory, *selected_item);
+        let slot = match *selected_item {
+            Some(slot) => slot,
+            None => return,
+        };
+        info!("Selected {}", inventory.stacks()[slot].item);
+
+        let mut hexes: Vec<Hex> = units
+            .iter()
+            .filter(|(combatant, target, effects)| {
+                combatant.team == team
+                    && position.distance(target.0) <= ITEM_RANGE
+                    && item_helps(&items, &inventory, slot, combatant, effects)
+            })
+            .map(|(_, target, _)| target.0)
+            .collect();
+
+        // Keep the active combatant in view even if no item would help it
+        if !hexes.contains(&position) {
+            hexes.push(position);
+        }
+
+        frames.send(FrameHexes(hexes));
+        return;
+    }
+
+    if !mouse.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    if let Some(slot) = selected_item.take() {
+        let target = hovered.and_then(|hex| {
+            units.iter().find(|(combatant, target, _)| {
+                target.0 == hex && combatant.team == team && position.distance(hex) <= ITEM_RANGE
+            })
+        });
+        if let Some((combatant, target, effects)) = target {
+            if item_helps(&items, &inventory, slot, combatant, effects) {
+                battle_commands.send(issue(BattleCommand::UseItem {
+                    slot,
+                    target: target.0,
+                }));
+            }
+        }
+        return;
+    }
+
+    if let Some(hex) = hovered {
+        let opponent_clicked = units
+            .iter()
+            .any(|(combatant, position, _)| position.0 == hex && combatant.team != team);
+
+        if opponent_clicked {
+            battle_commands.send(issue(BattleCommand::UseAbility {
+                ability: *selected_ability,
+                target: hex,
+            }));
+        } else {
+            battle_commands.send(issue(BattleCommand::Move(hex)));
+        }
+    }
+}
+
+/// The hexes an ability with the given range would hit when used on `target`
+///
+/// Abilities hit the opponent standing on their target, so this is empty if there is no
+/// opponent there, or if the ability can't reach it.
+fn affected_hexes(
+    board: &Board,
+    position: Hex,
+    target: Hex,
+    range: i32,
+    team: Team,
+    units: &Query<(&Combatant, &HexPosition, &StatusEffects)>,
+) -> Vec<Hex> {
+    let opponent = units
+        .iter()
+        .any(|(combatant, p, _)| p.0 == target && combatant.team != team);
+    if !opponent || position.distance(target) > board.attack_range(position, target, range) {
+        return Vec::new();
+    }
+
+    let occupied: HashSet<Hex> = units.iter().map(|(_, p, _)| p.0).collect();
+    if board.cover(position, target, &occupied) == Cover::Full {
+        return Vec::new();
+    }
+
+    vec![target]
+}
+
+/// The next inventory slot after `current` holding an item that can be used in battle,
+/// starting over from the first slot after the last one
+fn next_battle_item(items: &Items, inventory: &Inventory, current: Option<usize>) -> Option<usize> {
+    let stacks = inventory.stacks();
+    let start = current.map_or(0, |slot| slot + 1);
+
+    (0..stacks.len())
+        .map(|offset| (start + offset) % stacks.len())
+        .find(|slot| {
+            items
+                .get(&stacks[*slot].item)
+                .effect
+                .map_or(false, |effect| effect.in_battle())
+        })
+}
+
+/// Whether the item in the inventory slot would help the combatant
+fn item_helps(
+    items: &Items,
+    inventory: &Inventory,
+    slot: usize,
+    combatant: &Combatant,
+    effects: &StatusEffects,
+) -> bool {
+    inventory.stacks().get(slot).map_or(false, |stack| {
+        items
+            .get(&stack.item)
+            .effect
+            .map_or(false, |effect| effect.can_use_on(combatant, effects))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn item_selection_cycles_through_battle_items() {
+        let items = Items::default();
+        let mut inventory = Inventory::default();
+        inventory.add(&items, "Tome of Insight", 1);
+        inventory.add(&items, "Antidote", 1);
+
+        let slot = |item: &str| {
+            inventory
+                .stacks()
+                .iter()
+                .position(|stack| stack.item == item)
+        };
+        let herb = slot("Healing Herb");
+        let antidote = slot("Antidote");
+
+        assert_eq!(next_battle_item(&items, &inventory, None), herb);
+        assert_eq!(next_battle_item(&items, &inventory, herb), antidote);
+        assert_eq!(next_battle_item(&items, &inventory, antidote), herb);
+
+        inventory.remove("Healing Herb", 2);
+        inventory.remove("Antidote", 1);
+        assert_eq!(next_battle_item(&items, &inventory, None), None);
+    }
+}
@@ -0,0 +1,395 @@
+//! Recording battles, and playing them back
+//!
+//! Every battle records the RNG it started with, its encounter, the player's combatants
+//! and inventory, and every command issued to a combatant. The recording of the last
+//! battle is written to `last_battle.ron` in the user data directory.
+//!
+//! Running the game with `--replay <path>` plays a recording back instead of starting
+//! normally. The replay panics as soon as the battle stops matching the recording.
+//!
+//! ## Replay controls
+//! - `P` pauses and resumes
+//! - `N` executes the next command while paused
+//! - `+` and `-` change the playback speed
+
+use std::env;
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::battle::combat::Combatant;
+use crate::battle::command::{BattleCommand, IssuedCommand};
+use crate::battle::encounter::Encounter;
+use crate::battle::hex::Hex;
+use crate::battle::outcome::BattleOutcome;
+use crate::battle::turn::TurnState;
+use crate::battle::BattleResource;
+use crate::inventory::{Inventory, Items};
+use crate::party::{Characters, Party};
+use crate::rng::GameRng;
+use crate::AppState;
+
+const RECORDING_FILE_NAME: &str = "last_battle.ron";
+
+/// Time between two replayed commands at normal speed, in seconds
+const REPLAY_STEP_TIME: f32 = 0.6;
+
+const MIN_REPLAY_SPEED: f32 = 0.25;
+const MAX_REPLAY_SPEED: f32 = 8.0;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Everything needed to play a battle again
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BattleRecording {
+    /// The RNG as it was when the battle started
+    pub rng: GameRng,
+    pub encounter: Encounter,
+    /// The player's combatants as they entered the battle
+    pub players: Vec<Combatant>,
+    /// The inventory as it was when the battle started
+    #[serde(default)]
+    pub inventory: Inventory,
+    pub commands: Vec<RecordedCommand>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedCommand {
+    /// Name of the combatant whose turn it was
+    pub unit: String,
+    pub command: BattleCommand,
+    /// Checksum of all combatants right before the command was executed
+    pub checksum: u64,
+}
+
+impl BattleRecording {
+    pub fn new(
+        rng: GameRng,
+        encounter: Encounter,
+        players: Vec<Combatant>,
+        inventory: Inventory,
+    ) -> Self {
+        BattleRecording {
+            rng,
+            encounter,
+            players,
+            inventory,
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Self {
+        let file = OpenOptions::new()
+            .read(true)
+            .open(path)
+            .unwrap_or_else(|e| panic!("Could not open replay {}: {}", path.display(), e));
+
+        ron::de::from_reader(BufReader::new(file))
+            .unwrap_or_else(|e| panic!("Could not read replay {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        ron::ser::to_writer_pretty(BufWriter::new(file), self, Default::default())?;
+        Ok(())
+    }
+}
+
+/// Checksum of the name, health and position of every combatant
+///
+/// The order of the combatants does not matter. The checksum is saved in recordings, so
+/// it is computed with FNV-1a, which gives the same value on every build, unlike the
+/// hashers of the standard library.
+pub fn checksum(units: impl Iterator<Item = (String, i32, Hex)>) -> u64 {
+    let mut units: Vec<_> = units
+        .map(|(name, health, hex)| (name, health, hex.q, hex.r))
+        .collect();
+    units.sort_unstable();
+
+    let mut hash = FNV_OFFSET_BASIS;
+    let mut write = |bytes: &[u8]| {
+        for byte in bytes {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    };
+
+    for (name, health, q, r) in &units {
+        // The length keeps names from running into the numbers that follow them
+        write(&(name.len() as u64).to_le_bytes());
+        write(name.as_bytes());
+        write(&health.to_le_bytes());
+        write(&q.to_le_bytes());
+        write(&r.to_le_bytes());
+    }
+
+    hash
+}
+
+/// A recording being played back
+pub struct Replay {
+    recording: BattleRecording,
+    /// How many of the recorded commands have been sent
+    sent: usize,
+    /// How many of the executed commands have been checked against the recording
+    verified: usize,
+    paused: bool,
+    speed: f32,
+    cooldown: f32,
+}
+
+impl Replay {
+    pub fn new(recording: BattleRecording) -> Self {
+        Replay {
+            recording,
+            sent: 0,
+            verified: 0,
+            paused: false,
+            speed: 1.0,
+            cooldown: REPLAY_STEP_TIME,
+        }
+    }
+
+    /// The player's combatants of the recorded battle
+    pub fn players(&self) -> &[Combatant] {
+        &self.recording.players
+    }
+}
+
+/// Sets up replay mode if the game was started with `--replay <path>`
+pub fn setup_replay(app: &mut AppBuilder) {
+    let path = match crate::argument_value(env::args(), "--replay") {
+        Some(path) => PathBuf::from(path),
+        None => return,
+    };
+
+    let recording = BattleRecording::load(&path);
+    info!("Replaying battle from {}", path.display());
+
+    // Replaces the RNG and inventory loaded from the save game
+    app.insert_resource(recording.rng.clone())
+        .insert_resource(recording.inventory.clone())
+        .insert_resource(BattleResource {
+            encounter: recording.encounter.clone(),
+            starter_id: None,
+        })
+        .insert_resource(Replay::new(recording))
+        .add_startup_system(start_replay.system());
+}
+
+fn start_replay(mut state: ResMut<State<AppState>>) {
+    state.set_next(AppState::Battle).unwrap();
+}
+
+/// Starts recording the battle that is being set up
+#[allow(clippy::too_many_arguments)]
+pub fn start_recording(
+    mut commands: Commands,
+    rng: Res<GameRng>,
+    battle: Res<BattleResource>,
+    party: Res<Party>,
+    characters: Res<Characters>,
+    items: Res<Items>,
+    inventory: Res<Inventory>,
+    replay: Option<Res<Replay>>,
+) {
+    commands.insert_resource(BattleRecording::new(
+        rng.clone(),
+        battle.encounter.clone(),
+        super::player_combatants(&party, &characters, &items, replay.as_deref()),
+        inventory.clone(),
+    ));
+}
+
+/// Writes the recording of the battle to the user data directory
+pub fn save_recording(recording: Res<BattleRecording>, replay: Option<Res<Replay>>) {
+    if replay.is_some() {
+        return;
+    }
+
+    let path = crate::save::get_data_file_path(RECORDING_FILE_NAME);
+    match recording.save(&path) {
+        Ok(()) => info!("Saved battle recording to {}", path.display()),
+        Err(e) => error!(
+            "Could not save battle recording to {}: {}",
+            path.display(),
+            e
+        ),
+    }
+}
+
+/// Issues the recorded commands, with pause, step and speed controls
+///
+/// Panics if the recording runs out of commands before the battle has ended, if a command
+/// was recorded for another combatant than the one whose turn it is, or if a command is
+/// rejected when played back.
+#[allow(clippy::too_many_arguments)]
+pub fn play_replay(
+    time: Res<Time>,
+    input: Res<Input<KeyCode>>,
+    replay: Option<ResMut<Replay>>,
+    recording: Res<BattleRecording>,
+    turn: Res<TurnState>,
+    outcome: Option<Res<BattleOutcome>>,
+    units: Query<&Combatant>,
+    mut battle_commands: EventWriter<IssuedCommand>,
+) {
+    let mut replay = match replay {
+        Some(replay) => replay,
+        None => return,
+    };
+
+    // Only commands that take effect are recorded, and they are executed in the frame they
+    // are sent
+    if recording.commands.len() < replay.sent {
+        panic!(
+            "Replay diverged at command {}: the command was not executed",
+            recording.commands.len()
+        );
+    }
+
+    if input.just_pressed(KeyCode::P) {
+        replay.paused = !replay.paused;
+    }
+    if input.just_pressed(KeyCode::Equals) {
+        replay.speed = (replay.speed * 2.0).min(MAX_REPLAY_SPEED);
+    }
+    if input.just_pressed(KeyCode::Minus) {
+        replay.speed = (replay.speed / 2.0).max(MIN_REPLAY_SPEED);
+    }
+
+    replay.cooldown -= time.delta_seconds() * replay.speed;
+
+    let step = if replay.paused {
+        input.just_pressed(KeyCode::N)
+    } else {
+        replay.cooldown <= 0.0
+    };
+
+    // Wait for the previous command to be executed, and for a turn to be active
+    let active = match turn.active_entity() {
+        Some(active) if step && recording.commands.len() == replay.sent => active,
+        _ => return,
+    };
+
+    match replay.recording.commands.get(replay.sent) {
+        Some(recorded) => {
+            let name = units
+                .get(active)
+                .map_or("nobody", |unit| unit.name.as_str());
+            if name != recorded.unit {
+                panic!(
+                    "Replay diverged at command {}: expected {} to act, but it is {}'s turn",
+                    replay.sent, recorded.unit, name
+                );
+            }
+
+            battle_commands.send(IssuedCommand {
+                unit: active,
+                command: recorded.command,
+            });
+            replay.sent += 1;
+            replay.cooldown = REPLAY_STEP_TIME;
+        }
+        None if outcome.is_none() => panic!(
+            "Replay ran out of commands after {} commands, but the battle has not ended",
+            replay.sent
+        ),
+        None => {}
+    }
+}
+
+/// Panics as soon as the battle differs from the recording being played back
+pub fn verify_replay(recording: Res<BattleRecording>, replay: Option<ResMut<Replay>>) {
+    let mut replay = match replay {
+        Some(replay) => replay,
+        None => return,
+    };
+
+    for (index, command) in recording.commands.iter().enumerate().skip(replay.verified) {
+        match replay.recording.commands.get(index) {
+            Some(expected) if expected == command => {}
+            expected => panic!(
+                "Replay diverged at command {}: expected {:?}, got {:?}",
+                index, expected, command
+            ),
+        }
+    }
+    replay.verified = recording.commands.len();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksum_ignores_order() {
+        let units = vec![
+            ("Hero".to_string(), 30, Hex::new(0, -4)),
+            ("Goblin".to_string(), 18, Hex::new(-1, 4)),
+        ];
+
+        assert_eq!(
+            checksum(units.clone().into_iter()),
+            checksum(units.iter().rev().cloned())
+        );
+        assert_ne!(
+            checksum(units.clone().into_iter()),
+            checksum(units.into_iter().take(1))
+        );
+    }
+
+    #[test]
+    fn checksum_is_stable() {
+        // Recordings store checksums, so changing these values breaks old recordings
+        assert_eq!(checksum(std::iter::empty()), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(
+            checksum(
+                vec![
+                    ("Hero".to_string(), 30, Hex::new(0, -4)),
+                    ("Goblin".to_string(), 18, Hex::new(-1, 4)),
+                ]
+                .into_iter()
+            ),
+            0x36b7_e3cc_ffdf_dfd3
+        );
+    }
+
+    #[test]
+    fn recordings_survive_serialization() {
+        let players =
+            vec![Party::default().members[0].combatant(&Characters::default(), &Items::default())];
+        let mut recording = BattleRecording::new(
+            GameRng::new(5),
+            Encounter::load("bevytown_goblins"),
+            players,
+            Inventory::default(),
+        );
+        recording.commands.push(RecordedCommand {
+            unit: "Hero".to_string(),
+            command: BattleCommand::UseAbility {
+                ability: 1,
+                target: Hex::new(2, 4),
+            },
+            checksum: 1234,
+        });
+
+        let serialized = ron::ser::to_string(&recording).unwrap();
+        let restored: BattleRecording = ron::de::from_str(&serialized).unwrap();
+
+        assert_eq!(restored.commands, recording.commands);
+        assert_eq!(restored.players[0].name, "Hero");
+        assert_eq!(restored.rng.seed(), 5);
+        assert_eq!(restored.inventory, recording.inventory);
+    }
+}
@@ -0,0 +1,383 @@
+//! Buffs and debuffs that stay on a combatant for a number of its turns
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::battle::combat::Combatant;
+use crate::battle::combat_log::{CombatEvent, LogUnit};
+use crate::battle::hex::HexPosition;
+use crate::battle::turn::{TurnEnd, TurnStart, TurnState};
+
+/// How many times a stacking effect can be applied on top of itself
+const MAX_STACKS: u32 = 5;
+
+/// Damage dealt by each stack of poison at the start of the poisoned unit's turn
+const POISON_DAMAGE_PER_STACK: i32 = 2;
+
+/// Defence granted by a shield
+const SHIELD_DEFENCE: i32 = 3;
+
+/// Extra hexes of movement granted by haste
+const HASTE_MOVEMENT: i32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StatusEffectKind {
+    Poison,
+    Stun,
+    Shield,
+    Haste,
+}
+
+/// What happens when an effect is applied to a unit that already has it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackPolicy {
+    /// Keep a single stack, but extend the duration if the new one is longer
+    Refresh,
+    /// Add a stack (up to [`MAX_STACKS`]) and extend the duration like [`StackPolicy::Refresh`]
+    Stack,
+    /// Leave the active effect untouched
+    Ignore,
+}
+
+impl StatusEffectKind {
+    pub fn stack_policy(self) -> StackPolicy {
+        match self {
+            StatusEffectKind::Poison => StackPolicy::Stack,
+            StatusEffectKind::Stun => StackPolicy::Ignore,
+            StatusEffectKind::Shield | StatusEffectKind::Haste => StackPolicy::Refresh,
+        }
+    }
+
+    fn modifiers(self, stacks: u32) -> StatModifiers {
+        match self {
+            StatusEffectKind::Poison => StatModifiers::default(),
+            StatusEffectKind::Stun => StatModifiers {
+                stunned: true,
+                ..Default::default()
+            },
+            StatusEffectKind::Shield => StatModifiers {
+                defence: SHIELD_DEFENCE * stacks as i32,
+                ..Default::default()
+            },
+            StatusEffectKind::Haste => StatModifiers {
+                movement: HASTE_MOVEMENT * stacks as i32,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// A single active effect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatusEffect {
+    pub kind: StatusEffectKind,
+    /// Remaining turns of the affected unit, including the current one
+    pub turns_left: u32,
+    pub stacks: u32,
+}
+
+/// Adjustments to a combatant's base stats, read by the combat formulas
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatModifiers {
+    pub attack: i32,
+    pub defence: i32,
+    pub movement: i32,
+    /// Stunned units can neither move nor act
+    pub stunned: bool,
+}
+
+impl StatModifiers {
+    pub fn combine(self, other: StatModifiers) -> Self {
+        StatModifiers {
+            attack: self.attack + other.attack,
+            defence: self.defence + other.defence,
+            movement: self.movement + other.movement,
+            stunned: self.stunned || other.stunned,
+        }
+    }
+}
+
+/// All effects currently active on a combatant
+#[derive(Debug, Clone, Default)]
+pub struct StatusEffects {
+    effects: Vec<StatusEffect>,
+}
+
+impl StatusEffects {
+    /// Applies an effect for the given number of turns, following the effect's [`StackPolicy`]
+    ///
+    /// Returns whether the active effects changed.
+    pub fn apply(&mut self, kind: StatusEffectKind, turns: u32) -> bool {
+        if turns == 0 {
+            return false;
+        }
+
+        let existing = match self.effects.iter_mut().find(|e| e.kind == kind) {
+            Some(existing) => existing,
+            None => {
+                self.effects.push(StatusEffect {
+                    kind,
+                    turns_left: turns,
+                    stacks: 1,
+                });
+                return true;
+            }
+        };
+
+        let before = *existing;
+        match kind.stack_policy() {
+            StackPolicy::Refresh => {
+                existing.turns_left = existing.turns_left.max(turns);
+            }
+            StackPolicy::Stack => {
+                existing.stacks = (existing.stacks + 1).min(MAX_STACKS);
+                existing.turns_left = existing.turns_left.max(turns);
+            }
+            StackPolicy::Ignore => {}
+        }
+
+        *existing != before
+    }
+
+    /// Removes an effect, returning whether it was active
+    pub fn remove(&mut self, kind: StatusEffectKind) -> bool {
+        let before = self.effects.len();
+        self.effects.retain(|e| e.kind != kind);
+
+        self.effects.len() != before
+    }
+
+    pub fn get(&self, kind: StatusEffectKind) -> Option<&StatusEffect> {
+        self.effects.iter().find(|e| e.kind == kind)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &StatusEffect> {
+        self.effects.iter()
+    }
+
+    /// Combined stat modifiers of all active effects
+    pub fn modifiers(&self) -> StatModifiers {
+        self.effects
+            .iter()
+            .map(|e| e.kind.modifiers(e.stacks))
+            .fold(StatModifiers::default(), StatModifiers::combine)
+    }
+
+    /// Damage the unit takes when its turn starts
+    pub fn turn_start_damage(&self) -> i32 {
+        self.get(StatusEffectKind::Poison)
+            .map_or(0, |poison| POISON_DAMAGE_PER_STACK * poison.stacks as i32)
+    }
+
+    /// Counts down the duration of every effect, removing the ones that ran out
+    ///
+    /// Returns the kinds of the effects that expired.
+    pub fn tick(&mut self) -> Vec<StatusEffectKind> {
+        let mut expired = Vec::new();
+
+        for effect in &mut self.effects {
+            effect.turns_left -= 1;
+
+            if effect.turns_left == 0 {
+                expired.push(effect.kind);
+            }
+        }
+
+        self.effects.retain(|effect| effect.turns_left > 0);
+
+        expired
+    }
+}
+
+/// Applies start-of-turn effects, such as poison damage
+///
+/// Units defeated by these effects are removed from the board and lose their turn. This
+/// runs before any commands are issued for the turn, see `BattleSystem`.
+pub fn status_effects_turn_start(
+    mut commands: Commands,
+    mut turn: ResMut<TurnState>,
+    mut turn_starts: EventReader<TurnStart>,
+    mut turn_ends: EventWriter<TurnEnd>,
+    mut combat_events: EventWriter<CombatEvent>,
+    mut query: Query<(&mut Combatant, &HexPosition, &StatusEffects)>,
+) {
+    for TurnStart(entity) in turn_starts.iter() {
+        if let Ok((mut combatant, position, effects)) = query.get_mut(*entity) {
+            let damage = effects.turn_start_damage();
+            if damage == 0 {
+                continue;
+            }
+
+            combatant.take_damage(damage);
+
+            let unit = LogUnit::new(&combatant);
+            combat_events.send(CombatEvent::Damaged {
+                unit: unit.clone(),
+                hex: position.0,
+                amount: damage,
+            });
+
+            if !combatant.is_alive() {
+                combat_events.send(CombatEvent::Defeated { unit });
+                commands.despawn_recursive(*entity);
+
+                // Stunned units have no active turn to end
+                if turn.active_entity() == Some(*entity) {
+                    turn.end_turn(&mut turn_ends);
+                }
+            }
+        }
+    }
+}
+
+/// Counts down effect durations at the end of each turn
+pub fn status_effects_turn_end(
+    mut turn_ends: EventReader<TurnEnd>,
+    mut combat_events: EventWriter<CombatEvent>,
+    mut query: Query<(&Combatant, &mut StatusEffects)>,
+) {
+    for TurnEnd(entity) in turn_ends.iter() {
+        if let Ok((combatant, mut effects)) = query.get_mut(*entity) {
+            for effect in effects.tick() {
+                combat_events.send(CombatEvent::StatusExpired {
+                    unit: LogUnit::new(combatant),
+                    effect,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battle::combat::{Stats, Team};
+    use crate::battle::hex::Hex;
+
+    #[test]
+    fn effects_expire_after_their_duration() {
+        let mut effects = StatusEffects::default();
+        effects.apply(StatusEffectKind::Haste, 2);
+        effects.apply(StatusEffectKind::Stun, 1);
+
+        assert_eq!(effects.tick(), vec![StatusEffectKind::Stun]);
+        assert_eq!(effects.get(StatusEffectKind::Haste).unwrap().turns_left, 1);
+        assert!(effects.get(StatusEffectKind::Stun).is_none());
+
+        assert_eq!(effects.tick(), vec![StatusEffectKind::Haste]);
+        assert_eq!(effects.iter().count(), 0);
+        assert!(effects.tick().is_empty());
+    }
+
+    #[test]
+    fn zero_turn_effects_are_not_applied() {
+        let mut effects = StatusEffects::default();
+
+        assert!(!effects.apply(StatusEffectKind::Poison, 0));
+        assert!(effects.get(StatusEffectKind::Poison).is_none());
+    }
+
+    #[test]
+    fn refresh_extends_duration_without_stacking() {
+        let mut effects = StatusEffects::default();
+        effects.apply(StatusEffectKind::Shield, 2);
+
+        assert!(effects.apply(StatusEffectKind::Shield, 3));
+        assert_eq!(
+            effects.get(StatusEffectKind::Shield),
+            Some(&StatusEffect {
+                kind: StatusEffectKind::Shield,
+                turns_left: 3,
+                stacks: 1,
+            })
+        );
+
+        // A shorter duration never cuts an active effect short
+        assert!(!effects.apply(StatusEffectKind::Shield, 1));
+        assert_eq!(effects.get(StatusEffectKind::Shield).unwrap().turns_left, 3);
+    }
+
+    #[test]
+    fn stack_adds_stacks_up_to_the_limit() {
+        let mut effects = StatusEffects::default();
+
+        for _ in 0..MAX_STACKS + 2 {
+            effects.apply(StatusEffectKind::Poison, 2);
+        }
+
+        let poison = effects.get(StatusEffectKind::Poison).unwrap();
+        assert_eq!(poison.stacks, MAX_STACKS);
+        assert_eq!(poison.turns_left, 2);
+        assert_eq!(
+            effects.turn_start_damage(),
+            POISON_DAMAGE_PER_STACK * MAX_STACKS as i32
+        );
+    }
+
+    #[test]
+    fn ignore_keeps_the_active_effect() {
+        let mut effects = StatusEffects::default();
+        effects.apply(StatusEffectKind::Stun, 1);
+
+        assert!(!effects.apply(StatusEffectKind::Stun, 3));
+        assert_eq!(effects.get(StatusEffectKind::Stun).unwrap().turns_left, 1);
+    }
+
+    #[test]
+    fn modifiers_combine_all_effects() {
+        let mut effects = StatusEffects::default();
+        effects.apply(StatusEffectKind::Shield, 2);
+        effects.apply(StatusEffectKind::Haste, 2);
+        effects.apply(StatusEffectKind::Stun, 1);
+
+        assert_eq!(
+            effects.modifiers(),
+            StatModifiers {
+                attack: 0,
+                defence: SHIELD_DEFENCE,
+                movement: HASTE_MOVEMENT,
+                stunned: true,
+            }
+        );
+
+        effects.tick();
+        assert!(!effects.modifiers().stunned);
+    }
+
+    #[test]
+    fn units_defeated_by_poison_are_removed() {
+        let mut world = World::default();
+        world.insert_resource(Events::<TurnStart>::default());
+        world.insert_resource(Events::<TurnEnd>::default());
+        world.insert_resource(Events::<CombatEvent>::default());
+
+        let stats = Stats {
+            max_health: 10,
+            attack: 1,
+            defence: 1,
+            movement: 3,
+        };
+        let mut combatant = Combatant::new("Goblin", Team::Enemy, stats, Vec::new());
+        combatant.health = 1;
+        let mut effects = StatusEffects::default();
+        effects.apply(StatusEffectKind::Poison, 2);
+
+        let unit = world
+            .spawn()
+            .insert_bundle((combatant, HexPosition(Hex::new(0, 0)), effects))
+            .id();
+
+        let mut turn = TurnState::new(vec![unit]);
+        turn.begin_turn(unit);
+        world.insert_resource(turn);
+        world
+            .get_resource_mut::<Events<TurnStart>>()
+            .unwrap()
+            .send(TurnStart(unit));
+
+        SystemStage::single(status_effects_turn_start.system()).run(&mut world);
+
+        assert!(world.get_entity(unit).is_none());
+        assert_eq!(world.get_resource::<TurnState>().unwrap().active, None);
+    }
+}
@@ -0,0 +1,241 @@
+//! Turn structure of a battle
+//!
+//! Combatants take turns in a fixed order. During its turn a combatant may move once and
+//! use one ability, after which the turn ends automatically. Stunned combatants skip
+//! their turn. A move can be taken back until an ability is used.
+
+use bevy::prelude::*;
+
+use crate::battle::combat::Combatant;
+use crate::battle::combat_log::{CombatEvent, LogUnit};
+use crate::battle::hex::Hex;
+use crate::battle::outcome::BattleOutcome;
+use crate::battle::status_effects::StatusEffects;
+
+/// Sent when a combatant's turn begins
+pub struct TurnStart(pub Entity);
+
+/// Sent when a combatant's turn is over
+pub struct TurnEnd(pub Entity);
+
+/// The combatant whose turn it currently is, and what it has done so far
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveTurn {
+    pub entity: Entity,
+    pub has_moved: bool,
+    pub has_acted: bool,
+}
+
+impl ActiveTurn {
+    fn new(entity: Entity) -> Self {
+        ActiveTurn {
+            entity,
+            has_moved: false,
+            has_acted: false,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.has_moved && self.has_acted
+    }
+}
+
+/// Where a combatant stood and how healthy it was right before the active combatant moved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnitSnapshot {
+    pub entity: Entity,
+    pub hex: Hex,
+    pub health: i32,
+}
+
+/// The state of the battle right before the active combatant moved, used to undo the move
+///
+/// Taken at the move rather than at the start of the turn, so that undoing never reverts
+/// start-of-turn effects like poison damage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TurnSnapshot {
+    active: ActiveTurn,
+    units: Vec<UnitSnapshot>,
+}
+
+/// Keeps track of whose turn it is
+#[derive(Debug, Default)]
+pub struct TurnState {
+    order: Vec<Entity>,
+    next: usize,
+    /// The current round, starting at 1 once the first turn starts
+    pub round: u32,
+    pub active: Option<ActiveTurn>,
+    snapshot: Option<TurnSnapshot>,
+    /// Set once the player's side flees, which ends the battle
+    pub fled: bool,
+}
+
+impl TurnState {
+    pub fn new(order: Vec<Entity>) -> Self {
+        TurnState {
+            order,
+            ..Default::default()
+        }
+    }
+
+    pub fn active_entity(&self) -> Option<Entity> {
+        self.active.map(|active| active.entity)
+    }
+
+    /// Makes it the given combatant's turn
+    pub fn begin_turn(&mut self, entity: Entity) {
+        self.active = Some(ActiveTurn::new(entity));
+        self.snapshot = None;
+    }
+
+    /// Remembers the state of every combatant right before the active combatant moves
+    pub fn save_snapshot(&mut self, units: Vec<UnitSnapshot>) {
+        if let Some(active) = self.active {
+            self.snapshot = Some(TurnSnapshot { active, units });
+        }
+    }
+
+    /// Ends the active turn, if there is one
+    pub fn end_turn(&mut self, turn_ends: &mut EventWriter<TurnEnd>) {
+        self.snapshot = None;
+
+        if let Some(active) = self.active.take() {
+            turn_ends.send(TurnEnd(active.entity));
+        }
+    }
+
+    /// Whether the active combatant has moved, and can still take the move back
+    pub fn can_undo_move(&self) -> bool {
+        match (&self.active, &self.snapshot) {
+            (Some(active), Some(snapshot)) => {
+                active.entity == snapshot.active.entity && active.has_moved && !active.has_acted
+            }
+            _ => false,
+        }
+    }
+
+    /// Rewinds the active turn to before the move
+    ///
+    /// Returns the state the combatants should be put back to, or `None` if the move
+    /// can't be undone.
+    pub fn undo_move(&mut self) -> Option<Vec<UnitSnapshot>> {
+        if !self.can_undo_move() {
+            return None;
+        }
+
+        let snapshot = self.snapshot.as_ref()?;
+        self.active = Some(snapshot.active);
+
+        Some(snapshot.units.clone())
+    }
+
+    /// Picks the next combatant in the turn order, starting a new round with the first one
+    fn advance(&mut self) -> Entity {
+        if self.next == 0 {
+            self.round += 1;
+        }
+
+        let entity = self.order[self.next];
+        self.next = (self.next + 1) % self.order.len();
+
+        entity
+    }
+}
+
+/// Starts the turn of the next living combatant once the previous turn is over
+pub fn start_next_turn(
+    mut turn: ResMut<TurnState>,
+    outcome: Option<Res<BattleOutcome>>,
+    units: Query<(&Combatant, &StatusEffects)>,
+    mut turn_starts: EventWriter<TurnStart>,
+    mut turn_ends: EventWriter<TurnEnd>,
+    mut combat_events: EventWriter<CombatEvent>,
+) {
+    if turn.active.is_some() || outcome.is_some() {
+        return;
+    }
+
+    for _ in 0..turn.order.len() {
+        let entity = turn.advance();
+
+        let (combatant, effects) = match units.get(entity) {
+            Ok((combatant, effects)) if combatant.is_alive() => (combatant, effects),
+            _ => continue,
+        };
+
+        turn_starts.send(TurnStart(entity));
+        combat_events.send(CombatEvent::TurnStarted {
+            unit: LogUnit::new(combatant),
+        });
+
+        if effects.modifiers().stunned {
+            turn_ends.send(TurnEnd(entity));
+        } else {
+            turn.begin_turn(entity);
+        }
+
+        return;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turn_state() -> (TurnState, Entity, Vec<UnitSnapshot>) {
+        let unit = Entity::new(0);
+        let units = vec![
+            UnitSnapshot {
+                entity: unit,
+                hex: Hex::new(0, 0),
+                health: 10,
+            },
+            UnitSnapshot {
+                entity: Entity::new(1),
+                hex: Hex::new(2, 0),
+                health: 8,
+            },
+        ];
+
+        let mut turn = TurnState::new(vec![unit, Entity::new(1)]);
+        turn.begin_turn(unit);
+        turn.save_snapshot(units.clone());
+
+        (turn, unit, units)
+    }
+
+    #[test]
+    fn moves_can_be_undone() {
+        let (mut turn, _, units) = turn_state();
+        assert_eq!(turn.undo_move(), None);
+
+        turn.active.as_mut().unwrap().has_moved = true;
+
+        assert_eq!(turn.undo_move(), Some(units));
+        assert!(!turn.active.unwrap().has_moved);
+        assert!(!turn.can_undo_move());
+    }
+
+    #[test]
+    fn rounds_start_with_the_first_combatant() {
+        let (first, second) = (Entity::new(0), Entity::new(1));
+        let mut turn = TurnState::new(vec![first, second]);
+
+        assert_eq!((turn.advance(), turn.round), (first, 1));
+        assert_eq!((turn.advance(), turn.round), (second, 1));
+        assert_eq!((turn.advance(), turn.round), (first, 2));
+    }
+
+    #[test]
+    fn moves_cannot_be_undone_after_acting() {
+        let (mut turn, _, _) = turn_state();
+
+        let active = turn.active.as_mut().unwrap();
+        active.has_moved = true;
+        active.has_acted = true;
+
+        assert_eq!(turn.undo_move(), None);
+        assert!(turn.active.unwrap().has_moved);
+    }
+}
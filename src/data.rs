@@ -0,0 +1,33 @@
+//! Game data files, like AI personalities, stored as RON in the `assets/data` directory
+
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+
+/// Loads and parses a data file
+///
+/// `path` is relative to `assets/data` and should include the file extension. Panics if
+/// the file is missing or malformed, since the game cannot run without its data.
+pub fn load<T: DeserializeOwned>(path: &str) -> T {
+    let file = File::open(get_data_file_path(path))
+        .unwrap_or_else(|e| panic!("Could not open data file {}: {}", path, e));
+
+    ron::de::from_reader(BufReader::new(file))
+        .unwrap_or_else(|e| panic!("Could not read data file {}: {}", path, e))
+}
+
+/// Resolves the data directory the same way Bevy resolves the asset directory
+fn get_data_file_path(path: &str) -> PathBuf {
+    let root = match env::var("CARGO_MANIFEST_DIR") {
+        Ok(manifest_dir) => PathBuf::from(manifest_dir),
+        Err(_) => env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.to_path_buf()))
+            .expect("Could not find the executable directory"),
+    };
+
+    root.join("assets").join("data").join(path)
+}
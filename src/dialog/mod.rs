@@ -1,5 +1,7 @@
 use bevy::prelude::*;
 
+use crate::menu::shop_screen::ShopScreen;
+use crate::menu::{button, button_interact, spawn_button};
 use crate::AppState;
 use crate::Stage;
 use crate::UiAssets;
@@ -16,6 +18,13 @@ impl Plugin for DialogPlugin {
                 AppState::Dialog,
                 back_to_overworld.system(),
             )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Dialog,
+                button_interact::<button::OpenShop>
+                    .system()
+                    .chain(button_open_shop.system()),
+            )
             .on_state_exit(
                 Stage::AppState,
                 AppState::Dialog,
@@ -30,12 +39,50 @@ fn back_to_overworld(mut state: ResMut<State<AppState>>, input: Res<Input<KeyCod
     }
 }
 
+fn button_open_shop(
+    In(clicked): In<bool>,
+    mut commands: Commands,
+    dialog: Res<DialogResource>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if !clicked {
+        return;
+    }
+
+    if let Some(shop) = &dialog.shop {
+        commands.insert_resource(ShopScreen::new(shop));
+        state.set_next(AppState::Shop).unwrap();
+    }
+}
+
 pub struct DialogResource {
     pub npc_name: String,
     pub sprite: Handle<ColorMaterial>,
+    /// ID of the shop the NPC runs, if any
+    pub shop: Option<String>,
 }
 
 pub fn setup_dialog(mut commands: Commands, assets: Res<UiAssets>, dialog: Res<DialogResource>) {
+    let button_style = Style {
+        size: Size::new(Val::Auto, Val::Auto),
+        margin: Rect::all(Val::Px(10.0)),
+        padding: Rect {
+            left: Val::Px(12.0),
+            right: Val::Px(12.0),
+            top: Val::Px(8.0),
+            bottom: Val::Px(8.0),
+        },
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..Default::default()
+    };
+
+    let button_text_style = TextStyle {
+        font: assets.font_light.clone(),
+        font_size: 18.0,
+        color: Color::WHITE,
+    };
+
     commands
         // Container
         .spawn(NodeBundle {
@@ -146,6 +193,17 @@ pub fn setup_dialog(mut commands: Commands, assets: Res<UiAssets>, dialog: Res<D
                                 ),
                                 ..Default::default()
                             });
+
+                            if dialog.shop.is_some() {
+                                spawn_button(
+                                    root,
+                                    &assets,
+                                    &button_style,
+                                    &button_text_style,
+                                    "Shop",
+                                    button::OpenShop,
+                                );
+                            }
                         });
                 });
         });
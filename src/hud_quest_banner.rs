@@ -0,0 +1,162 @@
+//! The HUD quest banner pops up below the area label whenever a quest is started,
+//! advanced or completed. Banners are queued, so quick changes are all shown one by one.
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+/// How large the heading above the banner text should be
+const HEADING_FONT_SIZE: f32 = 12.0;
+
+/// How large the banner text should be
+const TEXT_FONT_SIZE: f32 = 18.0;
+
+/// Offset of the banner in pixels from the top of the window, below the area label
+const OFFSET_FROM_TOP: f32 = 80.0;
+
+/// How long a banner should be visible, in seconds
+const BANNER_DISPLAY_TIME: f32 = 3.0;
+
+/// How long fading in and out should last, in seconds
+const BANNER_FADE_TIME: f32 = 0.4;
+
+/// Loads the assets required for the HUD quest banner
+pub struct HudQuestBannerAssets {
+    transparent: Handle<ColorMaterial>,
+    font_bold: Handle<Font>,
+    font_regular: Handle<Font>,
+}
+
+impl FromWorld for HudQuestBannerAssets {
+    fn from_world(world: &mut World) -> Self {
+        let world = world.cell();
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        let assets = world.get_resource_mut::<AssetServer>().unwrap();
+
+        HudQuestBannerAssets {
+            transparent: materials.add(Color::NONE.into()),
+
+            font_bold: assets.load("fonts/sansation/Sansation-Bold.ttf"),
+            font_regular: assets.load("fonts/sansation/Sansation-Regular.ttf"),
+        }
+    }
+}
+
+/// Displays temporary quest banners at the top of the screen
+#[derive(Default)]
+pub struct HudQuestBanner {
+    /// Heading and text of the banners waiting to be shown
+    queue: VecDeque<(String, String)>,
+    /// How long the current banner has been shown, if there is one
+    display_timer: Option<Timer>,
+}
+
+impl HudQuestBanner {
+    /// Schedules a banner to be displayed after the ones already waiting
+    pub fn show<H: Into<String>, T: Into<String>>(&mut self, heading: H, text: T) {
+        self.queue
+            .push_back((heading.into().to_uppercase(), text.into()));
+    }
+}
+
+/// Used to identify the heading of the HUD quest banner
+pub struct HudQuestBannerHeading;
+
+/// Used to identify the text of the HUD quest banner
+pub struct HudQuestBannerText;
+
+/// Opacity of the banner, fading in at the start and out at the end
+fn opacity(elapsed: f32) -> f32 {
+    let fade_in = elapsed / BANNER_FADE_TIME;
+    let fade_out = (BANNER_DISPLAY_TIME - elapsed) / BANNER_FADE_TIME;
+    fade_in.min(fade_out).clamp(0.0, 1.0)
+}
+
+/// Updates the HUD quest banner system
+pub fn update_hud_quest_banner(
+    time: Res<Time>,
+    mut banner: ResMut<HudQuestBanner>,
+    mut headings: Query<&mut Text, (With<HudQuestBannerHeading>, Without<HudQuestBannerText>)>,
+    mut texts: Query<&mut Text, (With<HudQuestBannerText>, Without<HudQuestBannerHeading>)>,
+) {
+    if banner.display_timer.is_none() {
+        let (heading, text) = match banner.queue.pop_front() {
+            Some(next) => next,
+            None => return,
+        };
+
+        for mut node in headings.iter_mut() {
+            node.sections[0].value = heading.clone();
+        }
+        for mut node in texts.iter_mut() {
+            node.sections[0].value = text.clone();
+        }
+        banner.display_timer = Some(Timer::from_seconds(BANNER_DISPLAY_TIME, false));
+    }
+
+    let timer = banner.display_timer.as_mut().unwrap();
+    timer.tick(time.delta());
+    let alpha = opacity(timer.elapsed_secs());
+    if timer.finished() {
+        banner.display_timer = None;
+    }
+
+    for mut text in headings.iter_mut().chain(texts.iter_mut()) {
+        text.sections[0].style.color.set_a(alpha);
+    }
+}
+
+/// Sets up the components necessary for the HUD quest banner
+pub fn setup_hud_quest_banner(mut commands: Commands, assets: Res<HudQuestBannerAssets>) {
+    commands
+        // Root container at the top of the screen
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(OFFSET_FROM_TOP),
+                    left: Val::Px(0.0),
+                    right: Val::Auto,
+                    bottom: Val::Auto,
+                },
+                size: Size {
+                    width: Val::Percent(100.0),
+                    height: Val::Auto,
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: assets.transparent.clone(),
+            ..Default::default()
+        })
+        .with_children(|root| {
+            root.spawn(TextBundle {
+                text: Text::with_section(
+                    "",
+                    TextStyle {
+                        font: assets.font_bold.clone(),
+                        font_size: HEADING_FONT_SIZE,
+                        color: Color::rgba(1.0, 0.85, 0.4, 0.0),
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            })
+            .with(HudQuestBannerHeading)
+            .spawn(TextBundle {
+                text: Text::with_section(
+                    "",
+                    TextStyle {
+                        font: assets.font_regular.clone(),
+                        font_size: TEXT_FONT_SIZE,
+                        color: Color::rgba(1.0, 1.0, 1.0, 0.0),
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            })
+            .with(HudQuestBannerText);
+        });
+}
@@ -0,0 +1,94 @@
+//! The HUD stamina bar shows how much longer the player can sprint while walking around
+//! the overworld. It turns red when stamina has run out, until it has recovered enough
+//! to sprint again.
+
+use bevy::prelude::*;
+
+use crate::overworld::player::Stamina;
+use crate::overworld::StateCleanup;
+
+/// Size of the stamina bar in pixels
+const BAR_WIDTH: f32 = 120.0;
+const BAR_HEIGHT: f32 = 8.0;
+
+/// Offset of the stamina bar in pixels from the bottom left corner of the window
+const OFFSET_FROM_CORNER: f32 = 20.0;
+
+/// Loads the assets required for the HUD stamina bar
+pub struct HudStaminaAssets {
+    background: Handle<ColorMaterial>,
+    fill: Handle<ColorMaterial>,
+    fill_exhausted: Handle<ColorMaterial>,
+}
+
+impl FromWorld for HudStaminaAssets {
+    fn from_world(world: &mut World) -> Self {
+        let world = world.cell();
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+
+        HudStaminaAssets {
+            background: materials.add(Color::rgba(0.0, 0.0, 0.0, 0.5).into()),
+            fill: materials.add(Color::rgb(0.95, 0.8, 0.25).into()),
+            fill_exhausted: materials.add(Color::rgb(0.8, 0.2, 0.15).into()),
+        }
+    }
+}
+
+/// Used to identify the filled part of the HUD stamina bar
+pub struct HudStaminaFill;
+
+/// Updates the HUD stamina bar to the player's stamina
+pub fn update_hud_stamina(
+    assets: Res<HudStaminaAssets>,
+    players: Query<&Stamina>,
+    mut fills: Query<(&mut Style, &mut Handle<ColorMaterial>), With<HudStaminaFill>>,
+) {
+    let stamina = match players.iter().next() {
+        Some(stamina) => stamina,
+        None => return,
+    };
+
+    for (mut style, mut material) in fills.iter_mut() {
+        style.size.width = Val::Percent(stamina.level() * 100.0);
+        let fill = if stamina.is_exhausted() {
+            &assets.fill_exhausted
+        } else {
+            &assets.fill
+        };
+        if *material != *fill {
+            *material = fill.clone();
+        }
+    }
+}
+
+/// Sets up the HUD stamina bar, for as long as the player is in the overworld
+pub fn setup_hud_stamina(mut commands: Commands, assets: Res<HudStaminaAssets>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(OFFSET_FROM_CORNER),
+                    left: Val::Px(OFFSET_FROM_CORNER),
+                    top: Val::Auto,
+                    right: Val::Auto,
+                },
+                size: Size::new(Val::Px(BAR_WIDTH), Val::Px(BAR_HEIGHT)),
+                ..Default::default()
+            },
+            material: assets.background.clone(),
+            ..Default::default()
+        })
+        .with(StateCleanup)
+        .with_children(|root| {
+            root.spawn(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    ..Default::default()
+                },
+                material: assets.fill.clone(),
+                ..Default::default()
+            })
+            .with(HudStaminaFill);
+        });
+}
@@ -0,0 +1,402 @@
+//! Items and the party's inventory
+//!
+//! Items are defined in `assets/data/items.ron`, by name. The inventory holds stacks of
+//! items, each limited to the item's stack size, in a limited number of slots. Equipment
+//! is an item too, and is taken out of the inventory while a party member wears it.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::battle::combat::{Ability, Combatant, Stats};
+use crate::battle::status_effects::{StatusEffectKind, StatusEffects};
+
+/// How many stacks of items the inventory can hold
+pub const INVENTORY_CAPACITY: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ItemCategory {
+    Consumable,
+    Equipment,
+    Material,
+}
+
+/// Where a piece of equipment is worn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EquipmentSlot {
+    Weapon,
+    Armour,
+    Accessory,
+}
+
+impl EquipmentSlot {
+    pub const ALL: [EquipmentSlot; 3] = [
+        EquipmentSlot::Weapon,
+        EquipmentSlot::Armour,
+        EquipmentSlot::Accessory,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EquipmentSlot::Weapon => "Weapon",
+            EquipmentSlot::Armour => "Armour",
+            EquipmentSlot::Accessory => "Accessory",
+        }
+    }
+}
+
+/// An amount added to each stat, either in points or in percent
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct StatBonus {
+    pub max_health: i32,
+    pub attack: i32,
+    pub defence: i32,
+    pub movement: i32,
+}
+
+impl StatBonus {
+    pub fn combine(self, other: StatBonus) -> Self {
+        StatBonus {
+            max_health: self.max_health + other.max_health,
+            attack: self.attack + other.attack,
+            defence: self.defence + other.defence,
+            movement: self.movement + other.movement,
+        }
+    }
+
+    /// Adds the flat bonus to the stats, then increases the result by the percent bonus
+    ///
+    /// Percentages are rounded down, and no stat drops below 0, or 1 for health.
+    pub fn apply(flat: StatBonus, percent: StatBonus, stats: Stats) -> Stats {
+        let apply = |stat: i32, flat: i32, percent: i32, min: i32| {
+            ((stat + flat) * (100 + percent) / 100).max(min)
+        };
+
+        Stats {
+            max_health: apply(stats.max_health, flat.max_health, percent.max_health, 1),
+            attack: apply(stats.attack, flat.attack, percent.attack, 0),
+            defence: apply(stats.defence, flat.defence, percent.defence, 0),
+            movement: apply(stats.movement, flat.movement, percent.movement, 0),
+        }
+    }
+}
+
+/// What a piece of equipment does for the party member wearing it
+#[derive(Debug, Clone, Deserialize)]
+pub struct EquipmentStats {
+    pub slot: EquipmentSlot,
+    #[serde(default)]
+    pub flat: StatBonus,
+    #[serde(default)]
+    pub percent: StatBonus,
+    /// Abilities the wearer can use in battle, besides its own
+    #[serde(default)]
+    pub abilities: Vec<Ability>,
+}
+
+/// What happens when an item is used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ItemEffect {
+    /// Restores health of a combatant
+    Heal(i32),
+    /// Removes a status effect from a combatant
+    Cure(StatusEffectKind),
+    /// Gives every party member experience
+    GainExperience(u32),
+}
+
+impl ItemEffect {
+    /// Whether the effect can only be used on a combatant during a battle
+    pub fn in_battle(self) -> bool {
+        match self {
+            ItemEffect::Heal(_) | ItemEffect::Cure(_) => true,
+            ItemEffect::GainExperience(_) => false,
+        }
+    }
+
+    /// Whether using the item on the combatant would do anything
+    pub fn can_use_on(self, combatant: &Combatant, effects: &StatusEffects) -> bool {
+        match self {
+            ItemEffect::Heal(_) => {
+                combatant.is_alive() && combatant.health < combatant.stats.max_health
+            }
+            ItemEffect::Cure(kind) => combatant.is_alive() && effects.get(kind).is_some(),
+            ItemEffect::GainExperience(_) => false,
+        }
+    }
+
+    /// Human readable description of the effect
+    pub fn describe(self) -> String {
+        match self {
+            ItemEffect::Heal(amount) => format!("Restores {} health", amount),
+            ItemEffect::Cure(kind) => format!("Cures {:?}", kind),
+            ItemEffect::GainExperience(amount) => format!("Gives the party {} experience", amount),
+        }
+    }
+}
+
+/// An item, as defined in `data/items.ron`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Item {
+    /// Path of the icon, relative to the assets directory
+    pub icon: String,
+    /// How many of the item fit in one inventory slot
+    pub stack_size: u32,
+    pub category: ItemCategory,
+    /// Base price in shops, items without a price can't be sold
+    #[serde(default)]
+    pub price: u32,
+    /// What using the item does, if it can be used at all
+    #[serde(default)]
+    pub effect: Option<ItemEffect>,
+    /// What wearing the item does, if it can be equipped
+    #[serde(default)]
+    pub equipment: Option<EquipmentStats>,
+}
+
+/// All items, by name, as defined in `data/items.ron`
+pub struct Items(pub HashMap<String, Item>);
+
+impl Default for Items {
+    fn default() -> Self {
+        Items(crate::data::load("items.ron"))
+    }
+}
+
+impl Items {
+    pub fn get(&self, name: &str) -> &Item {
+        self.0
+            .get(name)
+            .unwrap_or_else(|| panic!("Unknown item: {}", name))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemStack {
+    pub item: String,
+    pub count: u32,
+}
+
+/// The items the party carries
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Inventory {
+    pub capacity: usize,
+    stacks: Vec<ItemStack>,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Inventory {
+            capacity: INVENTORY_CAPACITY,
+            stacks: vec![
+                ItemStack {
+                    item: "Healing Herb".to_string(),
+                    count: 2,
+                },
+                ItemStack {
+                    item: "Iron Sword".to_string(),
+                    count: 1,
+                },
+            ],
+        }
+    }
+}
+
+impl Inventory {
+    pub fn stacks(&self) -> &[ItemStack] {
+        &self.stacks
+    }
+
+    /// How many of the item the inventory holds, in all stacks
+    pub fn count(&self, item: &str) -> u32 {
+        self.stacks
+            .iter()
+            .filter(|stack| stack.item == item)
+            .map(|stack| stack.count)
+            .sum()
+    }
+
+    /// Adds items, filling existing stacks before starting new ones
+    ///
+    /// Returns how many items did not fit.
+    pub fn add(&mut self, items: &Items, item: &str, mut count: u32) -> u32 {
+        let stack_size = items.get(item).stack_size.max(1);
+
+        for stack in self.stacks.iter_mut().filter(|stack| stack.item == item) {
+            let added = count.min(stack_size.saturating_sub(stack.count));
+            stack.count += added;
+            count -= added;
+        }
+
+        while count > 0 && self.stacks.len() < self.capacity {
+            let added = count.min(stack_size);
+            self.stacks.push(ItemStack {
+                item: item.to_string(),
+                count: added,
+            });
+            count -= added;
+        }
+
+        count
+    }
+
+    /// Removes items, taking from the last stacks first
+    ///
+    /// Nothing is removed if the inventory doesn't hold enough of the item.
+    pub fn remove(&mut self, item: &str, mut count: u32) -> bool {
+        if self.count(item) < count {
+            return false;
+        }
+
+        for stack in self
+            .stacks
+            .iter_mut()
+            .rev()
+            .filter(|stack| stack.item == item)
+        {
+            let removed = count.min(stack.count);
+            stack.count -= removed;
+            count -= removed;
+        }
+
+        self.stacks.retain(|stack| stack.count > 0);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items() -> Items {
+        let item = |stack_size| Item {
+            icon: String::new(),
+            stack_size,
+            category: ItemCategory::Consumable,
+            price: 0,
+            effect: None,
+            equipment: None,
+        };
+
+        let mut items = HashMap::new();
+        items.insert("Herb".to_string(), item(5));
+        items.insert("Tome".to_string(), item(1));
+        Items(items)
+    }
+
+    fn empty_inventory(capacity: usize) -> Inventory {
+        Inventory {
+            capacity,
+            stacks: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn items_stack_up_to_their_stack_size() {
+        let items = items();
+        let mut inventory = empty_inventory(10);
+
+        assert_eq!(inventory.add(&items, "Herb", 3), 0);
+        assert_eq!(inventory.add(&items, "Herb", 4), 0);
+        assert_eq!(inventory.add(&items, "Tome", 2), 0);
+
+        let counts: Vec<(&str, u32)> = inventory
+            .stacks()
+            .iter()
+            .map(|s| (s.item.as_str(), s.count))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![("Herb", 5), ("Herb", 2), ("Tome", 1), ("Tome", 1)]
+        );
+        assert_eq!(inventory.count("Herb"), 7);
+    }
+
+    #[test]
+    fn full_inventories_return_what_does_not_fit() {
+        let items = items();
+        let mut inventory = empty_inventory(2);
+
+        assert_eq!(inventory.add(&items, "Herb", 12), 2);
+        assert_eq!(inventory.add(&items, "Tome", 1), 1);
+        assert_eq!(inventory.count("Herb"), 10);
+    }
+
+    #[test]
+    fn removing_takes_from_the_last_stacks() {
+        let items = items();
+        let mut inventory = empty_inventory(10);
+        inventory.add(&items, "Herb", 7);
+
+        assert!(!inventory.remove("Herb", 8));
+        assert_eq!(inventory.count("Herb"), 7);
+
+        assert!(inventory.remove("Herb", 3));
+        assert_eq!(inventory.stacks().len(), 1);
+        assert_eq!(inventory.count("Herb"), 4);
+    }
+
+    #[test]
+    fn bonuses_add_points_before_percentages() {
+        let stats = Stats {
+            max_health: 30,
+            attack: 4,
+            defence: 2,
+            movement: 4,
+        };
+        let flat = StatBonus {
+            attack: 2,
+            defence: -5,
+            ..Default::default()
+        };
+        let percent = StatBonus {
+            max_health: 10,
+            attack: 50,
+            ..Default::default()
+        };
+
+        let bonus = StatBonus::apply(flat, percent, stats);
+        assert_eq!(bonus.max_health, 33);
+        assert_eq!(bonus.attack, 9);
+        assert_eq!(bonus.defence, 0);
+        assert_eq!(bonus.movement, 4);
+    }
+
+    #[test]
+    fn items_are_only_used_when_they_help() {
+        let mut combatant = crate::party::PartyMember::new("Hero")
+            .combatant(&crate::party::Characters::default(), &Items::default());
+        let mut effects = StatusEffects::default();
+
+        assert!(!ItemEffect::Heal(5).can_use_on(&combatant, &effects));
+        assert!(!ItemEffect::Cure(StatusEffectKind::Poison).can_use_on(&combatant, &effects));
+
+        combatant.take_damage(3);
+        effects.apply(StatusEffectKind::Poison, 2);
+        assert!(ItemEffect::Heal(5).can_use_on(&combatant, &effects));
+        assert!(ItemEffect::Cure(StatusEffectKind::Poison).can_use_on(&combatant, &effects));
+        assert!(!ItemEffect::GainExperience(5).can_use_on(&combatant, &effects));
+    }
+
+    #[test]
+    fn item_files_are_valid() {
+        let items = Items::default();
+
+        for stack in Inventory::default().stacks() {
+            assert!(items.0.contains_key(&stack.item));
+        }
+
+        let encounter = crate::battle::encounter::Encounter::load("bevytown_goblins");
+        for loot in &encounter.rewards.loot {
+            assert!(items.0.contains_key(loot));
+        }
+
+        for item in items.0.values() {
+            assert_eq!(
+                item.category == ItemCategory::Equipment,
+                item.equipment.is_some()
+            );
+        }
+    }
+}
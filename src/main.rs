@@ -7,20 +7,37 @@ use bevy::{ecs::schedule::ReportExecutionOrderAmbiguities, prelude::*};
 
 use battle::BattlePlugin;
 use dialog::DialogPlugin;
+use inventory::Items;
 use menu::MenuPlugin;
 use overworld::OverworldPlugin;
+use party::Characters;
+use quest::QuestPlugin;
+use save::SavePlugin;
 
 use crate::hud_area_label::{
     setup_hud_area_label, update_hud_area_label, HudAreaLabel, HudAreaLabelAssets,
 };
+use crate::hud_quest_banner::{
+    setup_hud_quest_banner, update_hud_quest_banner, HudQuestBanner, HudQuestBannerAssets,
+};
 use crate::user_config::{KeyBinds, UserConfig};
 
 mod battle;
+mod data;
 mod dialog;
 mod hud_area_label;
+mod hud_quest_banner;
+mod hud_stamina;
+mod inventory;
 mod menu;
 mod overworld;
+mod party;
+mod quest;
+mod rng;
+mod save;
+mod shop;
 mod user_config;
+mod world_time;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, StageLabel)]
 pub enum Stage {
@@ -32,6 +49,12 @@ pub enum AppState {
     MainMenu,
     SettingsMenu,
     Overworld,
+    PauseMenu,
+    PartyScreen,
+    InventoryScreen,
+    EquipScreen,
+    Shop,
+    Journal,
     Battle,
     Dialog,
 }
@@ -71,6 +94,20 @@ fn despawn_all<T: Component>(
     }
 }
 
+/// Finds the value following the given command line argument, like `--seed 42`
+fn argument_value<I: Iterator<Item = String>>(mut args: I, name: &str) -> Option<String> {
+    while let Some(arg) = args.next() {
+        if arg == name {
+            let value = args
+                .next()
+                .unwrap_or_else(|| panic!("Missing value for {}", name));
+            return Some(value);
+        }
+    }
+
+    None
+}
+
 fn main() {
     App::build()
         // Bevy configurations
@@ -90,6 +127,7 @@ fn main() {
         .insert_resource(ClearColor(Color::BLACK))
         .add_plugins(DefaultPlugins)
         .insert_resource(KeyBinds::load())
+        .add_startup_system(rng::log_rng_seed.system())
         .init_resource::<UiAssets>()
         .add_startup_system(global_setup.system())
         // HUD area label
@@ -97,6 +135,11 @@ fn main() {
         .init_resource::<HudAreaLabel>()
         .add_startup_system(setup_hud_area_label.system())
         .add_system(update_hud_area_label.system())
+        // HUD quest banner
+        .init_resource::<HudQuestBannerAssets>()
+        .init_resource::<HudQuestBanner>()
+        .add_startup_system(setup_hud_quest_banner.system())
+        .add_system(update_hud_quest_banner.system())
         // AppState
         .insert_resource(State::new(AppState::MainMenu))
         .add_stage_before(
@@ -104,6 +147,10 @@ fn main() {
             Stage::AppState,
             StateStage::<AppState>::default(),
         )
+        .init_resource::<Characters>()
+        .init_resource::<Items>()
+        .add_plugin(SavePlugin)
+        .add_plugin(QuestPlugin)
         // State Plugins
         .add_plugin(MenuPlugin)
         .add_plugin(OverworldPlugin)
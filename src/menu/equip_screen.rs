@@ -0,0 +1,429 @@
+//! Changing the equipment of a party member
+//!
+//! Picking a piece of equipment from the inventory previews how the member's stats would
+//! change, and only takes effect once confirmed.
+
+use bevy::prelude::*;
+
+use crate::battle::combat::Stats;
+use crate::inventory::{Inventory, Items};
+use crate::menu::{button, spawn_button};
+use crate::party::{Characters, Party};
+use crate::AppState;
+use crate::UiAssets;
+
+/// Marker for despawning when exiting `AppState::EquipScreen`
+pub struct StateCleanup;
+
+/// The party member being equipped, inserted before entering `AppState::EquipScreen`
+pub struct EquipScreen {
+    /// Index of the member in the party
+    member: usize,
+    /// Inventory slot of the equipment being previewed
+    selected: Option<usize>,
+    /// Whether the screen has to be spawned again to show a change
+    dirty: bool,
+}
+
+impl EquipScreen {
+    pub fn new(member: usize) -> Self {
+        EquipScreen {
+            member,
+            selected: None,
+            dirty: true,
+        }
+    }
+}
+
+pub fn button_close_equip_screen(In(clicked): In<bool>, mut state: ResMut<State<AppState>>) {
+    if clicked {
+        state.set_next(AppState::PartyScreen).unwrap();
+    }
+}
+
+pub fn close_equip_screen(mut state: ResMut<State<AppState>>, input: Res<Input<KeyCode>>) {
+    if input.just_pressed(KeyCode::Escape) {
+        state.set_next(AppState::PartyScreen).unwrap();
+    }
+}
+
+pub fn button_select_equipment(
+    In(clicked): In<bool>,
+    mut screen: ResMut<EquipScreen>,
+    buttons: Query<(&Interaction, &button::SelectEquipment)>,
+) {
+    if !clicked {
+        return;
+    }
+
+    let slot = buttons
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Clicked)
+        .map(|(_, button)| button.0);
+
+    if slot.is_some() {
+        screen.selected = slot;
+        screen.dirty = true;
+    }
+}
+
+pub fn button_confirm_equip(
+    In(clicked): In<bool>,
+    mut screen: ResMut<EquipScreen>,
+    mut party: ResMut<Party>,
+    items: Res<Items>,
+    mut inventory: ResMut<Inventory>,
+) {
+    if !clicked {
+        return;
+    }
+
+    let item = match screen
+        .selected
+        .and_then(|slot| inventory.stacks().get(slot))
+    {
+        Some(stack) => stack.item.clone(),
+        None => return,
+    };
+
+    let member = &mut party.members[screen.member];
+    if !member.equip(&item, &items, &mut inventory) {
+        warn!("{} could not equip {}", member.name, item);
+    }
+
+    screen.selected = None;
+    screen.dirty = true;
+}
+
+pub fn button_unequip(
+    In(clicked): In<bool>,
+    mut screen: ResMut<EquipScreen>,
+    mut party: ResMut<Party>,
+    items: Res<Items>,
+    mut inventory: ResMut<Inventory>,
+    buttons: Query<(&Interaction, &button::Unequip)>,
+) {
+    if !clicked {
+        return;
+    }
+
+    let slot = buttons
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Clicked)
+        .map(|(_, button)| button.0);
+
+    if let Some(slot) = slot {
+        let member = &mut party.members[screen.member];
+        if !member.unequip(slot, &items, &mut inventory) {
+            warn!(
+                "No room in the inventory for {}'s {}",
+                member.name,
+                slot.name()
+            );
+        }
+
+        // Inventory slots may have moved
+        screen.selected = None;
+        screen.dirty = true;
+    }
+}
+
+/// One line per stat, showing how the preview changes it
+fn stat_lines(current: &Stats, preview: &Stats) -> Vec<String> {
+    let stats = [
+        ("Health", current.max_health, preview.max_health),
+        ("Attack", current.attack, preview.attack),
+        ("Defence", current.defence, preview.defence),
+        ("Movement", current.movement, preview.movement),
+    ];
+
+    stats
+        .iter()
+        .map(|(name, current, preview)| {
+            if current == preview {
+                format!("{} {}", name, current)
+            } else {
+                format!(
+                    "{} {} -> {} ({:+})",
+                    name,
+                    current,
+                    preview,
+                    preview - current
+                )
+            }
+        })
+        .collect()
+}
+
+/// Spawns the screen again whenever something on it changed
+#[allow(clippy::too_many_arguments)]
+pub fn refresh_equip_screen(
+    mut commands: Commands,
+    mut screen: ResMut<EquipScreen>,
+    assets: Res<UiAssets>,
+    party: Res<Party>,
+    characters: Res<Characters>,
+    items: Res<Items>,
+    inventory: Res<Inventory>,
+    spawned: Query<Entity, With<StateCleanup>>,
+) {
+    if !screen.dirty {
+        return;
+    }
+    screen.dirty = false;
+
+    for entity in spawned.iter() {
+        commands.despawn_recursive(entity);
+    }
+
+    let member = &party.members[screen.member];
+    let character = characters.get(&member.name);
+
+    // Equip the selected item on copies, to preview the result
+    let mut preview = member.clone();
+    let mut preview_inventory = inventory.clone();
+    let selected = screen
+        .selected
+        .and_then(|slot| inventory.stacks().get(slot))
+        .map(|stack| stack.item.as_str());
+    let fits = selected.map_or(true, |item| {
+        preview.equip(item, &items, &mut preview_inventory)
+    });
+
+    let mut lines = stat_lines(
+        &member.equipped_stats(character, &items),
+        &preview.equipped_stats(character, &items),
+    );
+    let abilities: Vec<String> = preview
+        .abilities(character, &items)
+        .into_iter()
+        .map(|a| a.name)
+        .collect();
+    lines.push(format!("Abilities: {}", abilities.join(", ")));
+    if !fits {
+        lines.push("No room in the inventory for the replaced item".to_string());
+    }
+
+    let button_style = Style {
+        size: Size::new(Val::Auto, Val::Auto),
+        margin: Rect::all(Val::Px(5.0)),
+        padding: Rect {
+            left: Val::Px(12.0),
+            right: Val::Px(12.0),
+            top: Val::Px(8.0),
+            bottom: Val::Px(8.0),
+        },
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..Default::default()
+    };
+
+    let row_style = Style {
+        size: Size::new(Val::Percent(100.0), Val::Auto),
+        justify_content: JustifyContent::SpaceBetween,
+        align_items: AlignItems::Center,
+        ..Default::default()
+    };
+
+    let spacer = || NodeBundle {
+        style: Style {
+            size: Size::new(Val::Auto, Val::Px(16.0)),
+            ..Default::default()
+        },
+        material: assets.transparent.clone(),
+        ..Default::default()
+    };
+
+    let button_text_style = TextStyle {
+        font: assets.font_light.clone(),
+        font_size: 18.0,
+        color: Color::WHITE,
+    };
+
+    let name_style = TextStyle {
+        font: assets.font_bold.clone(),
+        font_size: 18.0,
+        color: Color::rgb(0.9, 0.9, 0.95),
+    };
+
+    let line_style = TextStyle {
+        font: assets.font_light.clone(),
+        font_size: 15.0,
+        color: Color::rgb(0.9, 0.9, 0.95),
+    };
+
+    let text = |value: String, style: &TextStyle| TextBundle {
+        text: Text::with_section(value, style.clone(), Default::default()),
+        ..Default::default()
+    };
+
+    commands
+        // Container
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: assets.transparent.clone(),
+            ..Default::default()
+        })
+        .with(StateCleanup)
+        .with_children(|root| {
+            root
+                // Equip panel
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(500.0), Val::Auto),
+                        flex_direction: FlexDirection::ColumnReverse,
+                        justify_content: JustifyContent::FlexStart,
+                        align_items: AlignItems::FlexStart,
+                        padding: Rect::all(Val::Px(8.0)),
+                        ..Default::default()
+                    },
+                    material: assets.menu_panel_background.clone(),
+                    ..Default::default()
+                })
+                .with_children(|panel| {
+                    // Title
+                    panel
+                        .spawn(TextBundle {
+                            text: Text::with_section(
+                                format!("EQUIP {}", member.name.to_uppercase()),
+                                TextStyle {
+                                    font: assets.font_bold.clone(),
+                                    font_size: 16.0,
+                                    color: Color::rgb(0.9, 0.9, 0.95),
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .spawn(spacer());
+
+                    // Equipped items
+                    for (slot, item) in member.equipment.slots().iter() {
+                        panel
+                            .spawn(NodeBundle {
+                                style: row_style.clone(),
+                                material: assets.transparent.clone(),
+                                ..Default::default()
+                            })
+                            .with_children(|row| {
+                                row.spawn(text(
+                                    format!("{}: {}", slot.name(), item.unwrap_or("-")),
+                                    &line_style,
+                                ));
+
+                                if item.is_some() {
+                                    spawn_button(
+                                        row,
+                                        &assets,
+                                        &button_style,
+                                        &button_text_style,
+                                        "Unequip",
+                                        button::Unequip(*slot),
+                                    );
+                                }
+                            });
+                    }
+
+                    // Stats, with the changes of the selected equipment
+                    panel
+                        .spawn(spacer())
+                        .spawn(text("Stats".to_string(), &name_style));
+                    for line in lines {
+                        panel.spawn(text(line, &line_style));
+                    }
+
+                    // Equipment in the inventory
+                    panel
+                        .spawn(spacer())
+                        .spawn(text("Inventory".to_string(), &name_style));
+
+                    let equipment =
+                        inventory
+                            .stacks()
+                            .iter()
+                            .enumerate()
+                            .filter_map(|(slot, stack)| {
+                                items
+                                    .get(&stack.item)
+                                    .equipment
+                                    .as_ref()
+                                    .map(|equipment| (slot, stack, equipment))
+                            });
+
+                    for (slot, stack, equipment) in equipment {
+                        panel
+                            .spawn(NodeBundle {
+                                style: row_style.clone(),
+                                material: assets.transparent.clone(),
+                                ..Default::default()
+                            })
+                            .with_children(|row| {
+                                row.spawn(text(
+                                    format!("{} ({})", stack.item, equipment.slot.name()),
+                                    &line_style,
+                                ));
+
+                                if screen.selected == Some(slot) {
+                                    row.spawn(text("Selected".to_string(), &line_style));
+                                } else {
+                                    spawn_button(
+                                        row,
+                                        &assets,
+                                        &button_style,
+                                        &button_text_style,
+                                        "Select",
+                                        button::SelectEquipment(slot),
+                                    );
+                                }
+                            });
+                    }
+                })
+                // Spacer
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Auto, Val::Px(8.0)),
+                        ..Default::default()
+                    },
+                    material: assets.transparent.clone(),
+                    ..Default::default()
+                })
+                // Button bar under equip panel
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(500.0), Val::Auto),
+                        justify_content: JustifyContent::FlexEnd,
+                        ..Default::default()
+                    },
+                    material: assets.menu_panel_background.clone(),
+                    ..Default::default()
+                })
+                .with_children(|button_bar| {
+                    if selected.is_some() && fits {
+                        spawn_button(
+                            button_bar,
+                            &assets,
+                            &button_style,
+                            &button_text_style,
+                            "Confirm",
+                            button::ConfirmEquip,
+                        );
+                    }
+
+                    spawn_button(
+                        button_bar,
+                        &assets,
+                        &button_style,
+                        &button_text_style,
+                        "Back",
+                        button::CloseEquipScreen,
+                    );
+                });
+        });
+}
@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::inventory::{Inventory, Item, ItemEffect, Items};
+use crate::menu::{button, spawn_button};
+use crate::party::Party;
+use crate::shop::Wallet;
+use crate::AppState;
+use crate::UiAssets;
+
+/// Marker for despawning when exiting `AppState::InventoryScreen`
+pub struct StateCleanup;
+
+/// State of the inventory screen, inserted before entering `AppState::InventoryScreen`
+pub struct InventoryScreen {
+    /// Whether the screen has to be spawned again to show a change
+    dirty: bool,
+}
+
+impl Default for InventoryScreen {
+    fn default() -> Self {
+        InventoryScreen { dirty: true }
+    }
+}
+
+/// Materials showing the item icons, by icon path, so refreshing the screen reuses them
+#[derive(Default)]
+pub struct ItemIcons(HashMap<String, Handle<ColorMaterial>>);
+
+impl ItemIcons {
+    /// The material showing the item's icon, loaded the first time it is needed
+    fn get(
+        &mut self,
+        item: &Item,
+        asset_server: &AssetServer,
+        materials: &mut Assets<ColorMaterial>,
+    ) -> Handle<ColorMaterial> {
+        self.0
+            .entry(item.icon.clone())
+            .or_insert_with(|| materials.add(asset_server.load(item.icon.as_str()).into()))
+            .clone()
+    }
+}
+
+pub fn button_close_inventory_screen(In(clicked): In<bool>, mut state: ResMut<State<AppState>>) {
+    if clicked {
+        state.set_next(AppState::PauseMenu).unwrap();
+    }
+}
+
+pub fn close_inventory_screen(mut state: ResMut<State<AppState>>, input: Res<Input<KeyCode>>) {
+    if input.just_pressed(KeyCode::Escape) {
+        state.set_next(AppState::PauseMenu).unwrap();
+    }
+}
+
+/// Uses the clicked item
+pub fn button_use_item(
+    In(clicked): In<bool>,
+    mut screen: ResMut<InventoryScreen>,
+    items: Res<Items>,
+    mut inventory: ResMut<Inventory>,
+    mut party: ResMut<Party>,
+    buttons: Query<(&Interaction, &button::UseItem)>,
+) {
+    if !clicked {
+        return;
+    }
+
+    let slot = buttons
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Clicked)
+        .map(|(_, button)| button.0);
+
+    let item = match slot.and_then(|slot| inventory.stacks().get(slot)) {
+        Some(stack) => stack.item.clone(),
+        None => return,
+    };
+
+    match items.get(&item).effect {
+        Some(ItemEffect::GainExperience(experience)) => party.gain_experience(experience),
+        _ => return,
+    }
+
+    inventory.remove(&item, 1);
+    info!("Used {}", item);
+
+    screen.dirty = true;
+}
+
+/// Spawns the screen again whenever something on it changed
+#[allow(clippy::too_many_arguments)]
+pub fn refresh_inventory_screen(
+    mut commands: Commands,
+    mut screen: ResMut<InventoryScreen>,
+    assets: Res<UiAssets>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut icons: ResMut<ItemIcons>,
+    items: Res<Items>,
+    inventory: Res<Inventory>,
+    wallet: Res<Wallet>,
+    spawned: Query<Entity, With<StateCleanup>>,
+) {
+    if !screen.dirty {
+        return;
+    }
+    screen.dirty = false;
+
+    for entity in spawned.iter() {
+        commands.despawn_recursive(entity);
+    }
+
+    spawn_inventory_screen(
+        &mut commands,
+        &assets,
+        &asset_server,
+        &mut materials,
+        &mut icons,
+        &items,
+        &inventory,
+        &wallet,
+    );
+}
+
+fn spawn_inventory_screen(
+    commands: &mut Commands,
+    assets: &UiAssets,
+    asset_server: &AssetServer,
+    materials: &mut Assets<ColorMaterial>,
+    icons: &mut ItemIcons,
+    items: &Items,
+    inventory: &Inventory,
+    wallet: &Wallet,
+) {
+    let button_style = Style {
+        size: Size::new(Val::Auto, Val::Auto),
+        margin: Rect::all(Val::Px(5.0)),
+        padding: Rect {
+            left: Val::Px(12.0),
+            right: Val::Px(12.0),
+            top: Val::Px(8.0),
+            bottom: Val::Px(8.0),
+        },
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..Default::default()
+    };
+
+    let button_text_style = TextStyle {
+        font: assets.font_light.clone(),
+        font_size: 18.0,
+        color: Color::WHITE,
+    };
+
+    let name_style = TextStyle {
+        font: assets.font_bold.clone(),
+        font_size: 18.0,
+        color: Color::rgb(0.9, 0.9, 0.95),
+    };
+
+    let line_style = TextStyle {
+        font: assets.font_light.clone(),
+        font_size: 15.0,
+        color: Color::rgb(0.9, 0.9, 0.95),
+    };
+
+    commands
+        // Container
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: assets.transparent.clone(),
+            ..Default::default()
+        })
+        .with(StateCleanup)
+        .with_children(|root| {
+            root
+                // Inventory panel
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(500.0), Val::Auto),
+                        flex_direction: FlexDirection::ColumnReverse,
+                        justify_content: JustifyContent::FlexStart,
+                        align_items: AlignItems::Stretch,
+                        padding: Rect::all(Val::Px(8.0)),
+                        ..Default::default()
+                    },
+                    material: assets.menu_panel_background.clone(),
+                    ..Default::default()
+                })
+                .with_children(|panel| {
+                    // Title
+                    panel
+                        .spawn(TextBundle {
+                            text: Text::with_section(
+                                format!(
+                                    "INVENTORY   {} / {}",
+                                    inventory.stacks().len(),
+                                    inventory.capacity
+                                ),
+                                TextStyle {
+                                    font: assets.font_bold.clone(),
+                                    font_size: 16.0,
+                                    color: Color::rgb(0.9, 0.9, 0.95),
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .spawn(TextBundle {
+                            text: Text::with_section(
+                                format!("Gold: {}", wallet.0),
+                                line_style.clone(),
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        // Spacer
+                        .spawn(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Auto, Val::Px(8.0)),
+                                ..Default::default()
+                            },
+                            material: assets.transparent.clone(),
+                            ..Default::default()
+                        });
+
+                    if inventory.stacks().is_empty() {
+                        panel.spawn(TextBundle {
+                            text: Text::with_section(
+                                "No items",
+                                line_style.clone(),
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        });
+                    }
+
+                    for (slot, stack) in inventory.stacks().iter().enumerate() {
+                        let item = items.get(&stack.item);
+                        let description = match item.effect {
+                            Some(effect) => format!("{:?}: {}", item.category, effect.describe()),
+                            None => format!("{:?}", item.category),
+                        };
+
+                        panel
+                            // Item row
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    size: Size::new(Val::Auto, Val::Auto),
+                                    margin: Rect::all(Val::Px(4.0)),
+                                    align_items: AlignItems::Center,
+                                    ..Default::default()
+                                },
+                                material: assets.transparent.clone(),
+                                ..Default::default()
+                            })
+                            .with_children(|row| {
+                                row
+                                    // Icon
+                                    .spawn(ImageBundle {
+                                        style: Style {
+                                            size: Size::new(Val::Px(32.0), Val::Px(32.0)),
+                                            margin: Rect {
+                                                right: Val::Px(8.0),
+                                                ..Default::default()
+                                            },
+                                            ..Default::default()
+                                        },
+                                        material: icons.get(item, asset_server, materials),
+                                        ..Default::default()
+                                    })
+                                    // Name, count and description
+                                    .spawn(NodeBundle {
+                                        style: Style {
+                                            flex_direction: FlexDirection::ColumnReverse,
+                                            flex_grow: 1.0,
+                                            ..Default::default()
+                                        },
+                                        material: assets.transparent.clone(),
+                                        ..Default::default()
+                                    })
+                                    .with_children(|text| {
+                                        text.spawn(TextBundle {
+                                            text: Text::with_section(
+                                                format!("{} x{}", stack.item, stack.count),
+                                                name_style.clone(),
+                                                Default::default(),
+                                            ),
+                                            ..Default::default()
+                                        })
+                                        .spawn(
+                                            TextBundle {
+                                                text: Text::with_section(
+                                                    description,
+                                                    line_style.clone(),
+                                                    Default::default(),
+                                                ),
+                                                ..Default::default()
+                                            },
+                                        );
+                                    });
+
+                                // Only items that don't need a combatant can be used here
+                                if item.effect.map_or(false, |effect| !effect.in_battle()) {
+                                    spawn_button(
+                                        row,
+                                        assets,
+                                        &button_style,
+                                        &button_text_style,
+                                        "Use",
+                                        button::UseItem(slot),
+                                    );
+                                }
+                            });
+                    }
+                })
+                // Spacer
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Auto, Val::Px(8.0)),
+                        ..Default::default()
+                    },
+                    material: assets.transparent.clone(),
+                    ..Default::default()
+                })
+                // Button bar under inventory panel
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(500.0), Val::Auto),
+                        justify_content: JustifyContent::FlexEnd,
+                        ..Default::default()
+                    },
+                    material: assets.menu_panel_background.clone(),
+                    ..Default::default()
+                })
+                .with_children(|button_bar| {
+                    spawn_button(
+                        button_bar,
+                        assets,
+                        &button_style,
+                        &button_text_style,
+                        "Back",
+                        button::CloseInventoryScreen,
+                    );
+                });
+        });
+}
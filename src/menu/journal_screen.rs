@@ -0,0 +1,216 @@
+//! The quest journal, listing the started quests and what is left to do for them
+
+use bevy::prelude::*;
+
+use crate::inventory::Inventory;
+use crate::menu::{button, spawn_button};
+use crate::quest::{Objective, QuestLog, QuestProgress, Quests};
+use crate::AppState;
+use crate::UiAssets;
+
+/// Marker for despawning when exiting `AppState::Journal`
+pub struct StateCleanup;
+
+pub fn button_close_journal(In(clicked): In<bool>, mut state: ResMut<State<AppState>>) {
+    if clicked {
+        state.set_next(AppState::PauseMenu).unwrap();
+    }
+}
+
+pub fn close_journal(mut state: ResMut<State<AppState>>, input: Res<Input<KeyCode>>) {
+    if input.just_pressed(KeyCode::Escape) {
+        state.set_next(AppState::PauseMenu).unwrap();
+    }
+}
+
+/// One line per objective the quest got to, ticking off the completed ones
+fn objective_lines(
+    progress: &QuestProgress,
+    quests: &Quests,
+    inventory: &Inventory,
+) -> Vec<String> {
+    quests
+        .get(&progress.id)
+        .objectives
+        .iter()
+        .take(progress.objective + 1)
+        .enumerate()
+        .map(|(index, objective)| {
+            let done = index < progress.objective;
+            let count = match objective {
+                Objective::Collect { item, count } if !done => {
+                    format!(" ({}/{})", inventory.count(item).min(*count), count)
+                }
+                _ => String::new(),
+            };
+
+            format!(
+                "[{}] {}{}",
+                if done { "x" } else { " " },
+                objective.describe(),
+                count
+            )
+        })
+        .collect()
+}
+
+pub fn setup(
+    mut commands: Commands,
+    assets: Res<UiAssets>,
+    quests: Res<Quests>,
+    log: Res<QuestLog>,
+    inventory: Res<Inventory>,
+) {
+    let (completed, active): (Vec<&QuestProgress>, Vec<&QuestProgress>) = log
+        .quests()
+        .iter()
+        .partition(|progress| log.is_completed(progress, &quests));
+
+    let button_style = Style {
+        size: Size::new(Val::Auto, Val::Auto),
+        margin: Rect::all(Val::Px(5.0)),
+        padding: Rect {
+            left: Val::Px(12.0),
+            right: Val::Px(12.0),
+            top: Val::Px(8.0),
+            bottom: Val::Px(8.0),
+        },
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..Default::default()
+    };
+
+    let spacer = || NodeBundle {
+        style: Style {
+            size: Size::new(Val::Auto, Val::Px(16.0)),
+            ..Default::default()
+        },
+        material: assets.transparent.clone(),
+        ..Default::default()
+    };
+
+    let button_text_style = TextStyle {
+        font: assets.font_light.clone(),
+        font_size: 18.0,
+        color: Color::WHITE,
+    };
+
+    let name_style = TextStyle {
+        font: assets.font_bold.clone(),
+        font_size: 18.0,
+        color: Color::rgb(0.9, 0.9, 0.95),
+    };
+
+    let line_style = TextStyle {
+        font: assets.font_light.clone(),
+        font_size: 15.0,
+        color: Color::rgb(0.9, 0.9, 0.95),
+    };
+
+    let text = |value: String, style: &TextStyle| TextBundle {
+        text: Text::with_section(value, style.clone(), Default::default()),
+        ..Default::default()
+    };
+
+    commands
+        // Container
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: assets.transparent.clone(),
+            ..Default::default()
+        })
+        .with(StateCleanup)
+        .with_children(|root| {
+            root
+                // Journal panel
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(560.0), Val::Auto),
+                        flex_direction: FlexDirection::ColumnReverse,
+                        justify_content: JustifyContent::FlexStart,
+                        align_items: AlignItems::FlexStart,
+                        padding: Rect::all(Val::Px(8.0)),
+                        ..Default::default()
+                    },
+                    material: assets.menu_panel_background.clone(),
+                    ..Default::default()
+                })
+                .with_children(|panel| {
+                    // Title
+                    panel
+                        .spawn(TextBundle {
+                            text: Text::with_section(
+                                "JOURNAL",
+                                TextStyle {
+                                    font: assets.font_bold.clone(),
+                                    font_size: 16.0,
+                                    color: Color::rgb(0.9, 0.9, 0.95),
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .spawn(spacer());
+
+                    if active.is_empty() {
+                        panel.spawn(text("No quests in progress".to_string(), &line_style));
+                    }
+
+                    for progress in active {
+                        let quest = quests.get(&progress.id);
+
+                        panel
+                            .spawn(text(quest.title.clone(), &name_style))
+                            .spawn(text(quest.description.clone(), &line_style));
+                        for line in objective_lines(progress, &quests, &inventory) {
+                            panel.spawn(text(line, &line_style));
+                        }
+                        panel.spawn(spacer());
+                    }
+
+                    if !completed.is_empty() {
+                        panel.spawn(text("Completed".to_string(), &name_style));
+                    }
+
+                    for progress in completed {
+                        panel.spawn(text(quests.get(&progress.id).title.clone(), &line_style));
+                    }
+                })
+                // Spacer
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Auto, Val::Px(8.0)),
+                        ..Default::default()
+                    },
+                    material: assets.transparent.clone(),
+                    ..Default::default()
+                })
+                // Button bar under journal panel
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(560.0), Val::Auto),
+                        justify_content: JustifyContent::FlexEnd,
+                        ..Default::default()
+                    },
+                    material: assets.menu_panel_background.clone(),
+                    ..Default::default()
+                })
+                .with_children(|button_bar| {
+                    spawn_button(
+                        button_bar,
+                        &assets,
+                        &button_style,
+                        &button_text_style,
+                        "Back",
+                        button::CloseJournal,
+                    );
+                });
+        });
+}
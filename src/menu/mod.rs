@@ -4,8 +4,14 @@ use crate::AppState;
 use crate::Stage;
 use crate::UiAssets;
 
+pub mod equip_screen;
+pub mod inventory_screen;
+pub mod journal_screen;
 pub mod main_menu;
+pub mod party_screen;
+pub mod pause_menu;
 pub mod settings;
+pub mod shop_screen;
 
 /// Every logical action for which we can have a UI button
 ///
@@ -15,11 +21,37 @@ pub mod button {
     pub struct ExitApp;
     pub struct OpenSettingsMenu;
     pub struct ExitSettingsMenu;
+    pub struct LeaveBattle;
+    pub struct ResumeGame;
+    pub struct OpenPartyScreen;
+    pub struct ExitToMainMenu;
+    pub struct ClosePartyScreen;
+    pub struct OpenInventoryScreen;
+    pub struct CloseInventoryScreen;
+    /// Uses the item in the inventory slot with the given index
+    pub struct UseItem(pub usize);
+    /// Opens the equip screen for the party member with the given index
+    pub struct OpenEquipScreen(pub usize);
+    pub struct CloseEquipScreen;
+    /// Selects the equipment in the inventory slot with the given index
+    pub struct SelectEquipment(pub usize);
+    pub struct ConfirmEquip;
+    pub struct Unequip(pub crate::inventory::EquipmentSlot);
+    pub struct OpenShop;
+    pub struct CloseShop;
+    /// Buys the shop's entry with the given index
+    pub struct BuyItem(pub usize);
+    /// Sells one of the named item
+    pub struct SellItem(pub String);
+    pub struct OpenJournal;
+    pub struct CloseJournal;
 }
 
 pub struct MenuPlugin;
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<inventory_screen::ItemIcons>();
+
         app.on_state_enter(
             Stage::AppState,
             AppState::MainMenu,
@@ -68,6 +100,221 @@ impl Plugin for MenuPlugin {
             Stage::AppState,
             AppState::SettingsMenu,
             crate::despawn_all::<settings::StateCleanup>.system(),
+        )
+        // Pause menu
+        .on_state_enter(
+            Stage::AppState,
+            AppState::PauseMenu,
+            pause_menu::setup.system(),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::PauseMenu,
+            button_interact::<button::ResumeGame>
+                .system()
+                .chain(pause_menu::button_resume_game.system()),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::PauseMenu,
+            button_interact::<button::OpenPartyScreen>
+                .system()
+                .chain(pause_menu::button_open_party_screen.system()),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::PauseMenu,
+            button_interact::<button::OpenInventoryScreen>
+                .system()
+                .chain(pause_menu::button_open_inventory_screen.system()),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::PauseMenu,
+            button_interact::<button::OpenJournal>
+                .system()
+                .chain(pause_menu::button_open_journal.system()),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::PauseMenu,
+            button_interact::<button::ExitToMainMenu>
+                .system()
+                .chain(pause_menu::button_exit_to_main_menu.system()),
+        )
+        .on_state_exit(
+            Stage::AppState,
+            AppState::PauseMenu,
+            crate::despawn_all::<pause_menu::StateCleanup>.system(),
+        )
+        // Party screen
+        .on_state_enter(
+            Stage::AppState,
+            AppState::PartyScreen,
+            party_screen::setup.system(),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::PartyScreen,
+            button_interact::<button::ClosePartyScreen>
+                .system()
+                .chain(party_screen::button_close_party_screen.system()),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::PartyScreen,
+            button_interact::<button::OpenEquipScreen>
+                .system()
+                .chain(party_screen::button_open_equip_screen.system()),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::PartyScreen,
+            party_screen::close_party_screen.system(),
+        )
+        .on_state_exit(
+            Stage::AppState,
+            AppState::PartyScreen,
+            crate::despawn_all::<party_screen::StateCleanup>.system(),
+        )
+        // Inventory screen
+        .on_state_update(
+            Stage::AppState,
+            AppState::InventoryScreen,
+            inventory_screen::refresh_inventory_screen.system(),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::InventoryScreen,
+            button_interact::<button::CloseInventoryScreen>
+                .system()
+                .chain(inventory_screen::button_close_inventory_screen.system()),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::InventoryScreen,
+            button_interact::<button::UseItem>
+                .system()
+                .chain(inventory_screen::button_use_item.system()),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::InventoryScreen,
+            inventory_screen::close_inventory_screen.system(),
+        )
+        .on_state_exit(
+            Stage::AppState,
+            AppState::InventoryScreen,
+            crate::despawn_all::<inventory_screen::StateCleanup>.system(),
+        )
+        // Equip screen
+        .on_state_update(
+            Stage::AppState,
+            AppState::EquipScreen,
+            equip_screen::refresh_equip_screen.system(),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::EquipScreen,
+            button_interact::<button::SelectEquipment>
+                .system()
+                .chain(equip_screen::button_select_equipment.system()),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::EquipScreen,
+            button_interact::<button::ConfirmEquip>
+                .system()
+                .chain(equip_screen::button_confirm_equip.system()),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::EquipScreen,
+            button_interact::<button::Unequip>
+                .system()
+                .chain(equip_screen::button_unequip.system()),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::EquipScreen,
+            button_interact::<button::CloseEquipScreen>
+                .system()
+                .chain(equip_screen::button_close_equip_screen.system()),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::EquipScreen,
+            equip_screen::close_equip_screen.system(),
+        )
+        .on_state_exit(
+            Stage::AppState,
+            AppState::EquipScreen,
+            crate::despawn_all::<equip_screen::StateCleanup>.system(),
+        )
+        // Shop
+        .on_state_enter(
+            Stage::AppState,
+            AppState::Shop,
+            shop_screen::restock_shop.system(),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::Shop,
+            shop_screen::refresh_shop_screen.system(),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::Shop,
+            button_interact::<button::BuyItem>
+                .system()
+                .chain(shop_screen::button_buy_item.system()),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::Shop,
+            button_interact::<button::SellItem>
+                .system()
+                .chain(shop_screen::button_sell_item.system()),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::Shop,
+            button_interact::<button::CloseShop>
+                .system()
+                .chain(shop_screen::button_close_shop.system()),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::Shop,
+            shop_screen::close_shop.system(),
+        )
+        .on_state_exit(
+            Stage::AppState,
+            AppState::Shop,
+            crate::despawn_all::<shop_screen::StateCleanup>.system(),
+        )
+        // Journal
+        .on_state_enter(
+            Stage::AppState,
+            AppState::Journal,
+            journal_screen::setup.system(),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::Journal,
+            button_interact::<button::CloseJournal>
+                .system()
+                .chain(journal_screen::button_close_journal.system()),
+        )
+        .on_state_update(
+            Stage::AppState,
+            AppState::Journal,
+            journal_screen::close_journal.system(),
+        )
+        .on_state_exit(
+            Stage::AppState,
+            AppState::Journal,
+            crate::despawn_all::<journal_screen::StateCleanup>.system(),
         );
     }
 }
@@ -94,3 +341,27 @@ pub fn button_interact<B: Component>(
 
     clicked
 }
+
+/// Spawns a button with a text label, marked with the given button component
+pub fn spawn_button<B: Component>(
+    parent: &mut ChildBuilder,
+    assets: &UiAssets,
+    style: &Style,
+    text_style: &TextStyle,
+    label: &str,
+    marker: B,
+) {
+    parent
+        .spawn(ButtonBundle {
+            material: assets.button_normal.clone(),
+            style: style.clone(),
+            ..Default::default()
+        })
+        .with(marker)
+        .with_children(|button| {
+            button.spawn(TextBundle {
+                text: Text::with_section(label, text_style.clone(), Default::default()),
+                ..Default::default()
+            });
+        });
+}
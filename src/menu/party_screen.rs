@@ -0,0 +1,253 @@
+use bevy::prelude::*;
+
+use crate::inventory::Items;
+use crate::menu::equip_screen::EquipScreen;
+use crate::menu::{button, spawn_button};
+use crate::party::{self, Characters, Party, PartyMember};
+use crate::AppState;
+use crate::UiAssets;
+
+/// Marker for despawning when exiting `AppState::PartyScreen`
+pub struct StateCleanup;
+
+pub fn button_close_party_screen(In(clicked): In<bool>, mut state: ResMut<State<AppState>>) {
+    if clicked {
+        state.set_next(AppState::PauseMenu).unwrap();
+    }
+}
+
+/// Opens the equip screen for the member whose button was clicked
+pub fn button_open_equip_screen(
+    In(clicked): In<bool>,
+    mut commands: Commands,
+    mut state: ResMut<State<AppState>>,
+    buttons: Query<(&Interaction, &button::OpenEquipScreen)>,
+) {
+    if !clicked {
+        return;
+    }
+
+    let member = buttons
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Clicked)
+        .map(|(_, button)| button.0);
+
+    if let Some(member) = member {
+        commands.insert_resource(EquipScreen::new(member));
+        state.set_next(AppState::EquipScreen).unwrap();
+    }
+}
+
+pub fn close_party_screen(mut state: ResMut<State<AppState>>, input: Res<Input<KeyCode>>) {
+    if input.just_pressed(KeyCode::Escape) {
+        state.set_next(AppState::PauseMenu).unwrap();
+    }
+}
+
+/// The lines describing a party member, below its name
+fn member_lines(member: &PartyMember, characters: &Characters, items: &Items) -> Vec<String> {
+    let character = characters.get(&member.name);
+    let stats = member.equipped_stats(character, items);
+    let level = member.level();
+
+    let experience = if level < party::MAX_LEVEL {
+        format!(
+            "Experience: {} / {}",
+            member.experience,
+            party::experience_for_level(level + 1)
+        )
+    } else {
+        format!("Experience: {} (max level)", member.experience)
+    };
+
+    let abilities: Vec<String> = member
+        .abilities(character, items)
+        .into_iter()
+        .map(|a| a.name)
+        .collect();
+
+    let mut lines = vec![
+        experience,
+        format!(
+            "Health {}   Attack {}   Defence {}   Movement {}",
+            stats.max_health, stats.attack, stats.defence, stats.movement
+        ),
+        format!("Abilities: {}", abilities.join(", ")),
+    ];
+
+    for (slot, item) in member.equipment.slots().iter() {
+        lines.push(format!("{}: {}", slot.name(), item.unwrap_or("-")));
+    }
+
+    lines
+}
+
+pub fn setup(
+    mut commands: Commands,
+    assets: Res<UiAssets>,
+    party: Res<Party>,
+    characters: Res<Characters>,
+    items: Res<Items>,
+) {
+    let button_style = Style {
+        size: Size::new(Val::Auto, Val::Auto),
+        margin: Rect::all(Val::Px(5.0)),
+        padding: Rect {
+            left: Val::Px(12.0),
+            right: Val::Px(12.0),
+            top: Val::Px(8.0),
+            bottom: Val::Px(8.0),
+        },
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..Default::default()
+    };
+
+    let button_text_style = TextStyle {
+        font: assets.font_light.clone(),
+        font_size: 18.0,
+        color: Color::WHITE,
+    };
+
+    let name_style = TextStyle {
+        font: assets.font_bold.clone(),
+        font_size: 18.0,
+        color: Color::rgb(0.9, 0.9, 0.95),
+    };
+
+    let line_style = TextStyle {
+        font: assets.font_light.clone(),
+        font_size: 15.0,
+        color: Color::rgb(0.9, 0.9, 0.95),
+    };
+
+    commands
+        // Container
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: assets.transparent.clone(),
+            ..Default::default()
+        })
+        .with(StateCleanup)
+        .with_children(|root| {
+            root
+                // Party panel
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(500.0), Val::Auto),
+                        flex_direction: FlexDirection::ColumnReverse,
+                        justify_content: JustifyContent::FlexStart,
+                        align_items: AlignItems::FlexStart,
+                        padding: Rect::all(Val::Px(8.0)),
+                        ..Default::default()
+                    },
+                    material: assets.menu_panel_background.clone(),
+                    ..Default::default()
+                })
+                .with_children(|panel| {
+                    // Title
+                    panel.spawn(TextBundle {
+                        text: Text::with_section(
+                            "PARTY",
+                            TextStyle {
+                                font: assets.font_bold.clone(),
+                                font_size: 16.0,
+                                color: Color::rgb(0.9, 0.9, 0.95),
+                            },
+                            Default::default(),
+                        ),
+                        ..Default::default()
+                    });
+
+                    for (index, member) in party.members.iter().enumerate() {
+                        panel
+                            // Spacer
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    size: Size::new(Val::Auto, Val::Px(16.0)),
+                                    ..Default::default()
+                                },
+                                material: assets.transparent.clone(),
+                                ..Default::default()
+                            })
+                            // Name, level and equip button
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    size: Size::new(Val::Percent(100.0), Val::Auto),
+                                    justify_content: JustifyContent::SpaceBetween,
+                                    align_items: AlignItems::Center,
+                                    ..Default::default()
+                                },
+                                material: assets.transparent.clone(),
+                                ..Default::default()
+                            })
+                            .with_children(|header| {
+                                header.spawn(TextBundle {
+                                    text: Text::with_section(
+                                        format!("{}   Level {}", member.name, member.level()),
+                                        name_style.clone(),
+                                        Default::default(),
+                                    ),
+                                    ..Default::default()
+                                });
+
+                                spawn_button(
+                                    header,
+                                    &assets,
+                                    &button_style,
+                                    &button_text_style,
+                                    "Equip",
+                                    button::OpenEquipScreen(index),
+                                );
+                            });
+
+                        for line in member_lines(member, &characters, &items) {
+                            panel.spawn(TextBundle {
+                                text: Text::with_section(
+                                    line,
+                                    line_style.clone(),
+                                    Default::default(),
+                                ),
+                                ..Default::default()
+                            });
+                        }
+                    }
+                })
+                // Spacer
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Auto, Val::Px(8.0)),
+                        ..Default::default()
+                    },
+                    material: assets.transparent.clone(),
+                    ..Default::default()
+                })
+                // Button bar under party panel
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(500.0), Val::Auto),
+                        justify_content: JustifyContent::FlexEnd,
+                        ..Default::default()
+                    },
+                    material: assets.menu_panel_background.clone(),
+                    ..Default::default()
+                })
+                .with_children(|button_bar| {
+                    spawn_button(
+                        button_bar,
+                        &assets,
+                        &button_style,
+                        &button_text_style,
+                        "Back",
+                        button::ClosePartyScreen,
+                    );
+                });
+        });
+}
@@ -0,0 +1,170 @@
+use bevy::prelude::*;
+
+use crate::menu::inventory_screen::InventoryScreen;
+use crate::menu::{button, spawn_button};
+use crate::overworld::PausedOverworld;
+use crate::AppState;
+use crate::UiAssets;
+
+/// Marker for despawning when exiting `AppState::PauseMenu`
+pub struct StateCleanup;
+
+pub fn button_resume_game(In(clicked): In<bool>, mut state: ResMut<State<AppState>>) {
+    if clicked {
+        state.set_next(AppState::Overworld).unwrap();
+    }
+}
+
+pub fn button_open_party_screen(In(clicked): In<bool>, mut state: ResMut<State<AppState>>) {
+    if clicked {
+        state.set_next(AppState::PartyScreen).unwrap();
+    }
+}
+
+pub fn button_open_inventory_screen(
+    In(clicked): In<bool>,
+    mut commands: Commands,
+    mut state: ResMut<State<AppState>>,
+) {
+    if clicked {
+        commands.insert_resource(InventoryScreen::default());
+        state.set_next(AppState::InventoryScreen).unwrap();
+    }
+}
+
+pub fn button_open_journal(In(clicked): In<bool>, mut state: ResMut<State<AppState>>) {
+    if clicked {
+        state.set_next(AppState::Journal).unwrap();
+    }
+}
+
+pub fn button_exit_to_main_menu(
+    In(clicked): In<bool>,
+    mut commands: Commands,
+    mut state: ResMut<State<AppState>>,
+) {
+    if clicked {
+        // The game started next shouldn't resume where this one was paused
+        commands.remove_resource::<PausedOverworld>();
+        state.set_next(AppState::MainMenu).unwrap();
+    }
+}
+
+pub fn setup(mut commands: Commands, assets: Res<UiAssets>) {
+    let button_style = Style {
+        size: Size::new(Val::Px(200.0), Val::Auto),
+        margin: Rect::all(Val::Px(5.0)),
+        padding: Rect {
+            left: Val::Px(12.0),
+            right: Val::Px(12.0),
+            top: Val::Px(10.0),
+            bottom: Val::Px(10.0),
+        },
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..Default::default()
+    };
+
+    let button_text_style = TextStyle {
+        font: assets.font_light.clone(),
+        font_size: 18.0,
+        color: Color::WHITE,
+    };
+
+    commands
+        // Container
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: assets.transparent.clone(),
+            ..Default::default()
+        })
+        .with(StateCleanup)
+        .with_children(|root| {
+            root
+                // Pause menu panel
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Auto, Val::Auto),
+                        flex_direction: FlexDirection::ColumnReverse,
+                        justify_content: JustifyContent::FlexStart,
+                        align_items: AlignItems::Center,
+                        padding: Rect::all(Val::Px(8.0)),
+                        ..Default::default()
+                    },
+                    material: assets.menu_panel_background.clone(),
+                    ..Default::default()
+                })
+                .with_children(|menu| {
+                    // Title
+                    menu.spawn(TextBundle {
+                        text: Text::with_section(
+                            "PAUSED",
+                            TextStyle {
+                                font: assets.font_bold.clone(),
+                                font_size: 16.0,
+                                color: Color::rgb(0.9, 0.9, 0.95),
+                            },
+                            Default::default(),
+                        ),
+                        ..Default::default()
+                    })
+                    // Spacer
+                    .spawn(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Auto, Val::Px(16.0)),
+                            ..Default::default()
+                        },
+                        material: assets.transparent.clone(),
+                        ..Default::default()
+                    });
+
+                    spawn_button(
+                        menu,
+                        &assets,
+                        &button_style,
+                        &button_text_style,
+                        "Resume",
+                        button::ResumeGame,
+                    );
+                    spawn_button(
+                        menu,
+                        &assets,
+                        &button_style,
+                        &button_text_style,
+                        "Party",
+                        button::OpenPartyScreen,
+                    );
+                    spawn_button(
+                        menu,
+                        &assets,
+                        &button_style,
+                        &button_text_style,
+                        "Inventory",
+                        button::OpenInventoryScreen,
+                    );
+                    spawn_button(
+                        menu,
+                        &assets,
+                        &button_style,
+                        &button_text_style,
+                        "Journal",
+                        button::OpenJournal,
+                    );
+                    spawn_button(
+                        menu,
+                        &assets,
+                        &button_style,
+                        &button_text_style,
+                        "Main Menu",
+                        button::ExitToMainMenu,
+                    );
+                });
+        });
+}
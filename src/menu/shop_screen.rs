@@ -0,0 +1,353 @@
+//! Buying and selling items in a shop, opened from the dialog of an NPC that runs one
+
+use bevy::prelude::*;
+
+use crate::inventory::{Inventory, Items};
+use crate::menu::{button, spawn_button};
+use crate::shop::{self, Shop, ShopStocks, Wallet};
+use crate::world_time::WorldTime;
+use crate::AppState;
+use crate::UiAssets;
+
+/// Marker for despawning when exiting `AppState::Shop`
+pub struct StateCleanup;
+
+/// The shop being visited, inserted before entering `AppState::Shop`
+pub struct ShopScreen {
+    shop_id: String,
+    shop: Shop,
+    /// Whether the screen has to be spawned again to show a change
+    dirty: bool,
+}
+
+impl ShopScreen {
+    pub fn new(shop_id: &str) -> Self {
+        ShopScreen {
+            shop_id: shop_id.to_string(),
+            shop: Shop::load(shop_id),
+            dirty: true,
+        }
+    }
+}
+
+pub fn button_close_shop(In(clicked): In<bool>, mut state: ResMut<State<AppState>>) {
+    if clicked {
+        state.set_next(AppState::Dialog).unwrap();
+    }
+}
+
+pub fn close_shop(mut state: ResMut<State<AppState>>, input: Res<Input<KeyCode>>) {
+    if input.just_pressed(KeyCode::Escape) {
+        state.set_next(AppState::Dialog).unwrap();
+    }
+}
+
+/// Brings back the stock that was sold out before the shop's latest restock
+pub fn restock_shop(screen: Res<ShopScreen>, time: Res<WorldTime>, mut stocks: ResMut<ShopStocks>) {
+    stocks.restock(&screen.shop_id, &screen.shop, &time);
+}
+
+pub fn button_buy_item(
+    In(clicked): In<bool>,
+    mut screen: ResMut<ShopScreen>,
+    items: Res<Items>,
+    mut wallet: ResMut<Wallet>,
+    mut inventory: ResMut<Inventory>,
+    mut stocks: ResMut<ShopStocks>,
+    buttons: Query<(&Interaction, &button::BuyItem)>,
+) {
+    if !clicked {
+        return;
+    }
+
+    let entry = buttons
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Clicked)
+        .map(|(_, button)| button.0);
+
+    if let Some(entry) = entry {
+        let bought = shop::buy(
+            &screen.shop_id,
+            &screen.shop,
+            entry,
+            &items,
+            &mut wallet,
+            &mut inventory,
+            &mut stocks,
+        );
+        if !bought {
+            warn!("Could not buy entry {} of {}", entry, screen.shop_id);
+        }
+
+        screen.dirty = true;
+    }
+}
+
+pub fn button_sell_item(
+    In(clicked): In<bool>,
+    mut screen: ResMut<ShopScreen>,
+    items: Res<Items>,
+    mut wallet: ResMut<Wallet>,
+    mut inventory: ResMut<Inventory>,
+    buttons: Query<(&Interaction, &button::SellItem)>,
+) {
+    if !clicked {
+        return;
+    }
+
+    let item = buttons
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Clicked)
+        .map(|(_, button)| button.0.clone());
+
+    if let Some(item) = item {
+        if !shop::sell(&screen.shop, &item, &items, &mut wallet, &mut inventory) {
+            warn!("Could not sell {}", item);
+        }
+
+        screen.dirty = true;
+    }
+}
+
+/// Spawns the screen again whenever something on it changed
+#[allow(clippy::too_many_arguments)]
+pub fn refresh_shop_screen(
+    mut commands: Commands,
+    mut screen: ResMut<ShopScreen>,
+    assets: Res<UiAssets>,
+    items: Res<Items>,
+    wallet: Res<Wallet>,
+    inventory: Res<Inventory>,
+    stocks: Res<ShopStocks>,
+    spawned: Query<Entity, With<StateCleanup>>,
+) {
+    if !screen.dirty {
+        return;
+    }
+    screen.dirty = false;
+
+    for entity in spawned.iter() {
+        commands.despawn_recursive(entity);
+    }
+
+    let shop = &screen.shop;
+
+    // Every item the shop would buy, once even if it takes several stacks
+    let mut sellable: Vec<&str> = Vec::new();
+    for stack in inventory.stacks() {
+        if shop.sell_price(items.get(&stack.item)) > 0 && !sellable.contains(&stack.item.as_str()) {
+            sellable.push(&stack.item);
+        }
+    }
+
+    let button_style = Style {
+        size: Size::new(Val::Auto, Val::Auto),
+        margin: Rect::all(Val::Px(5.0)),
+        padding: Rect {
+            left: Val::Px(12.0),
+            right: Val::Px(12.0),
+            top: Val::Px(8.0),
+            bottom: Val::Px(8.0),
+        },
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..Default::default()
+    };
+
+    let row_style = Style {
+        size: Size::new(Val::Percent(100.0), Val::Auto),
+        justify_content: JustifyContent::SpaceBetween,
+        align_items: AlignItems::Center,
+        ..Default::default()
+    };
+
+    let spacer = || NodeBundle {
+        style: Style {
+            size: Size::new(Val::Auto, Val::Px(16.0)),
+            ..Default::default()
+        },
+        material: assets.transparent.clone(),
+        ..Default::default()
+    };
+
+    let button_text_style = TextStyle {
+        font: assets.font_light.clone(),
+        font_size: 18.0,
+        color: Color::WHITE,
+    };
+
+    let name_style = TextStyle {
+        font: assets.font_bold.clone(),
+        font_size: 18.0,
+        color: Color::rgb(0.9, 0.9, 0.95),
+    };
+
+    let line_style = TextStyle {
+        font: assets.font_light.clone(),
+        font_size: 15.0,
+        color: Color::rgb(0.9, 0.9, 0.95),
+    };
+
+    let text = |value: String, style: &TextStyle| TextBundle {
+        text: Text::with_section(value, style.clone(), Default::default()),
+        ..Default::default()
+    };
+
+    commands
+        // Container
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: assets.transparent.clone(),
+            ..Default::default()
+        })
+        .with(StateCleanup)
+        .with_children(|root| {
+            root
+                // Shop panel
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(560.0), Val::Auto),
+                        flex_direction: FlexDirection::ColumnReverse,
+                        justify_content: JustifyContent::FlexStart,
+                        align_items: AlignItems::FlexStart,
+                        padding: Rect::all(Val::Px(8.0)),
+                        ..Default::default()
+                    },
+                    material: assets.menu_panel_background.clone(),
+                    ..Default::default()
+                })
+                .with_children(|panel| {
+                    // Title
+                    panel
+                        .spawn(TextBundle {
+                            text: Text::with_section(
+                                shop.name.to_uppercase(),
+                                TextStyle {
+                                    font: assets.font_bold.clone(),
+                                    font_size: 16.0,
+                                    color: Color::rgb(0.9, 0.9, 0.95),
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .spawn(text(format!("Gold: {}", wallet.0), &line_style))
+                        .spawn(spacer())
+                        .spawn(text("Buy".to_string(), &name_style));
+
+                    for (index, entry) in shop.stock.iter().enumerate() {
+                        let price = shop.buy_price(items.get(&entry.item));
+                        let remaining = stocks.remaining(&screen.shop_id, entry);
+                        let stock = match remaining {
+                            Some(remaining) => format!("   {} left", remaining),
+                            None => String::new(),
+                        };
+
+                        panel
+                            .spawn(NodeBundle {
+                                style: row_style.clone(),
+                                material: assets.transparent.clone(),
+                                ..Default::default()
+                            })
+                            .with_children(|row| {
+                                row.spawn(text(
+                                    format!(
+                                        "{}   {} gold   owned {}{}",
+                                        entry.item,
+                                        price,
+                                        inventory.count(&entry.item),
+                                        stock
+                                    ),
+                                    &line_style,
+                                ));
+
+                                if remaining == Some(0) {
+                                    row.spawn(text("Sold out".to_string(), &line_style));
+                                } else if wallet.0 >= price {
+                                    spawn_button(
+                                        row,
+                                        &assets,
+                                        &button_style,
+                                        &button_text_style,
+                                        "Buy",
+                                        button::BuyItem(index),
+                                    );
+                                }
+                            });
+                    }
+
+                    panel
+                        .spawn(spacer())
+                        .spawn(text("Sell".to_string(), &name_style));
+
+                    if sellable.is_empty() {
+                        panel.spawn(text("Nothing to sell".to_string(), &line_style));
+                    }
+
+                    for item in sellable {
+                        panel
+                            .spawn(NodeBundle {
+                                style: row_style.clone(),
+                                material: assets.transparent.clone(),
+                                ..Default::default()
+                            })
+                            .with_children(|row| {
+                                row.spawn(text(
+                                    format!(
+                                        "{}   {} gold   owned {}",
+                                        item,
+                                        shop.sell_price(items.get(item)),
+                                        inventory.count(item)
+                                    ),
+                                    &line_style,
+                                ));
+
+                                spawn_button(
+                                    row,
+                                    &assets,
+                                    &button_style,
+                                    &button_text_style,
+                                    "Sell",
+                                    button::SellItem(item.to_string()),
+                                );
+                            });
+                    }
+                })
+                // Spacer
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Auto, Val::Px(8.0)),
+                        ..Default::default()
+                    },
+                    material: assets.transparent.clone(),
+                    ..Default::default()
+                })
+                // Button bar under shop panel
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(560.0), Val::Auto),
+                        justify_content: JustifyContent::FlexEnd,
+                        ..Default::default()
+                    },
+                    material: assets.menu_panel_background.clone(),
+                    ..Default::default()
+                })
+                .with_children(|button_bar| {
+                    spawn_button(
+                        button_bar,
+                        &assets,
+                        &button_style,
+                        &button_text_style,
+                        "Back",
+                        button::CloseShop,
+                    );
+                });
+        });
+}
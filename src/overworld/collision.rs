@@ -0,0 +1,288 @@
+//! A simple kinematic character controller for walking around the overworld
+//!
+//! Anything solid gets a [`Collider`], a box around its position that never moves. The
+//! player moves one axis at a time, so walking into a wall at an angle slides along it.
+//! Colliders low enough to step onto are climbed instead, gravity keeps the player on the
+//! ground, and the level's bounds keep the player from walking off the map.
+
+use bevy::prelude::*;
+
+/// How fast falling speeds up, in world units per second squared
+const GRAVITY: f32 = 20.0;
+
+/// Highest ledge the player walks onto without jumping, in world units
+const STEP_HEIGHT: f32 = 0.35;
+
+/// How far boxes may overlap while only touching, to allow for rounding errors
+const CONTACT_TOLERANCE: f32 = 0.001;
+
+/// An axis-aligned box around the entity's position that the player can't walk through
+#[derive(Debug, Clone, Copy)]
+pub struct Collider {
+    pub half_extents: Vec3,
+}
+
+impl Collider {
+    pub fn new(width: f32, height: f32, depth: f32) -> Self {
+        Collider {
+            half_extents: Vec3::new(width, height, depth) / 2.,
+        }
+    }
+
+    pub fn aabb(&self, center: Vec3) -> Aabb {
+        Aabb {
+            min: center - self.half_extents,
+            max: center + self.half_extents,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// Whether the boxes overlap, boxes that only touch don't
+    fn overlaps(&self, other: &Aabb) -> bool {
+        let min = self.min + Vec3::splat(CONTACT_TOLERANCE);
+        let max = self.max - Vec3::splat(CONTACT_TOLERANCE);
+
+        min.x < other.max.x
+            && max.x > other.min.x
+            && min.y < other.max.y
+            && max.y > other.min.y
+            && min.z < other.max.z
+            && max.z > other.min.z
+    }
+}
+
+/// Movement state of an entity moved by [`move_and_slide`]
+#[derive(Debug, Default)]
+pub struct KinematicBody {
+    /// Upwards speed, negative while falling
+    pub vertical_speed: f32,
+    /// Whether the body stood on something after its last move
+    pub grounded: bool,
+}
+
+/// Area of the ground the player can walk in, from the lowest to the highest `(x, z)`
+#[derive(Debug, Clone, Copy)]
+pub struct WorldBounds {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+/// Moves a box horizontally by the displacement and vertically by its speed, without
+/// going through colliders or out of bounds
+///
+/// Returns the new position of the box.
+pub fn move_and_slide(
+    body: &mut KinematicBody,
+    collider: &Collider,
+    position: Vec3,
+    displacement: Vec3,
+    delta_seconds: f32,
+    colliders: &[Aabb],
+    bounds: WorldBounds,
+) -> Vec3 {
+    let half = collider.half_extents;
+    let blocked = |position: Vec3| {
+        let aabb = collider.aabb(position);
+        colliders.iter().find(|other| aabb.overlaps(other)).copied()
+    };
+
+    let mut position = position;
+
+    // Move along each axis on its own, to slide along what blocks the other
+    for axis in &[Vec3::X, Vec3::Z] {
+        let step = *axis * displacement.dot(*axis);
+        if step == Vec3::ZERO {
+            continue;
+        }
+
+        let moved = position + step;
+        let obstacle = match blocked(moved) {
+            Some(obstacle) => obstacle,
+            None => {
+                position = moved;
+                continue;
+            }
+        };
+
+        // Climb onto low obstacles, if there is room on top
+        let feet = moved.y - half.y;
+        let climbed = Vec3::new(moved.x, obstacle.max.y + half.y, moved.z);
+        if body.grounded && obstacle.max.y - feet <= STEP_HEIGHT && blocked(climbed).is_none() {
+            position = climbed;
+            continue;
+        }
+
+        // Stop against the obstacle
+        if axis.x > 0. {
+            position.x = if step.x > 0. {
+                obstacle.min.x - half.x
+            } else {
+                obstacle.max.x + half.x
+            };
+        } else {
+            position.z = if step.z > 0. {
+                obstacle.min.z - half.z
+            } else {
+                obstacle.max.z + half.z
+            };
+        }
+    }
+
+    position.x = position
+        .x
+        .max(bounds.min.x + half.x)
+        .min(bounds.max.x - half.x);
+    position.z = position
+        .z
+        .max(bounds.min.y + half.z)
+        .min(bounds.max.y - half.z);
+
+    body.vertical_speed -= GRAVITY * delta_seconds;
+    let moved = position + Vec3::Y * body.vertical_speed * delta_seconds;
+    let falling = body.vertical_speed <= 0.;
+    body.grounded = false;
+
+    // Look a bit further down when falling, so standing still stays on the ground even
+    // when the frame is too short to fall into it
+    let probe = if falling {
+        moved - Vec3::Y * 2. * CONTACT_TOLERANCE
+    } else {
+        moved
+    };
+
+    position = match blocked(probe) {
+        Some(obstacle) if falling => {
+            body.grounded = true;
+            body.vertical_speed = 0.;
+            Vec3::new(moved.x, obstacle.max.y + half.y, moved.z)
+        }
+        Some(obstacle) => {
+            // Bumped into a ceiling
+            body.vertical_speed = 0.;
+            Vec3::new(moved.x, obstacle.min.y - half.y, moved.z)
+        }
+        None => moved,
+    };
+    position
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player() -> Collider {
+        Collider::new(1., 2., 1.)
+    }
+
+    fn ground() -> Aabb {
+        Collider::new(20., 0., 20.).aabb(Vec3::ZERO)
+    }
+
+    fn bounds() -> WorldBounds {
+        WorldBounds {
+            min: Vec2::new(-10., -10.),
+            max: Vec2::new(10., 10.),
+        }
+    }
+
+    fn grounded() -> KinematicBody {
+        KinematicBody {
+            vertical_speed: 0.,
+            grounded: true,
+        }
+    }
+
+    #[test]
+    fn walls_are_slid_along() {
+        let wall = Collider::new(1., 2., 10.).aabb(Vec3::new(2., 1., 0.));
+        let mut body = grounded();
+
+        let position = move_and_slide(
+            &mut body,
+            &player(),
+            Vec3::new(0., 1., 0.),
+            Vec3::new(2., 0., 1.),
+            0.01,
+            &[ground(), wall],
+            bounds(),
+        );
+
+        assert_eq!(position, Vec3::new(1., 1., 1.));
+        assert!(body.grounded);
+    }
+
+    #[test]
+    fn low_steps_are_climbed() {
+        let step = Collider::new(2., 0.3, 2.).aabb(Vec3::new(2., 0.15, 0.));
+        let ledge = Collider::new(2., 1., 2.).aabb(Vec3::new(-2., 0.5, 0.));
+        let colliders = [ground(), step, ledge];
+
+        let mut body = grounded();
+        let position = move_and_slide(
+            &mut body,
+            &player(),
+            Vec3::new(0., 1., 0.),
+            Vec3::new(1.5, 0., 0.),
+            0.01,
+            &colliders,
+            bounds(),
+        );
+        assert_eq!(position, Vec3::new(1.5, 1.3, 0.));
+
+        let mut body = grounded();
+        let position = move_and_slide(
+            &mut body,
+            &player(),
+            Vec3::new(0., 1., 0.),
+            Vec3::new(-1.5, 0., 0.),
+            0.01,
+            &colliders,
+            bounds(),
+        );
+        assert_eq!(position, Vec3::new(-0.5, 1., 0.));
+    }
+
+    #[test]
+    fn falling_stops_on_the_ground() {
+        let mut body = KinematicBody::default();
+        let mut position = Vec3::new(0., 3., 0.);
+
+        for _ in 0..100 {
+            position = move_and_slide(
+                &mut body,
+                &player(),
+                position,
+                Vec3::ZERO,
+                0.02,
+                &[ground()],
+                bounds(),
+            );
+        }
+
+        assert_eq!(position, Vec3::new(0., 1., 0.));
+        assert!(body.grounded);
+    }
+
+    #[test]
+    fn bounds_keep_the_player_on_the_map() {
+        let mut body = grounded();
+        let position = move_and_slide(
+            &mut body,
+            &player(),
+            Vec3::new(9., 1., 0.),
+            Vec3::new(3., 0., -12.),
+            0.01,
+            &[ground()],
+            bounds(),
+        );
+
+        assert_eq!(position, Vec3::new(9.5, 1., -9.5));
+    }
+}
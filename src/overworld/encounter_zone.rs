@@ -0,0 +1,290 @@
+//! Areas of the overworld where walking around can start random battles
+//!
+//! Every [`EncounterZone`] has an [`EncounterTable`], stored in
+//! `assets/data/encounter_tables`. Each step walked inside a zone has a chance to start
+//! one of the table's encounters, picked by weight at a random level. After a random
+//! battle, no new one can start until the player has walked the table's cooldown.
+
+use bevy::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::battle::encounter::Encounter;
+use crate::battle::BattleResource;
+use crate::overworld::player::Player;
+use crate::overworld::PausedOverworld;
+use crate::rng::{GameRng, RngStream};
+use crate::AppState;
+
+/// Distance the player has to walk to take one step, in world units
+const STEP_LENGTH: f32 = 1.0;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EncounterTable {
+    /// Chance of a battle starting with every step walked in the zone
+    pub chance_per_step: f32,
+    /// How many steps have to be walked after a battle before the next one can start
+    pub cooldown_steps: u32,
+    pub entries: Vec<EncounterEntry>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EncounterEntry {
+    /// Name of the encounter in `assets/data/encounters`
+    pub encounter: String,
+    /// How likely this entry is picked, relative to the other entries
+    pub weight: u32,
+    /// Lowest and highest level the encounter can have
+    pub levels: (u32, u32),
+}
+
+/// An encounter picked from a table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RolledEncounter {
+    pub encounter: String,
+    pub level: u32,
+}
+
+impl EncounterTable {
+    /// Loads an encounter table from `assets/data/encounter_tables`
+    pub fn load(name: &str) -> Self {
+        crate::data::load(&format!("encounter_tables/{}.ron", name))
+    }
+
+    /// Rolls whether a step starts a battle, and which encounter it is
+    pub fn roll(&self, rng: &mut impl Rng) -> Option<RolledEncounter> {
+        if rng.gen::<f32>() >= self.chance_per_step {
+            return None;
+        }
+
+        let total_weight: u32 = self.entries.iter().map(|entry| entry.weight).sum();
+        if total_weight == 0 {
+            return None;
+        }
+
+        let mut roll = rng.gen_range(0..total_weight);
+        let entry = self.entries.iter().find(|entry| {
+            if roll < entry.weight {
+                true
+            } else {
+                roll -= entry.weight;
+                false
+            }
+        })?;
+
+        let (min_level, max_level) = entry.levels;
+        Some(RolledEncounter {
+            encounter: entry.encounter.clone(),
+            level: rng.gen_range(min_level..=max_level.max(min_level)),
+        })
+    }
+}
+
+/// A box around the entity's position where random battles can start
+pub struct EncounterZone {
+    pub half_extents: Vec3,
+    pub table: EncounterTable,
+}
+
+impl EncounterZone {
+    pub fn contains(&self, center: Vec3, point: Vec3) -> bool {
+        let offset = (point - center).abs();
+
+        offset.x <= self.half_extents.x
+            && offset.y <= self.half_extents.y
+            && offset.z <= self.half_extents.z
+    }
+}
+
+/// Tracks how far the player walked, to know when a step is taken
+#[derive(Debug, Default)]
+pub struct EncounterSteps {
+    last_position: Option<Vec3>,
+    /// Distance walked since the last step
+    distance: f32,
+    /// Steps left before random battles can start again
+    cooldown: u32,
+}
+
+impl EncounterSteps {
+    /// Forgets where the player was, so the next position isn't counted as walking
+    pub fn reset(&mut self) {
+        self.last_position = None;
+        self.distance = 0.0;
+    }
+
+    /// Records where the player is, returning whether that completed a step
+    ///
+    /// Only the distance along the ground counts, so jumping isn't walking.
+    fn walk_to(&mut self, position: Vec3) -> bool {
+        if let Some(last_position) = self.last_position.replace(position) {
+            let offset = position - last_position;
+            self.distance += Vec2::new(offset.x, offset.z).length();
+        }
+
+        if self.distance < STEP_LENGTH {
+            return false;
+        }
+
+        self.distance -= STEP_LENGTH;
+        true
+    }
+}
+
+/// Forgets where the player was, so entering the overworld isn't counted as walking
+///
+/// Resuming from the pause menu keeps the steps, as the player is put back where they were.
+pub fn reset_encounter_steps(
+    mut steps: ResMut<EncounterSteps>,
+    paused: Option<Res<PausedOverworld>>,
+) {
+    if paused.is_none() {
+        steps.reset();
+    }
+}
+
+/// Starts random battles while the player walks through encounter zones
+pub fn random_encounters(
+    mut commands: Commands,
+    mut state: ResMut<State<AppState>>,
+    mut rng: ResMut<GameRng>,
+    mut steps: ResMut<EncounterSteps>,
+    players: Query<&Transform, With<Player>>,
+    zones: Query<(&EncounterZone, &GlobalTransform)>,
+) {
+    let position = match players.iter().next() {
+        Some(transform) => transform.translation,
+        None => return,
+    };
+
+    if !steps.walk_to(position) {
+        return;
+    }
+
+    if steps.cooldown > 0 {
+        steps.cooldown -= 1;
+        return;
+    }
+
+    let zone = zones
+        .iter()
+        .find(|(zone, transform)| zone.contains(transform.translation, position));
+
+    let table = match zone {
+        Some((zone, _)) => &zone.table,
+        None => return,
+    };
+
+    if let Some(rolled) = table.roll(rng.stream(RngStream::Encounters)) {
+        info!(
+            "Random encounter: {} at level {}",
+            rolled.encounter, rolled.level
+        );
+
+        steps.cooldown = table.cooldown_steps;
+
+        commands.insert_resource(BattleResource {
+            encounter: Encounter::load(&rolled.encounter).at_level(rolled.level),
+            starter_id: None,
+        });
+        state.set_next(AppState::Battle).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    fn test_table(chance_per_step: f32) -> EncounterTable {
+        EncounterTable {
+            chance_per_step,
+            cooldown_steps: 5,
+            entries: vec![
+                EncounterEntry {
+                    encounter: "common".to_string(),
+                    weight: 3,
+                    levels: (1, 2),
+                },
+                EncounterEntry {
+                    encounter: "never".to_string(),
+                    weight: 0,
+                    levels: (1, 1),
+                },
+                EncounterEntry {
+                    encounter: "rare".to_string(),
+                    weight: 1,
+                    levels: (4, 6),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn encounter_tables_are_valid() {
+        let table = EncounterTable::load("bevytown_grass");
+
+        for entry in &table.entries {
+            assert!(entry.levels.0 <= entry.levels.1);
+            assert!(!Encounter::load(&entry.encounter).enemies.is_empty());
+        }
+    }
+
+    #[test]
+    fn rolls_respect_weights_and_levels() {
+        let table = test_table(1.0);
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+
+        let rolls: Vec<RolledEncounter> = (0..400).filter_map(|_| table.roll(&mut rng)).collect();
+        let common = rolls.iter().filter(|r| r.encounter == "common").count();
+        let rare = rolls.iter().filter(|r| r.encounter == "rare").count();
+
+        assert_eq!(rolls.len(), 400);
+        assert_eq!(common + rare, 400);
+        assert!(common > rare * 2);
+        for roll in rolls {
+            match roll.encounter.as_str() {
+                "common" => assert!((1..=2).contains(&roll.level)),
+                _ => assert!((4..=6).contains(&roll.level)),
+            }
+        }
+    }
+
+    #[test]
+    fn rolls_are_deterministic() {
+        let table = test_table(0.3);
+        let roll_all = |seed| {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            (0..50).map(|_| table.roll(&mut rng)).collect::<Vec<_>>()
+        };
+
+        assert_eq!(roll_all(3), roll_all(3));
+        assert!(roll_all(3).iter().any(Option::is_none));
+        assert!(test_table(0.0)
+            .roll(&mut ChaCha8Rng::seed_from_u64(3))
+            .is_none());
+    }
+
+    #[test]
+    fn steps_are_counted_by_distance() {
+        let mut steps = EncounterSteps::default();
+
+        assert!(!steps.walk_to(Vec3::ZERO));
+        assert!(!steps.walk_to(Vec3::new(0.6, 0.0, 0.0)));
+        assert!(steps.walk_to(Vec3::new(1.2, 0.0, 0.0)));
+        assert!(!steps.walk_to(Vec3::new(1.2, 0.0, 0.5)));
+    }
+
+    #[test]
+    fn jumping_in_place_takes_no_steps() {
+        let mut steps = EncounterSteps::default();
+
+        steps.walk_to(Vec3::ZERO);
+        for _ in 0..3 {
+            assert!(!steps.walk_to(Vec3::new(0.0, 1.5, 0.0)));
+            assert!(!steps.walk_to(Vec3::ZERO));
+        }
+    }
+}
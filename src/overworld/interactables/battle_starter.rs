@@ -1,15 +1,41 @@
+use std::collections::BTreeSet;
+
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
+use crate::battle::encounter::Encounter;
+use crate::battle::BattleResource;
 use crate::AppState;
 
 #[derive(Clone)]
-pub struct BattleStarter;
+pub struct BattleStarter {
+    /// Identifies this battle starter in the world, so it can be removed once defeated
+    pub id: String,
+    pub encounter: Encounter,
+}
+
+/// IDs of the battle starters the player has won against
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DefeatedBattleStarters(pub BTreeSet<String>);
+
+pub fn start_battle(commands: &mut Commands, state: &mut State<AppState>, battle: &BattleStarter) {
+    commands.insert_resource(BattleResource {
+        encounter: battle.encounter.clone(),
+        starter_id: Some(battle.id.clone()),
+    });
+
+    state.set_next(AppState::Battle).unwrap();
+}
 
-pub fn interactable_start_battle(
-    In(interactable): In<Option<BattleStarter>>,
-    mut state: ResMut<State<AppState>>,
+/// Removes the battle starters that have already been defeated, so they can't be fought again
+pub fn remove_defeated_battle_starters(
+    mut commands: Commands,
+    defeated: Res<DefeatedBattleStarters>,
+    starters: Query<(Entity, &BattleStarter)>,
 ) {
-    if interactable.is_some() {
-        state.set_next(AppState::Battle).unwrap();
+    for (entity, starter) in starters.iter() {
+        if defeated.0.contains(&starter.id) {
+            commands.despawn_recursive(entity);
+        }
     }
 }
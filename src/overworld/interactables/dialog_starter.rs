@@ -1,25 +1,33 @@
 use bevy::prelude::*;
 
 use crate::dialog::DialogResource;
+use crate::quest::QuestEvent;
 use crate::AppState;
 
 #[derive(Clone)]
 pub struct DialogStarter {
     pub npc_name: String,
     pub sprite: Handle<ColorMaterial>,
+    /// ID of the shop in `assets/data/shops` the NPC runs, if any
+    pub shop: Option<String>,
 }
 
-pub fn interactable_start_dialog(
-    In(interactable): In<Option<DialogStarter>>,
-    mut commands: Commands,
-    mut state: ResMut<State<AppState>>,
+/// Starts talking to the NPC
+///
+/// Counts as talking to it for quests. Coming back to the dialog from the NPC's shop
+/// doesn't, so this is only done when the dialog starts from the overworld.
+pub fn start_dialog(
+    commands: &mut Commands,
+    state: &mut State<AppState>,
+    quest_events: &mut EventWriter<QuestEvent>,
+    dialog: &DialogStarter,
 ) {
-    if let Some(dialog) = interactable {
-        commands.insert_resource(DialogResource {
-            npc_name: dialog.npc_name,
-            sprite: dialog.sprite,
-        });
+    quest_events.send(QuestEvent::TalkedTo(dialog.npc_name.clone()));
+    commands.insert_resource(DialogResource {
+        npc_name: dialog.npc_name.clone(),
+        sprite: dialog.sprite.clone(),
+        shop: dialog.shop.clone(),
+    });
 
-        state.set_next(AppState::Dialog).unwrap();
-    }
+    state.set_next(AppState::Dialog).unwrap();
 }
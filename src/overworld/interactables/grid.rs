@@ -0,0 +1,141 @@
+//! A spatial hash of the interactables in the level, so finding the one next to the
+//! player only looks at the few entities around it
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::overworld::interactables::Interactable;
+
+/// Width and depth of a grid cell, in world units
+const CELL_SIZE: f32 = 4.0;
+
+/// Cell the position is in, from its `x` and `z`
+fn cell(position: Vec3) -> (i32, i32) {
+    (
+        (position.x / CELL_SIZE).floor() as i32,
+        (position.z / CELL_SIZE).floor() as i32,
+    )
+}
+
+/// Positions of all interactable entities, bucketed into cells on the ground
+#[derive(Debug, Default)]
+pub struct InteractableGrid {
+    cells: HashMap<(i32, i32), Vec<Entity>>,
+    positions: HashMap<Entity, Vec3>,
+}
+
+impl InteractableGrid {
+    /// Adds the entity at the position, or moves it there if it is already in the grid
+    pub fn insert(&mut self, entity: Entity, position: Vec3) {
+        if let Some(previous) = self.positions.insert(entity, position) {
+            if cell(previous) == cell(position) {
+                return;
+            }
+            self.remove_from_cell(entity, previous);
+        }
+
+        self.cells.entry(cell(position)).or_default().push(entity);
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        if let Some(position) = self.positions.remove(&entity) {
+            self.remove_from_cell(entity, position);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.positions.clear();
+    }
+
+    fn remove_from_cell(&mut self, entity: Entity, position: Vec3) {
+        let key = cell(position);
+        if let Some(entities) = self.cells.get_mut(&key) {
+            entities.retain(|&other| other != entity);
+            if entities.is_empty() {
+                self.cells.remove(&key);
+            }
+        }
+    }
+
+    /// Finds the entity closest to the position, if any is closer than the radius
+    pub fn nearest(&self, position: Vec3, radius: f32) -> Option<Entity> {
+        let reach = Vec3::new(radius, 0., radius);
+        let (min_x, min_z) = cell(position - reach);
+        let (max_x, max_z) = cell(position + reach);
+
+        let mut nearest = None;
+        let mut nearest_distance = radius;
+
+        for x in min_x..=max_x {
+            for z in min_z..=max_z {
+                for &entity in self.cells.get(&(x, z)).into_iter().flatten() {
+                    let distance = (self.positions[&entity] - position).length();
+                    if distance < nearest_distance {
+                        nearest = Some(entity);
+                        nearest_distance = distance;
+                    }
+                }
+            }
+        }
+
+        nearest
+    }
+}
+
+/// Adds interactables to the grid when spawned, moves them when their transform changes,
+/// and removes them once despawned
+pub fn track_interactables(
+    mut grid: ResMut<InteractableGrid>,
+    interactables: Query<(Entity, &Transform), (With<Interactable>, Changed<Transform>)>,
+    removed: RemovedComponents<Interactable>,
+) {
+    for entity in removed.iter() {
+        grid.remove(entity);
+    }
+
+    for (entity, transform) in interactables.iter() {
+        grid.insert(entity, transform.translation);
+    }
+}
+
+/// Empties the grid when the interactables are despawned by leaving the overworld
+pub fn clear_interactables(mut grid: ResMut<InteractableGrid>) {
+    grid.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_entity_within_radius_is_found() {
+        let mut grid = InteractableGrid::default();
+        let (near, far, other) = (Entity::new(0), Entity::new(1), Entity::new(2));
+
+        grid.insert(near, Vec3::new(1., 0., 0.5));
+        grid.insert(far, Vec3::new(-1.5, 0., 0.));
+        grid.insert(other, Vec3::new(40., 0., 40.));
+
+        // Neighbours across cell borders are found
+        assert_eq!(grid.nearest(Vec3::new(0.2, 0., 0.), 2.), Some(near));
+        assert_eq!(grid.nearest(Vec3::new(-1., 0., 0.), 2.), Some(far));
+        assert_eq!(grid.nearest(Vec3::new(20., 0., 20.), 2.), None);
+    }
+
+    #[test]
+    fn moved_and_removed_entities_are_updated() {
+        let mut grid = InteractableGrid::default();
+        let entity = Entity::new(0);
+
+        grid.insert(entity, Vec3::ZERO);
+        grid.insert(entity, Vec3::new(10., 0., 10.));
+        assert_eq!(grid.nearest(Vec3::ZERO, 2.), None);
+        assert_eq!(grid.nearest(Vec3::new(10., 0., 9.), 2.), Some(entity));
+
+        grid.remove(entity);
+        assert_eq!(grid.nearest(Vec3::new(10., 0., 9.), 2.), None);
+        assert!(grid.cells.is_empty());
+    }
+}
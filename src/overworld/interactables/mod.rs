@@ -1,31 +1,48 @@
-use bevy::{ecs::component::Component, prelude::*};
+use bevy::prelude::*;
 
+use self::{battle_starter::BattleStarter, dialog_starter::DialogStarter, grid::InteractableGrid};
 use crate::overworld::player::Player;
+use crate::quest::QuestEvent;
 use crate::user_config::KeyBinds;
+use crate::AppState;
 
 pub mod battle_starter;
 pub mod dialog_starter;
+pub mod grid;
 
-pub fn interactable_interact<B: Component + Clone>(
-    player: Query<&Transform, With<Player>>,
-    interactables: Query<(&Transform, &B)>,
+/// How close the player has to be to an interactable to interact with it, in world units
+const INTERACT_RADIUS: f32 = 2.0;
+
+/// Marker for entities the player can interact with, tracked in the [`InteractableGrid`]
+pub struct Interactable;
+
+/// Starts a battle or a dialog with the nearest interactable when the interact key is
+/// pressed, depending on what kind of interactable it is
+#[allow(clippy::too_many_arguments)]
+pub fn interactable_interact(
+    mut commands: Commands,
+    mut state: ResMut<State<AppState>>,
+    mut quest_events: EventWriter<QuestEvent>,
     input: Res<Input<KeyCode>>,
     keybinds: Res<KeyBinds>,
-) -> Option<B> {
+    grid: Res<InteractableGrid>,
+    player: Query<&Transform, With<Player>>,
+    interactables: Query<(Option<&BattleStarter>, Option<&DialogStarter>)>,
+) {
     if !input.just_pressed(keybinds.interact) {
-        return None;
+        return;
     }
 
-    // TODO: O(p*i), should be changed for a smarter implementation
-    for p_transform in player.iter() {
-        for (i_transform, interactable) in interactables.iter() {
-            let distance = p_transform.translation - i_transform.translation;
+    let entity = match player.iter().next() {
+        Some(transform) => grid.nearest(transform.translation, INTERACT_RADIUS),
+        None => return,
+    };
 
-            if distance.length() < 2.0 {
-                return Some(interactable.clone());
-            }
+    match entity.and_then(|entity| interactables.get(entity).ok()) {
+        Some((Some(battle), _)) => battle_starter::start_battle(&mut commands, &mut state, battle),
+        Some((_, Some(dialog))) => {
+            dialog_starter::start_dialog(&mut commands, &mut state, &mut quest_events, dialog)
         }
+        _ => {}
     }
-
-    None
 }
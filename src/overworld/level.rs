@@ -0,0 +1,358 @@
+//! Levels of the overworld, stored as RON files in `assets/data/levels`
+//!
+//! A level describes everything spawned when entering the overworld: the terrain and
+//! props, NPCs, battle starters, encounter zones and the points the player can spawn at.
+//! Levels are connected by transitions, see [`crate::overworld::transition`].
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::battle::encounter::Encounter;
+use crate::overworld::collision::{Collider, WorldBounds};
+use crate::overworld::encounter_zone::{EncounterTable, EncounterZone};
+use crate::overworld::interactables::{
+    battle_starter::BattleStarter, dialog_starter::DialogStarter, Interactable,
+};
+use crate::overworld::region::{NamedRegion, RegionBounds};
+use crate::overworld::transition::TransitionTrigger;
+use crate::overworld::StateCleanup;
+
+/// ID of the level the game starts in
+const FIRST_LEVEL: &str = "bevytown";
+
+/// Spawn point the game starts at, in the first level
+const FIRST_SPAWN_POINT: &str = "start";
+
+/// Marker for despawning when leaving the level, while staying in the overworld
+pub struct LevelCleanup;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum Shape {
+    /// A flat square, with the given side length
+    Plane(f32),
+    /// A box with the given width, height and depth
+    Box(f32, f32, f32),
+}
+
+impl Shape {
+    fn mesh(self) -> Mesh {
+        match self {
+            Shape::Plane(size) => Mesh::from(shape::Plane { size }),
+            Shape::Box(x, y, z) => Mesh::from(shape::Box::new(x, y, z)),
+        }
+    }
+
+    fn collider(self) -> Collider {
+        match self {
+            Shape::Plane(size) => Collider::new(size, 0., size),
+            Shape::Box(x, y, z) => Collider::new(x, y, z),
+        }
+    }
+}
+
+/// A piece of scenery, like the ground, a wall or a tree
+#[derive(Debug, Clone, Deserialize)]
+pub struct Prop {
+    pub shape: Shape,
+    /// Red, green and blue, between 0 and 1
+    pub color: (f32, f32, f32),
+    /// Position of the centre of the shape
+    pub position: (f32, f32, f32),
+}
+
+/// An NPC the player can talk to
+#[derive(Debug, Clone, Deserialize)]
+pub struct Npc {
+    pub name: String,
+    /// Path of the portrait shown in the dialog, relative to the assets directory
+    pub sprite: String,
+    /// ID of the shop in `assets/data/shops` the NPC runs, if any
+    #[serde(default)]
+    pub shop: Option<String>,
+    pub color: (f32, f32, f32),
+    pub position: (f32, f32, f32),
+}
+
+/// A battle started by walking up to it, which doesn't come back once won
+#[derive(Debug, Clone, Deserialize)]
+pub struct LevelBattleStarter {
+    /// Unique ID, remembered once the battle is won
+    pub id: String,
+    /// Name of the encounter in `assets/data/encounters`
+    pub encounter: String,
+    pub position: (f32, f32, f32),
+}
+
+/// An area where random battles can start
+#[derive(Debug, Clone, Deserialize)]
+pub struct LevelEncounterZone {
+    /// Name of the table in `assets/data/encounter_tables`
+    pub table: String,
+    /// Position of the centre of the zone, on the ground
+    pub position: (f32, f32, f32),
+    /// Width and depth of the zone
+    pub size: (f32, f32),
+    pub color: (f32, f32, f32),
+}
+
+/// A trigger that moves the player to another level, like a door or the edge of the map
+#[derive(Debug, Clone, Deserialize)]
+pub struct Transition {
+    /// Position of the centre of the trigger, on the ground
+    pub position: (f32, f32, f32),
+    /// Width and depth of the trigger
+    pub size: (f32, f32),
+    /// ID of the level to go to
+    pub level: String,
+    /// Spawn point to place the player at, which should be outside of the level's
+    /// transitions so the player isn't sent straight back
+    pub spawn_point: String,
+}
+
+/// A named part of the level, shown in the HUD area label when entered
+#[derive(Debug, Clone, Deserialize)]
+pub struct LevelRegion {
+    pub name: String,
+    /// Position the bounds are relative to, on the ground
+    pub position: (f32, f32, f32),
+    pub bounds: RegionBounds,
+}
+
+/// A level, as defined in `data/levels`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Level {
+    /// Shown in the HUD area label when entering the level
+    pub name: String,
+    pub terrain: Vec<Prop>,
+    /// Lowest and highest `(x, z)` the player can walk to
+    pub bounds: ((f32, f32), (f32, f32)),
+    #[serde(default)]
+    pub props: Vec<Prop>,
+    #[serde(default)]
+    pub npcs: Vec<Npc>,
+    #[serde(default)]
+    pub battle_starters: Vec<LevelBattleStarter>,
+    #[serde(default)]
+    pub encounter_zones: Vec<LevelEncounterZone>,
+    #[serde(default)]
+    pub transitions: Vec<Transition>,
+    #[serde(default)]
+    pub regions: Vec<LevelRegion>,
+    /// Where the player can appear, by name
+    pub spawn_points: HashMap<String, (f32, f32, f32)>,
+}
+
+impl Level {
+    /// Loads a level from `assets/data/levels`
+    pub fn load(id: &str) -> Self {
+        crate::data::load(&format!("levels/{}.ron", id))
+    }
+
+    /// Position of the named spawn point
+    pub fn spawn_point(&self, name: &str) -> Vec3 {
+        let position = self
+            .spawn_points
+            .get(name)
+            .unwrap_or_else(|| panic!("Unknown spawn point in {}: {}", self.name, name));
+
+        Vec3::from(*position)
+    }
+
+    pub fn world_bounds(&self) -> WorldBounds {
+        let ((min_x, min_z), (max_x, max_z)) = self.bounds;
+
+        WorldBounds {
+            min: Vec2::new(min_x, min_z),
+            max: Vec2::new(max_x, max_z),
+        }
+    }
+}
+
+/// Where the player is in the overworld, kept in the save game
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Location {
+    /// ID of the level in `assets/data/levels`
+    pub level: String,
+    /// Spawn point the player entered the level at, and returns to after a battle
+    pub spawn_point: String,
+}
+
+impl Default for Location {
+    fn default() -> Self {
+        Location {
+            level: FIRST_LEVEL.to_string(),
+            spawn_point: FIRST_SPAWN_POINT.to_string(),
+        }
+    }
+}
+
+/// The level the player is in
+pub struct CurrentLevel {
+    pub location: Location,
+    pub level: Level,
+}
+
+impl CurrentLevel {
+    pub fn new(location: Location) -> Self {
+        CurrentLevel {
+            level: Level::load(&location.level),
+            location,
+        }
+    }
+
+    /// Position the player appears at when entering the overworld
+    pub fn spawn_position(&self) -> Vec3 {
+        self.level.spawn_point(&self.location.spawn_point)
+    }
+}
+
+/// Spawns the terrain, props, NPCs, battle starters, encounter zones, transitions and
+/// regions of the level
+///
+/// Everything but encounter zones, transitions and regions gets a collider.
+pub fn spawn_level(
+    commands: &mut Commands,
+    level: &Level,
+    asset_server: &AssetServer,
+    meshes: &mut Assets<Mesh>,
+    s_materials: &mut Assets<StandardMaterial>,
+    c_materials: &mut Assets<ColorMaterial>,
+) {
+    let color = |(r, g, b): (f32, f32, f32)| Color::rgb(r, g, b);
+
+    for prop in level.terrain.iter().chain(&level.props) {
+        commands
+            .spawn(PbrBundle {
+                mesh: meshes.add(prop.shape.mesh()),
+                material: s_materials.add(color(prop.color).into()),
+                transform: Transform::from_translation(Vec3::from(prop.position)),
+                ..Default::default()
+            })
+            .with(prop.shape.collider())
+            .with(LevelCleanup)
+            .with(StateCleanup);
+    }
+
+    for npc in &level.npcs {
+        let sprite = asset_server.load(npc.sprite.as_str());
+        commands
+            .spawn(PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::Box::new(1., 2., 1.))),
+                material: s_materials.add(color(npc.color).into()),
+                transform: Transform::from_translation(Vec3::from(npc.position)),
+                ..Default::default()
+            })
+            .with(Collider::new(1., 2., 1.))
+            .with(DialogStarter {
+                npc_name: npc.name.clone(),
+                sprite: c_materials.add(sprite.into()),
+                shop: npc.shop.clone(),
+            })
+            .with(Interactable)
+            .with(LevelCleanup)
+            .with(StateCleanup);
+    }
+
+    for starter in &level.battle_starters {
+        commands
+            .spawn(PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::Box::new(1., 1., 1.))),
+                material: s_materials.add(Color::GREEN.into()),
+                transform: Transform::from_translation(Vec3::from(starter.position)),
+                ..Default::default()
+            })
+            .with(Collider::new(1., 1., 1.))
+            .with(BattleStarter {
+                id: starter.id.clone(),
+                encounter: Encounter::load(&starter.encounter),
+            })
+            .with(Interactable)
+            .with(LevelCleanup)
+            .with(StateCleanup);
+    }
+
+    for zone in &level.encounter_zones {
+        let (width, depth) = zone.size;
+
+        // Drawn just above the ground
+        commands
+            .spawn(PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::Box::new(width, 0.1, depth))),
+                material: s_materials.add(color(zone.color).into()),
+                transform: Transform::from_translation(
+                    Vec3::from(zone.position) + Vec3::new(0., 0.05, 0.),
+                ),
+                ..Default::default()
+            })
+            .with(EncounterZone {
+                half_extents: Vec3::new(width / 2., 2., depth / 2.),
+                table: EncounterTable::load(&zone.table),
+            })
+            .with(LevelCleanup)
+            .with(StateCleanup);
+    }
+
+    for transition in &level.transitions {
+        let (width, depth) = transition.size;
+
+        commands
+            .spawn(())
+            .with(Transform::from_translation(Vec3::from(transition.position)))
+            .with(TransitionTrigger {
+                half_extents: Vec3::new(width / 2., 2., depth / 2.),
+                level: transition.level.clone(),
+                spawn_point: transition.spawn_point.clone(),
+            })
+            .with(LevelCleanup)
+            .with(StateCleanup);
+    }
+
+    for region in &level.regions {
+        commands
+            .spawn(())
+            .with(Transform::from_translation(Vec3::from(region.position)))
+            .with(NamedRegion {
+                name: region.name.clone(),
+                bounds: region.bounds.clone(),
+            })
+            .with(LevelCleanup)
+            .with(StateCleanup);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_files_are_valid() {
+        let start = CurrentLevel::new(Location::default());
+        start.spawn_position();
+
+        for id in &["bevytown", "millbrook_fields"] {
+            let level = Level::load(id);
+            assert!(!level.terrain.is_empty());
+
+            for starter in &level.battle_starters {
+                assert!(!Encounter::load(&starter.encounter).enemies.is_empty());
+            }
+            for zone in &level.encounter_zones {
+                assert!(!EncounterTable::load(&zone.table).entries.is_empty());
+            }
+
+            // Arriving through a transition must not trigger one
+            for transition in &level.transitions {
+                let destination = Level::load(&transition.level);
+                let spawn = destination.spawn_point(&transition.spawn_point);
+
+                for back in &destination.transitions {
+                    let (width, depth) = back.size;
+                    let offset = (spawn - Vec3::from(back.position)).abs();
+                    assert!(offset.x > width / 2. || offset.z > depth / 2.);
+                }
+            }
+        }
+    }
+}
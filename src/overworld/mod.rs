@@ -2,87 +2,179 @@ use bevy::prelude::*;
 
 use self::{
     camera::Camera,
-    interactables::{battle_starter::BattleStarter, dialog_starter::DialogStarter},
-    player::Player,
+    collision::{Collider, KinematicBody},
+    encounter_zone::EncounterSteps,
+    interactables::grid::InteractableGrid,
+    level::CurrentLevel,
+    player::{Player, PlayerModel, Stamina},
+    region::RegionTracker,
 };
 
 use crate::hud_area_label::HudAreaLabel;
+use crate::hud_stamina::{self, HudStaminaAssets};
+use crate::quest::QuestEvent;
 use crate::AppState;
 use crate::Stage;
 
 pub mod camera;
+pub mod collision;
+pub mod encounter_zone;
 pub mod interactables;
+pub mod level;
 pub mod player;
+pub mod region;
+pub mod transition;
 
 /// Marker for despawning when exiting `AppState::Overworld`
 pub struct StateCleanup;
 
+/// Where the player stood when the game was paused
+///
+/// Present while the pause menu, or a screen opened from it, is shown. Resuming puts the
+/// player back there instead of at the level's spawn point, and skips the systems that
+/// only run when arriving in a level.
+pub struct PausedOverworld {
+    player: Transform,
+}
+
 pub struct OverworldPlugin;
 
 impl Plugin for OverworldPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.on_state_enter(
-            Stage::AppState,
-            AppState::Overworld,
-            setup_overworld.system(),
-        )
-        .on_state_enter(
-            Stage::AppState,
-            AppState::Overworld,
-            show_area_title.system(),
-        )
-        .on_state_update(
-            Stage::AppState,
-            AppState::Overworld,
-            player::move_player.system(),
-        )
-        .on_state_update(
-            Stage::AppState,
-            AppState::Overworld,
-            camera::rotate_camera.system(),
-        )
-        .on_state_update(
-            Stage::AppState,
-            AppState::Overworld,
-            interactables::interactable_interact::<BattleStarter>
-                .system()
-                .chain(interactables::battle_starter::interactable_start_battle.system()),
-        )
-        .on_state_update(
-            Stage::AppState,
-            AppState::Overworld,
-            interactables::interactable_interact::<DialogStarter>
-                .system()
-                .chain(interactables::dialog_starter::interactable_start_dialog.system()),
-        )
-        .on_state_update(Stage::AppState, AppState::Overworld, back_to_menu.system())
-        .on_state_exit(
-            Stage::AppState,
-            AppState::Overworld,
-            crate::despawn_all::<StateCleanup>.system(),
-        );
+        app.init_resource::<EncounterSteps>()
+            .init_resource::<RegionTracker>()
+            .init_resource::<HudStaminaAssets>()
+            .init_resource::<InteractableGrid>()
+            // Runs after the state stage, to see interactables spawned in it
+            .add_system(interactables::grid::track_interactables.system())
+            .on_state_enter(
+                Stage::AppState,
+                AppState::Overworld,
+                setup_overworld.system(),
+            )
+            .on_state_enter(
+                Stage::AppState,
+                AppState::Overworld,
+                encounter_zone::reset_encounter_steps.system(),
+            )
+            .on_state_enter(
+                Stage::AppState,
+                AppState::Overworld,
+                show_area_title.system(),
+            )
+            .on_state_enter(
+                Stage::AppState,
+                AppState::Overworld,
+                hud_stamina::setup_hud_stamina.system(),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Overworld,
+                player::move_player.system(),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Overworld,
+                player::turn_player_model.system(),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Overworld,
+                hud_stamina::update_hud_stamina.system(),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Overworld,
+                camera::rotate_camera.system(),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Overworld,
+                crate::world_time::advance_world_time.system(),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Overworld,
+                transition::enter_transitions.system(),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Overworld,
+                transition::update_transition.system(),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Overworld,
+                region::enter_regions.system(),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Overworld,
+                encounter_zone::random_encounters.system(),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Overworld,
+                interactables::interactable_interact.system(),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Overworld,
+                interactables::battle_starter::remove_defeated_battle_starters.system(),
+            )
+            .on_state_update(
+                Stage::AppState,
+                AppState::Overworld,
+                open_pause_menu.system(),
+            )
+            .on_state_exit(
+                Stage::AppState,
+                AppState::Overworld,
+                transition::cancel_transition.system(),
+            )
+            .on_state_exit(
+                Stage::AppState,
+                AppState::Overworld,
+                interactables::grid::clear_interactables.system(),
+            )
+            .on_state_exit(
+                Stage::AppState,
+                AppState::Overworld,
+                crate::despawn_all::<StateCleanup>.system(),
+            );
     }
 }
 
 fn setup_overworld(
     mut commands: Commands,
+    current: Res<CurrentLevel>,
+    paused: Option<Res<PausedOverworld>>,
     asset_server: Res<AssetServer>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut s_materials: ResMut<Assets<StandardMaterial>>,
     mut c_materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    let player_entity = spawn_player(&mut commands, &mut meshes, &mut s_materials);
+    let transform = match paused {
+        Some(paused) => paused.player,
+        None => Transform::from_translation(current.spawn_position()),
+    };
+    // Removed once the state stage ends, so the other enter systems still see it
+    commands.remove_resource::<PausedOverworld>();
+
+    let player_entity = spawn_player(&mut commands, transform, &mut meshes, &mut s_materials);
     let camera_entity = spawn_camera(&mut commands);
 
     commands.push_children(player_entity, &[camera_entity]);
 
-    spawn_interactables(
+    level::spawn_level(
         &mut commands,
+        &current.level,
         &asset_server,
         &mut meshes,
         &mut s_materials,
         &mut c_materials,
     );
+    transition::spawn_fade_overlay(&mut commands, &mut c_materials);
 
     commands
         .spawn(LightBundle {
@@ -98,26 +190,35 @@ fn setup_overworld(
 
 fn spawn_player(
     commands: &mut Commands,
+    transform: Transform,
     meshes: &mut Assets<Mesh>,
     materials: &mut Assets<StandardMaterial>,
 ) -> Entity {
-    commands
-        .spawn(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Plane { size: 20.0 })),
-            material: materials.add(Color::rgb(0.1, 0.8, 0.2).into()),
-            ..Default::default()
-        })
+    let player = commands
+        .spawn(())
+        .with(transform)
+        .with(GlobalTransform::default())
         .with(StateCleanup)
+        .with(Player::new(10.))
+        .with(Stamina::default())
+        .with(Collider::new(1., 2., 1.))
+        .with(KinematicBody::default())
+        .current_entity()
+        .unwrap();
+
+    let model = commands
         .spawn(PbrBundle {
             mesh: meshes.add(Mesh::from(shape::Box::new(1., 2., 1.))),
             material: materials.add(Color::WHITE.into()),
-            transform: Transform::from_translation(Vec3::new(0., 1.0, 0.)),
             ..Default::default()
         })
-        .with(StateCleanup)
-        .with(Player { speed: 10. })
+        .with(PlayerModel)
         .current_entity()
-        .unwrap()
+        .unwrap();
+
+    commands.push_children(player, &[model]);
+
+    player
 }
 
 fn spawn_camera(commands: &mut Commands) -> Entity {
@@ -146,44 +247,31 @@ fn spawn_camera(commands: &mut Commands) -> Entity {
     root
 }
 
-fn spawn_interactables(
-    commands: &mut Commands,
-    asset_server: &AssetServer,
-    meshes: &mut Assets<Mesh>,
-    s_materials: &mut Assets<StandardMaterial>,
-    c_materials: &mut Assets<ColorMaterial>,
+fn show_area_title(
+    current: Res<CurrentLevel>,
+    paused: Option<Res<PausedOverworld>>,
+    mut hud: ResMut<HudAreaLabel>,
+    mut quest_events: EventWriter<QuestEvent>,
 ) {
-    commands
-        .spawn(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Box::new(1., 1., 1.))),
-            material: s_materials.add(Color::GREEN.into()),
-            transform: Transform::from_translation(Vec3::new(5., 1.0, 5.)),
-            ..Default::default()
-        })
-        .with(BattleStarter)
-        .with(StateCleanup);
-
-    let ferris_handle = asset_server.load("sprites/ferris-happy.png");
-    commands
-        .spawn(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Box::new(1., 2., 1.))),
-            material: s_materials.add(Color::RED.into()),
-            transform: Transform::from_translation(Vec3::new(-5., 1.0, 5.)),
-            ..Default::default()
-        })
-        .with(DialogStarter {
-            npc_name: "Ferris".to_string(),
-            sprite: c_materials.add(ferris_handle.into()),
-        })
-        .with(StateCleanup);
-}
+    // Resuming isn't arriving in the area
+    if paused.is_some() {
+        return;
+    }
 
-fn show_area_title(mut hud: ResMut<HudAreaLabel>) {
-    hud.show_area_title("Overworld");
+    hud.show_area_title(current.level.name.as_str());
+    quest_events.send(QuestEvent::AreaEntered(current.level.name.clone()));
 }
 
-pub fn back_to_menu(mut state: ResMut<State<AppState>>, input: Res<Input<KeyCode>>) {
+pub fn open_pause_menu(
+    mut commands: Commands,
+    mut state: ResMut<State<AppState>>,
+    input: Res<Input<KeyCode>>,
+    players: Query<&Transform, With<Player>>,
+) {
     if input.just_pressed(KeyCode::Escape) {
-        state.set_next(AppState::MainMenu).unwrap();
+        if let Some(player) = players.iter().next() {
+            commands.insert_resource(PausedOverworld { player: *player });
+        }
+        state.set_next(AppState::PauseMenu).unwrap();
     }
 }
@@ -1,33 +1,319 @@
 use bevy::prelude::*;
 
+use crate::overworld::camera::Camera;
+use crate::overworld::collision::{self, Collider, KinematicBody};
+use crate::overworld::level::CurrentLevel;
+use crate::overworld::transition::ActiveTransition;
 use crate::user_config::KeyBinds;
 
+/// How fast the player speeds up to walking speed, in world units per second squared
+const ACCELERATION: f32 = 60.0;
+
+/// How fast the player slows down when no key is pressed, in world units per second squared
+const DECELERATION: f32 = 80.0;
+
+/// How fast the player model turns towards the direction it walks in
+const TURN_SPEED: f32 = 12.0;
+
+/// How much faster sprinting is than walking
+const SPRINT_MULTIPLIER: f32 = 1.8;
+
+/// How much stamina sprinting uses per second, out of 1
+const STAMINA_DRAIN: f32 = 0.25;
+
+/// How much stamina comes back per second when not sprinting, out of 1
+const STAMINA_REGENERATION: f32 = 0.2;
+
+/// How much stamina has to come back after running out before sprinting again, out of 1
+const STAMINA_RECOVERY: f32 = 0.3;
+
+/// Upwards speed at the start of a jump, in world units per second
+const JUMP_SPEED: f32 = 8.0;
+
+/// How long after walking off a ledge the player can still jump, in seconds
+const COYOTE_TIME: f32 = 0.1;
+
 pub struct Player {
+    /// Walking speed, in world units per second
     pub speed: f32,
+    /// Current horizontal velocity
+    pub velocity: Vec3,
+    /// Seconds since the player last stood on the ground
+    airborne_time: f32,
+}
+
+impl Player {
+    pub fn new(speed: f32) -> Self {
+        Player {
+            speed,
+            velocity: Vec3::ZERO,
+            airborne_time: 0.,
+        }
+    }
+
+    /// Keeps track of how long the player has been in the air, returning whether the
+    /// player can jump
+    ///
+    /// Jumping is still possible shortly after walking off a ledge.
+    fn update_airborne_time(&mut self, grounded: bool, delta_seconds: f32) -> bool {
+        if grounded {
+            self.airborne_time = 0.;
+        } else {
+            self.airborne_time += delta_seconds;
+        }
+
+        self.airborne_time < COYOTE_TIME
+    }
+
+    fn jump(&mut self, body: &mut KinematicBody) {
+        body.vertical_speed = JUMP_SPEED;
+        // No jumping again before landing
+        self.airborne_time = COYOTE_TIME;
+    }
+}
+
+/// How long the player can keep sprinting, shown in the HUD stamina bar
+pub struct Stamina {
+    /// Between 0 and 1
+    level: f32,
+    /// Whether stamina ran out, and hasn't recovered enough yet to sprint again
+    exhausted: bool,
+}
+
+impl Default for Stamina {
+    fn default() -> Self {
+        Stamina {
+            level: 1.,
+            exhausted: false,
+        }
+    }
+}
+
+impl Stamina {
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    /// Drains stamina while sprinting and regenerates it otherwise, returning whether the
+    /// player sprints
+    fn update(&mut self, wants_to_sprint: bool, delta_seconds: f32) -> bool {
+        if wants_to_sprint && !self.exhausted {
+            self.level -= STAMINA_DRAIN * delta_seconds;
+            if self.level <= 0. {
+                self.level = 0.;
+                self.exhausted = true;
+            }
+            return true;
+        }
+
+        self.level = (self.level + STAMINA_REGENERATION * delta_seconds).min(1.);
+        if self.level >= STAMINA_RECOVERY {
+            self.exhausted = false;
+        }
+        false
+    }
+}
+
+/// Marker for the visible part of the player, turned to face the way the player walks
+///
+/// The model is a child of the player, so turning it doesn't turn the camera.
+pub struct PlayerModel;
+
+/// Direction on the ground the pressed keys point to, relative to the camera
+///
+/// The direction is normalized, so walking diagonally isn't faster.
+fn input_direction(camera_rotation: Quat, forward: f32, right: f32) -> Vec3 {
+    let camera_forward = camera_rotation * Vec3::Z;
+    let camera_forward = Vec3::new(camera_forward.x, 0., camera_forward.z).normalize();
+    let camera_right = camera_forward.cross(Vec3::Y);
+
+    let direction = camera_forward * forward + camera_right * right;
+    if direction == Vec3::ZERO {
+        direction
+    } else {
+        direction.normalize()
+    }
+}
+
+/// Changes the velocity towards the target velocity, by at most `rate * delta_seconds`
+fn accelerate(velocity: Vec3, target: Vec3, rate: f32, delta_seconds: f32) -> Vec3 {
+    let difference = target - velocity;
+    let max_change = rate * delta_seconds;
+
+    if difference.length() <= max_change {
+        target
+    } else {
+        velocity + difference.normalize() * max_change
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn move_player(
     input: Res<Input<KeyCode>>,
     keybinds: Res<KeyBinds>,
-    mut query: Query<(&mut Transform, &Player)>,
+    mut query: Query<(
+        &mut Transform,
+        &mut Player,
+        &mut Stamina,
+        &Collider,
+        &mut KinematicBody,
+    )>,
+    cameras: Query<&Transform, (With<Camera>, Without<Player>)>,
+    colliders: Query<(&Transform, &Collider), Without<Player>>,
     time: Res<Time>,
+    current: Res<CurrentLevel>,
+    transition: Option<Res<ActiveTransition>>,
 ) {
-    for (mut transform, player) in query.iter_mut() {
-        let forward = transform.local_z();
-        let left = forward.cross(Vec3::Y);
-        let delta = time.delta_seconds() * player.speed;
-
-        if input.pressed(keybinds.move_forward) {
-            transform.translation += forward * delta;
-        }
-        if input.pressed(keybinds.move_backward) {
-            transform.translation -= forward * delta;
+    // The player stands still while the screen fades to the next level
+    if transition.is_some() {
+        for (_, mut player, _, _, _) in query.iter_mut() {
+            player.velocity = Vec3::ZERO;
         }
-        if input.pressed(keybinds.move_left) {
-            transform.translation -= left * delta;
-        }
-        if input.pressed(keybinds.move_right) {
-            transform.translation += left * delta;
+        return;
+    }
+
+    let camera_rotation = match cameras.iter().next() {
+        Some(transform) => transform.rotation,
+        None => return,
+    };
+
+    let axis = |positive: KeyCode, negative: KeyCode| {
+        input.pressed(positive) as i32 as f32 - input.pressed(negative) as i32 as f32
+    };
+    let direction = input_direction(
+        camera_rotation,
+        axis(keybinds.move_forward, keybinds.move_backward),
+        axis(keybinds.move_right, keybinds.move_left),
+    );
+
+    let obstacles: Vec<_> = colliders
+        .iter()
+        .map(|(transform, collider)| collider.aabb(transform.translation))
+        .collect();
+
+    for (mut transform, mut player, mut stamina, collider, mut body) in query.iter_mut() {
+        let delta_seconds = time.delta_seconds();
+
+        let moving = direction != Vec3::ZERO;
+        let sprinting = stamina.update(moving && input.pressed(keybinds.sprint), delta_seconds);
+        let speed = if sprinting {
+            player.speed * SPRINT_MULTIPLIER
+        } else {
+            player.speed
+        };
+
+        let rate = if moving { ACCELERATION } else { DECELERATION };
+        player.velocity = accelerate(player.velocity, direction * speed, rate, delta_seconds);
+
+        let can_jump = player.update_airborne_time(body.grounded, delta_seconds);
+        if can_jump && input.just_pressed(keybinds.jump) {
+            player.jump(&mut body);
         }
+
+        transform.translation = collision::move_and_slide(
+            &mut body,
+            collider,
+            transform.translation,
+            player.velocity * delta_seconds,
+            delta_seconds,
+            &obstacles,
+            current.level.world_bounds(),
+        );
+    }
+}
+
+/// Turns the player model towards the direction the player walks in
+pub fn turn_player_model(
+    time: Res<Time>,
+    players: Query<&Player>,
+    mut models: Query<&mut Transform, With<PlayerModel>>,
+) {
+    let velocity = match players.iter().next() {
+        Some(player) => player.velocity,
+        None => return,
+    };
+
+    // Keep facing the same way when standing still
+    if velocity.length_squared() < 0.01 {
+        return;
+    }
+
+    let facing = Quat::from_rotation_y(velocity.x.atan2(velocity.z));
+    let turn = (TURN_SPEED * time.delta_seconds()).min(1.);
+
+    for mut transform in models.iter_mut() {
+        transform.rotation = transform.rotation.slerp(facing, turn);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn input_is_relative_to_the_camera() {
+        let forward = input_direction(Quat::IDENTITY, 1., 0.);
+        assert!((forward - Vec3::Z).length() < 1e-5);
+
+        // The camera looks along +x after turning a quarter to the left
+        let turned = Quat::from_rotation_y(std::f32::consts::FRAC_PI_2);
+        let forward = input_direction(turned, 1., 0.);
+        assert!((forward - Vec3::X).length() < 1e-5);
+
+        let diagonal = input_direction(turned, 1., 1.);
+        assert!((diagonal.length() - 1.).abs() < 1e-5);
+
+        assert_eq!(input_direction(turned, 0., 0.), Vec3::ZERO);
+    }
+
+    #[test]
+    fn velocity_changes_gradually() {
+        let target = Vec3::new(10., 0., 0.);
+
+        let velocity = accelerate(Vec3::ZERO, target, 60., 0.1);
+        assert_eq!(velocity, Vec3::new(6., 0., 0.));
+
+        let velocity = accelerate(velocity, target, 60., 0.1);
+        assert_eq!(velocity, target);
+
+        let velocity = accelerate(velocity, Vec3::ZERO, 80., 0.1);
+        assert_eq!(velocity, Vec3::new(2., 0., 0.));
+    }
+
+    #[test]
+    fn stamina_recovers_before_sprinting_again() {
+        let mut stamina = Stamina::default();
+
+        // Sprinting until stamina runs out
+        assert!(stamina.update(true, 2.));
+        assert!(stamina.update(true, 2.));
+        assert_eq!(stamina.level(), 0.);
+        assert!(stamina.is_exhausted());
+
+        assert!(!stamina.update(true, 1.));
+        assert!(!stamina.update(true, 0.5));
+        assert!(stamina.update(true, 0.1));
+    }
+
+    #[test]
+    fn jumping_is_possible_shortly_after_leaving_the_ground() {
+        let mut player = Player::new(10.);
+        let mut body = KinematicBody::default();
+
+        assert!(player.update_airborne_time(true, 0.02));
+        assert!(player.update_airborne_time(false, 0.05));
+        player.jump(&mut body);
+        assert_eq!(body.vertical_speed, JUMP_SPEED);
+
+        // No jumping again in the air
+        assert!(!player.update_airborne_time(false, 0.02));
+
+        let mut player = Player::new(10.);
+        player.update_airborne_time(false, 0.05);
+        assert!(!player.update_airborne_time(false, 0.06));
     }
 }
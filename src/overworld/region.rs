@@ -0,0 +1,225 @@
+//! Named parts of a level, like a town square, shown in the HUD area label when entered
+//!
+//! Regions overlap their neighbours by a margin: the player has to walk some distance
+//! out of a region before it counts as left. Walking along a boundary only shows the
+//! label once.
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::hud_area_label::HudAreaLabel;
+use crate::overworld::level::CurrentLevel;
+use crate::overworld::player::Player;
+use crate::overworld::transition::ActiveTransition;
+
+/// How far the player has to be outside of a region to leave it, in world units
+const HYSTERESIS: f32 = 1.0;
+
+/// Outline of a region on the ground, relative to the region's position
+#[derive(Debug, Clone, Deserialize)]
+pub enum RegionBounds {
+    /// A box with the given width and depth
+    Box(f32, f32),
+    /// A polygon through the given points, as `(x, z)`
+    Polygon(Vec<(f32, f32)>),
+}
+
+impl RegionBounds {
+    /// How far the point is outside of the bounds, negative when it is inside
+    fn distance(&self, point: Vec2) -> f32 {
+        match self {
+            RegionBounds::Box(width, depth) => {
+                let offset = point.abs() - Vec2::new(width / 2., depth / 2.);
+                offset.x.max(offset.y)
+            }
+            RegionBounds::Polygon(points) => {
+                let points: Vec<Vec2> = points.iter().map(|&(x, z)| Vec2::new(x, z)).collect();
+                let mut inside = false;
+                let mut distance = f32::INFINITY;
+
+                for (i, &a) in points.iter().enumerate() {
+                    let b = points[(i + 1) % points.len()];
+
+                    // Count the edges crossed by a ray from the point towards +x
+                    if (a.y > point.y) != (b.y > point.y)
+                        && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x)
+                    {
+                        inside = !inside;
+                    }
+
+                    let edge = b - a;
+                    let t = ((point - a).dot(edge) / edge.length_squared()).clamp(0., 1.);
+                    distance = distance.min((a + edge * t - point).length());
+                }
+
+                if inside {
+                    -distance
+                } else {
+                    distance
+                }
+            }
+        }
+    }
+}
+
+/// A named part of a level, around the entity's position
+#[derive(Debug, Clone)]
+pub struct NamedRegion {
+    pub name: String,
+    pub bounds: RegionBounds,
+}
+
+impl NamedRegion {
+    fn distance(&self, center: Vec3, point: Vec3) -> f32 {
+        let offset = point - center;
+        self.bounds.distance(Vec2::new(offset.x, offset.z))
+    }
+}
+
+/// The region the player is in
+#[derive(Debug, Default)]
+pub struct RegionTracker {
+    /// ID of the level the region is in
+    level: String,
+    region: Option<String>,
+}
+
+impl RegionTracker {
+    /// Finds the region the player is in, returning its name if the player just walked in
+    ///
+    /// Regions the player is in when arriving in a level aren't announced, since the
+    /// level's name is shown then.
+    fn update<'a>(
+        &mut self,
+        level: &str,
+        position: Vec3,
+        mut regions: impl Iterator<Item = (Vec3, &'a NamedRegion)> + Clone,
+    ) -> Option<String> {
+        let arrived = self.level != level;
+        if arrived {
+            self.level = level.to_string();
+            self.region = None;
+        }
+
+        // Stay in the current region until clearly out of it. Regions that aren't spawned
+        // yet, like when coming back from a battle, aren't left either.
+        if let Some(name) = &self.region {
+            let stays = regions
+                .clone()
+                .find(|(_, region)| &region.name == name)
+                .map_or(true, |(center, region)| {
+                    region.distance(center, position) <= HYSTERESIS
+                });
+
+            if stays {
+                return None;
+            }
+        }
+
+        let entered = regions
+            .find(|(center, region)| region.distance(*center, position) <= 0.0)
+            .map(|(_, region)| region.name.clone());
+
+        self.region = entered.clone();
+        entered.filter(|_| !arrived)
+    }
+}
+
+/// Shows the name of the region the player walks into
+pub fn enter_regions(
+    current: Res<CurrentLevel>,
+    mut tracker: ResMut<RegionTracker>,
+    mut hud: ResMut<HudAreaLabel>,
+    transition: Option<Res<ActiveTransition>>,
+    players: Query<&Transform, With<Player>>,
+    regions: Query<(&Transform, &NamedRegion)>,
+) {
+    // The regions of the previous level may still be around
+    if transition.is_some() {
+        return;
+    }
+
+    let position = match players.iter().next() {
+        Some(transform) => transform.translation,
+        None => return,
+    };
+
+    let regions: Vec<(Vec3, &NamedRegion)> = regions
+        .iter()
+        .map(|(transform, region)| (transform.translation, region))
+        .collect();
+
+    if let Some(name) = tracker.update(&current.location.level, position, regions.into_iter()) {
+        hud.show_area_title(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square() -> NamedRegion {
+        NamedRegion {
+            name: "Square".to_string(),
+            bounds: RegionBounds::Box(4., 4.),
+        }
+    }
+
+    fn mill() -> NamedRegion {
+        NamedRegion {
+            name: "Mill".to_string(),
+            bounds: RegionBounds::Polygon(vec![(0., 0.), (4., 0.), (0., 4.)]),
+        }
+    }
+
+    #[test]
+    fn bounds_measure_the_distance_to_their_edge() {
+        let square = RegionBounds::Box(4., 2.);
+        assert_eq!(square.distance(Vec2::new(0., 0.)), -1.);
+        assert_eq!(square.distance(Vec2::new(-3., 0.)), 1.);
+
+        let triangle = mill().bounds;
+        assert_eq!(triangle.distance(Vec2::new(1., 0.5)), -0.5);
+        assert_eq!(triangle.distance(Vec2::new(-2., 1.)), 2.);
+        assert!(triangle.distance(Vec2::new(3., 3.)) > 0.);
+    }
+
+    #[test]
+    fn walking_along_a_boundary_enters_once() {
+        let square = square();
+        let mill = mill();
+        // The square ends at x = 2, where the mill starts
+        let regions = vec![(Vec3::ZERO, &square), (Vec3::new(2., 0., -2.), &mill)];
+        let mut tracker = RegionTracker::default();
+
+        let mut walk =
+            |x: f32| tracker.update("town", Vec3::new(x, 0., -1.), regions.clone().into_iter());
+
+        // Arriving in the level doesn't show the region
+        assert_eq!(walk(0.), None);
+
+        assert_eq!(walk(2.5), None);
+        assert_eq!(walk(1.5), None);
+        assert_eq!(walk(2.5), None);
+        assert_eq!(walk(3.2), Some("Mill".to_string()));
+        assert_eq!(walk(1.5), None);
+        assert_eq!(walk(0.8), Some("Square".to_string()));
+    }
+
+    #[test]
+    fn regions_are_kept_until_spawned() {
+        let square = square();
+        let mut tracker = RegionTracker::default();
+
+        tracker.update("town", Vec3::ZERO, vec![(Vec3::ZERO, &square)].into_iter());
+        assert_eq!(
+            tracker.update("town", Vec3::ZERO, Vec::new().into_iter()),
+            None
+        );
+        assert_eq!(
+            tracker.update("town", Vec3::ZERO, vec![(Vec3::ZERO, &square)].into_iter()),
+            None
+        );
+        assert_eq!(tracker.region, Some("Square".to_string()));
+    }
+}
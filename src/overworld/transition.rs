@@ -0,0 +1,178 @@
+//! Moving between levels, through doors and the edges of the map
+//!
+//! Walking into a [`TransitionTrigger`] fades the screen to black, swaps the level for the
+//! next one and places the player at its spawn point, then fades back in.
+
+use bevy::prelude::*;
+
+use crate::hud_area_label::HudAreaLabel;
+use crate::overworld::encounter_zone::EncounterSteps;
+use crate::overworld::interactables::grid::InteractableGrid;
+use crate::overworld::level::{self, CurrentLevel, LevelCleanup, Location};
+use crate::overworld::player::Player;
+use crate::overworld::StateCleanup;
+use crate::quest::QuestEvent;
+
+/// How long fading out, and fading back in, lasts in seconds
+const FADE_TIME: f32 = 0.4;
+
+/// A box around the entity's position that moves the player to another level
+pub struct TransitionTrigger {
+    pub half_extents: Vec3,
+    /// ID of the level to go to
+    pub level: String,
+    pub spawn_point: String,
+}
+
+impl TransitionTrigger {
+    pub fn contains(&self, center: Vec3, point: Vec3) -> bool {
+        let offset = (point - center).abs();
+
+        offset.x <= self.half_extents.x
+            && offset.y <= self.half_extents.y
+            && offset.z <= self.half_extents.z
+    }
+}
+
+/// A level transition in progress, inserted when the player walks into a trigger
+pub struct ActiveTransition {
+    destination: Location,
+    /// Seconds since the screen started fading, restarting when fading back in
+    elapsed: f32,
+    /// Whether the next level is spawned, and the screen is fading back in
+    arrived: bool,
+}
+
+/// Marker for the black screen covering the level swap
+pub struct FadeOverlay;
+
+/// Spawns the fade overlay, fully transparent
+pub fn spawn_fade_overlay(commands: &mut Commands, materials: &mut Assets<ColorMaterial>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0.0, 0.0, 0.0, 0.0).into()),
+            ..Default::default()
+        })
+        .with(FadeOverlay)
+        .with(StateCleanup);
+}
+
+/// Starts a transition when the player walks into a trigger
+pub fn enter_transitions(
+    mut commands: Commands,
+    transition: Option<Res<ActiveTransition>>,
+    players: Query<&Transform, With<Player>>,
+    triggers: Query<(&Transform, &TransitionTrigger)>,
+) {
+    if transition.is_some() {
+        return;
+    }
+
+    let position = match players.iter().next() {
+        Some(transform) => transform.translation,
+        None => return,
+    };
+
+    let trigger = triggers
+        .iter()
+        .find(|(transform, trigger)| trigger.contains(transform.translation, position));
+
+    if let Some((_, trigger)) = trigger {
+        info!("Going to {} at {}", trigger.level, trigger.spawn_point);
+
+        commands.insert_resource(ActiveTransition {
+            destination: Location {
+                level: trigger.level.clone(),
+                spawn_point: trigger.spawn_point.clone(),
+            },
+            elapsed: 0.0,
+            arrived: false,
+        });
+    }
+}
+
+/// Fades the screen out, swaps the level once it is black, and fades back in
+#[allow(clippy::too_many_arguments)]
+pub fn update_transition(
+    mut commands: Commands,
+    time: Res<Time>,
+    transition: Option<ResMut<ActiveTransition>>,
+    mut current: ResMut<CurrentLevel>,
+    mut steps: ResMut<EncounterSteps>,
+    mut grid: ResMut<InteractableGrid>,
+    mut hud: ResMut<HudAreaLabel>,
+    mut quest_events: EventWriter<QuestEvent>,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut s_materials: ResMut<Assets<StandardMaterial>>,
+    mut c_materials: ResMut<Assets<ColorMaterial>>,
+    mut players: Query<&mut Transform, With<Player>>,
+    level_entities: Query<Entity, With<LevelCleanup>>,
+    overlays: Query<&Handle<ColorMaterial>, With<FadeOverlay>>,
+) {
+    let mut transition = match transition {
+        Some(transition) => transition,
+        None => return,
+    };
+
+    transition.elapsed += time.delta_seconds();
+    let progress = (transition.elapsed / FADE_TIME).min(1.0);
+
+    let opacity = if transition.arrived {
+        1.0 - progress
+    } else {
+        progress
+    };
+    for overlay in overlays.iter() {
+        if let Some(material) = c_materials.get_mut(overlay) {
+            material.color.set_a(opacity);
+        }
+    }
+
+    if progress < 1.0 {
+        return;
+    }
+
+    if transition.arrived {
+        commands.remove_resource::<ActiveTransition>();
+        return;
+    }
+
+    for entity in level_entities.iter() {
+        commands.despawn_recursive(entity);
+    }
+    grid.clear();
+
+    *current = CurrentLevel::new(transition.destination.clone());
+    level::spawn_level(
+        &mut commands,
+        &current.level,
+        &asset_server,
+        &mut meshes,
+        &mut s_materials,
+        &mut c_materials,
+    );
+
+    for mut transform in players.iter_mut() {
+        transform.translation = current.spawn_position();
+    }
+    // Being moved to the spawn point isn't walking
+    steps.reset();
+
+    hud.show_area_title(current.level.name.as_str());
+    quest_events.send(QuestEvent::AreaEntered(current.level.name.clone()));
+
+    transition.arrived = true;
+    transition.elapsed = 0.0;
+}
+
+/// Drops a transition interrupted by leaving the overworld, the player spawns in the
+/// current level when coming back
+pub fn cancel_transition(mut commands: Commands) {
+    commands.remove_resource::<ActiveTransition>();
+}
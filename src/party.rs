@@ -0,0 +1,403 @@
+//! The player's party, which persists between battles
+//!
+//! Only what changes during the game, like experience and equipment, is saved with the
+//! party. What a member starts out as and how it grows is defined per character in
+//! `assets/data/characters.ron`.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::battle::combat::{Ability, Combatant, Stats, Team};
+use crate::battle::encounter::Rewards;
+use crate::inventory::{EquipmentSlot, Inventory, Items, StatBonus};
+
+/// Experience needed for the second level, every next level needs this much more
+const EXPERIENCE_STEP: u32 = 50;
+
+/// The highest level a party member can reach
+pub const MAX_LEVEL: u32 = 20;
+
+/// How a character starts out and grows, as defined in `data/characters.ron`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Character {
+    pub base_stats: Stats,
+    pub growth: StatGrowth,
+    pub abilities: Vec<Ability>,
+}
+
+/// Stats gained per level, fractions add up over several levels
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct StatGrowth {
+    pub max_health: f32,
+    pub attack: f32,
+    pub defence: f32,
+    pub movement: f32,
+}
+
+/// All characters, by name, as defined in `data/characters.ron`
+pub struct Characters(pub HashMap<String, Character>);
+
+impl Default for Characters {
+    fn default() -> Self {
+        Characters(crate::data::load("characters.ron"))
+    }
+}
+
+impl Characters {
+    pub fn get(&self, name: &str) -> &Character {
+        self.0
+            .get(name)
+            .unwrap_or_else(|| panic!("Unknown character: {}", name))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Party {
+    pub members: Vec<PartyMember>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartyMember {
+    /// Name of the member's character in `data/characters.ron`
+    pub name: String,
+    pub experience: u32,
+    #[serde(default)]
+    pub equipment: Equipment,
+}
+
+/// Names of the items a party member has equipped, per slot
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Equipment {
+    pub weapon: Option<String>,
+    pub armour: Option<String>,
+    pub accessory: Option<String>,
+}
+
+impl Equipment {
+    /// Every slot with its equipped item
+    pub fn slots(&self) -> [(EquipmentSlot, Option<&str>); 3] {
+        [
+            (EquipmentSlot::Weapon, self.weapon.as_deref()),
+            (EquipmentSlot::Armour, self.armour.as_deref()),
+            (EquipmentSlot::Accessory, self.accessory.as_deref()),
+        ]
+    }
+
+    pub fn get(&self, slot: EquipmentSlot) -> Option<&str> {
+        match slot {
+            EquipmentSlot::Weapon => self.weapon.as_deref(),
+            EquipmentSlot::Armour => self.armour.as_deref(),
+            EquipmentSlot::Accessory => self.accessory.as_deref(),
+        }
+    }
+
+    fn slot_mut(&mut self, slot: EquipmentSlot) -> &mut Option<String> {
+        match slot {
+            EquipmentSlot::Weapon => &mut self.weapon,
+            EquipmentSlot::Armour => &mut self.armour,
+            EquipmentSlot::Accessory => &mut self.accessory,
+        }
+    }
+
+    /// Names of all equipped items
+    pub fn items(&self) -> impl Iterator<Item = &str> {
+        EquipmentSlot::ALL
+            .iter()
+            .filter_map(move |slot| self.get(*slot))
+    }
+}
+
+/// Total experience needed to reach the given level
+pub fn experience_for_level(level: u32) -> u32 {
+    let level = level.clamp(1, MAX_LEVEL);
+
+    // Each level needs `EXPERIENCE_STEP` more than the previous one
+    EXPERIENCE_STEP * (level - 1) * level / 2
+}
+
+/// The level reached with the given total experience
+pub fn level_for_experience(experience: u32) -> u32 {
+    (1..MAX_LEVEL)
+        .find(|level| experience < experience_for_level(level + 1))
+        .unwrap_or(MAX_LEVEL)
+}
+
+impl PartyMember {
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        PartyMember {
+            name: name.into(),
+            experience: 0,
+            equipment: Equipment::default(),
+        }
+    }
+
+    pub fn level(&self) -> u32 {
+        level_for_experience(self.experience)
+    }
+
+    /// The member's stats at its current level, without equipment
+    pub fn stats(&self, character: &Character) -> Stats {
+        let levels = (self.level() - 1) as f32;
+        let grow = |base: i32, growth: f32| base + (growth * levels).floor() as i32;
+
+        Stats {
+            max_health: grow(character.base_stats.max_health, character.growth.max_health),
+            attack: grow(character.base_stats.attack, character.growth.attack),
+            defence: grow(character.base_stats.defence, character.growth.defence),
+            movement: grow(character.base_stats.movement, character.growth.movement),
+        }
+    }
+
+    /// The member's stats at its current level, with the bonuses of its equipment
+    pub fn equipped_stats(&self, character: &Character, items: &Items) -> Stats {
+        let (flat, percent) = self
+            .equipment
+            .items()
+            .filter_map(|item| items.get(item).equipment.as_ref())
+            .fold(
+                (StatBonus::default(), StatBonus::default()),
+                |(flat, percent), equipment| {
+                    (
+                        flat.combine(equipment.flat),
+                        percent.combine(equipment.percent),
+                    )
+                },
+            );
+
+        StatBonus::apply(flat, percent, self.stats(character))
+    }
+
+    /// The member's own abilities, followed by those granted by its equipment
+    pub fn abilities(&self, character: &Character, items: &Items) -> Vec<Ability> {
+        let granted = self
+            .equipment
+            .items()
+            .filter_map(|item| items.get(item).equipment.as_ref())
+            .flat_map(|equipment| equipment.abilities.iter().cloned());
+
+        character.abilities.iter().cloned().chain(granted).collect()
+    }
+
+    /// The combatant that represents this member in battle
+    pub fn combatant(&self, characters: &Characters, items: &Items) -> Combatant {
+        let character = characters.get(&self.name);
+
+        Combatant::new(
+            self.name.clone(),
+            Team::Player,
+            self.equipped_stats(character, items),
+            self.abilities(character, items),
+        )
+    }
+
+    /// Takes the item out of the inventory and wears it, putting back what was worn before
+    ///
+    /// Returns whether the item was equipped, which fails if it isn't equipment, isn't in the
+    /// inventory, or the replaced item doesn't fit in the inventory.
+    pub fn equip(&mut self, item: &str, items: &Items, inventory: &mut Inventory) -> bool {
+        let slot = match &items.get(item).equipment {
+            Some(equipment) => equipment.slot,
+            None => return false,
+        };
+
+        let mut changed = inventory.clone();
+        if !changed.remove(item, 1) {
+            return false;
+        }
+
+        if let Some(replaced) = self.equipment.get(slot) {
+            if changed.add(items, replaced, 1) > 0 {
+                return false;
+            }
+        }
+
+        *inventory = changed;
+        *self.equipment.slot_mut(slot) = Some(item.to_string());
+        true
+    }
+
+    /// Puts the item worn in the slot back into the inventory, if it fits
+    pub fn unequip(
+        &mut self,
+        slot: EquipmentSlot,
+        items: &Items,
+        inventory: &mut Inventory,
+    ) -> bool {
+        match self.equipment.get(slot) {
+            Some(item) if inventory.add(items, item, 1) == 0 => {
+                *self.equipment.slot_mut(slot) = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Default for Party {
+    fn default() -> Self {
+        Party {
+            members: vec![PartyMember::new("Hero")],
+        }
+    }
+}
+
+impl Party {
+    /// Gives every member the experience
+    pub fn gain_experience(&mut self, experience: u32) {
+        for member in &mut self.members {
+            member.experience = member.experience.saturating_add(experience);
+        }
+    }
+
+    /// Gives the experience to the first `fighters` members, the ones who fought in a battle
+    pub fn gain_battle_experience(&mut self, experience: u32, fighters: usize) {
+        for member in self.members.iter_mut().take(fighters) {
+            member.experience = member.experience.saturating_add(experience);
+        }
+    }
+
+    /// The first `fighters` members that would gain a level from the rewards of a battle,
+    /// with their new level
+    pub fn level_ups(&self, rewards: &Rewards, fighters: usize) -> Vec<(&str, u32)> {
+        self.members
+            .iter()
+            .take(fighters)
+            .filter_map(|member| {
+                let level =
+                    level_for_experience(member.experience.saturating_add(rewards.experience));
+                if level > member.level() {
+                    Some((member.name.as_str(), level))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_follow_the_experience_curve() {
+        assert_eq!(level_for_experience(0), 1);
+        assert_eq!(level_for_experience(EXPERIENCE_STEP - 1), 1);
+        assert_eq!(level_for_experience(EXPERIENCE_STEP), 2);
+        assert_eq!(level_for_experience(3 * EXPERIENCE_STEP), 3);
+        assert_eq!(level_for_experience(u32::MAX), MAX_LEVEL);
+
+        for level in 1..MAX_LEVEL {
+            assert_eq!(level_for_experience(experience_for_level(level)), level);
+        }
+    }
+
+    #[test]
+    fn stats_grow_with_levels() {
+        let characters = Characters::default();
+        let mut member = PartyMember::new("Hero");
+        let character = characters.get(&member.name);
+        let base = member.stats(character);
+
+        member.experience = experience_for_level(5);
+        let grown = member.stats(character);
+
+        assert_eq!(base, character.base_stats);
+        assert!(grown.max_health > base.max_health);
+        assert!(grown.attack >= base.attack);
+    }
+
+    #[test]
+    fn equipment_is_swapped_with_the_inventory() {
+        let items = Items::default();
+        let mut inventory = Inventory::default();
+        let mut member = PartyMember::new("Hero");
+
+        assert!(!member.equip("Healing Herb", &items, &mut inventory));
+        assert!(!member.equip("Leather Armour", &items, &mut inventory));
+
+        assert!(member.equip("Iron Sword", &items, &mut inventory));
+        assert_eq!(
+            member.equipment.get(EquipmentSlot::Weapon),
+            Some("Iron Sword")
+        );
+        assert_eq!(inventory.count("Iron Sword"), 0);
+
+        inventory.add(&items, "Fire Staff", 1);
+        assert!(member.equip("Fire Staff", &items, &mut inventory));
+        assert_eq!(inventory.count("Iron Sword"), 1);
+
+        assert!(member.unequip(EquipmentSlot::Weapon, &items, &mut inventory));
+        assert!(!member.unequip(EquipmentSlot::Weapon, &items, &mut inventory));
+        assert_eq!(inventory.count("Fire Staff"), 1);
+    }
+
+    #[test]
+    fn equipment_changes_the_combatant() {
+        let characters = Characters::default();
+        let items = Items::default();
+        let mut inventory = Inventory::default();
+        let mut member = PartyMember::new("Hero");
+        let plain = member.combatant(&characters, &items);
+
+        inventory.add(&items, "Fire Staff", 1);
+        member.equip("Fire Staff", &items, &mut inventory);
+        let equipped = member.combatant(&characters, &items);
+
+        assert!(equipped.stats.attack > plain.stats.attack);
+        assert_eq!(equipped.abilities.len(), plain.abilities.len() + 1);
+
+        let damage = |attacker: &Combatant| {
+            crate::battle::combat::damage(
+                5,
+                &attacker.stats,
+                &Default::default(),
+                &plain.stats,
+                &Default::default(),
+            )
+        };
+        assert!(damage(&equipped) > damage(&plain));
+    }
+
+    #[test]
+    fn rewards_can_level_up_members() {
+        let party = Party::default();
+        let rewards = Rewards {
+            experience: EXPERIENCE_STEP,
+            ..Default::default()
+        };
+
+        assert_eq!(party.level_ups(&rewards, 1), vec![("Hero", 2)]);
+        assert!(party.level_ups(&Rewards::default(), 1).is_empty());
+        assert!(party.level_ups(&rewards, 0).is_empty());
+    }
+
+    #[test]
+    fn only_fighters_gain_battle_experience() {
+        let mut party = Party::default();
+        party.members.push(PartyMember::new("Sidekick"));
+
+        party.gain_battle_experience(10, 1);
+
+        assert_eq!(party.members[0].experience, 10);
+        assert_eq!(party.members[1].experience, 0);
+    }
+
+    #[test]
+    fn experience_stops_at_the_maximum() {
+        let mut party = Party::default();
+        party.gain_experience(u32::MAX);
+        let rewards = Rewards {
+            experience: 1,
+            ..Default::default()
+        };
+
+        assert!(party.level_ups(&rewards, 1).is_empty());
+        party.gain_experience(1);
+        party.gain_battle_experience(1, 1);
+        assert_eq!(party.members[0].experience, u32::MAX);
+    }
+}
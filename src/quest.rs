@@ -0,0 +1,404 @@
+//! Quests, their progress and rewards
+//!
+//! Quests are defined in `assets/data/quests.ron`, by ID. Each has a list of objectives
+//! that are completed in order. Quests with a giver start when the player talks to that
+//! NPC, the others start as soon as the player is in the overworld.
+//!
+//! Gameplay systems report what the player does with [`QuestEvent`]s, which advance the
+//! objectives of the started quests. Collecting items is checked against the inventory
+//! instead. Every change to a quest pops up the [`HudQuestBanner`].
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::hud_quest_banner::HudQuestBanner;
+use crate::inventory::{Inventory, Items};
+use crate::party::Party;
+use crate::shop::Wallet;
+use crate::AppState;
+
+/// A quest, as defined in `data/quests.ron`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Quest {
+    pub title: String,
+    pub description: String,
+    /// Name of the NPC that gives the quest, it starts right away if there is none
+    #[serde(default)]
+    pub giver: Option<String>,
+    pub objectives: Vec<Objective>,
+    pub rewards: QuestRewards,
+}
+
+/// Something the player has to do for a quest
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub enum Objective {
+    /// Talk to the NPC with the given name
+    TalkTo(String),
+    /// Win the battle with the given title
+    WinBattle(String),
+    /// Carry a number of the item in the inventory
+    Collect { item: String, count: u32 },
+    /// Enter the area with the given name
+    ReachArea(String),
+}
+
+impl Objective {
+    pub fn describe(&self) -> String {
+        match self {
+            Objective::TalkTo(npc) => format!("Talk to {}", npc),
+            Objective::WinBattle(battle) => format!("Win {}", battle),
+            Objective::Collect { item, count } => format!("Collect {} {}", count, item),
+            Objective::ReachArea(area) => format!("Go to {}", area),
+        }
+    }
+
+    /// Whether the event or the state of the inventory completes the objective
+    fn is_completed_by(&self, event: Option<&QuestEvent>, inventory: &Inventory) -> bool {
+        match (self, event) {
+            (Objective::TalkTo(npc), Some(QuestEvent::TalkedTo(talked_to))) => npc == talked_to,
+            (Objective::WinBattle(battle), Some(QuestEvent::BattleWon(won))) => battle == won,
+            (Objective::ReachArea(area), Some(QuestEvent::AreaEntered(entered))) => area == entered,
+            (Objective::Collect { item, count }, _) => inventory.count(item) >= *count,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct QuestRewards {
+    pub experience: u32,
+    pub gold: u32,
+    /// Names of the items given to the player
+    pub items: Vec<String>,
+}
+
+/// All quests, by ID, as defined in `data/quests.ron`
+pub struct Quests(pub HashMap<String, Quest>);
+
+impl Default for Quests {
+    fn default() -> Self {
+        Quests(crate::data::load("quests.ron"))
+    }
+}
+
+impl Quests {
+    pub fn get(&self, id: &str) -> &Quest {
+        self.0
+            .get(id)
+            .unwrap_or_else(|| panic!("Unknown quest: {}", id))
+    }
+}
+
+/// Something the player did that quests may be waiting for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuestEvent {
+    TalkedTo(String),
+    BattleWon(String),
+    AreaEntered(String),
+}
+
+/// A change to a quest, shown in the banner
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuestUpdate {
+    Started(String),
+    /// The quest moved on to the objective with the given index
+    Advanced(String, usize),
+    Completed(String),
+}
+
+/// Progress of a started quest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuestProgress {
+    pub id: String,
+    /// Index of the objective being worked on, equal to the number of objectives once
+    /// the quest is completed
+    pub objective: usize,
+}
+
+/// The started quests, in the order they were started
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QuestLog {
+    quests: Vec<QuestProgress>,
+}
+
+impl QuestLog {
+    pub fn quests(&self) -> &[QuestProgress] {
+        &self.quests
+    }
+
+    pub fn is_completed(&self, progress: &QuestProgress, quests: &Quests) -> bool {
+        progress.objective >= quests.get(&progress.id).objectives.len()
+    }
+
+    /// Starts the quests that aren't started yet, and are given by the NPC (or by no one)
+    fn start_quests(&mut self, giver: Option<&str>, quests: &Quests) -> Vec<QuestUpdate> {
+        let mut ids: Vec<&String> = quests
+            .0
+            .iter()
+            .filter(|(id, quest)| {
+                quest.giver.as_deref() == giver && !self.quests.iter().any(|p| &p.id == *id)
+            })
+            .map(|(id, _)| id)
+            .collect();
+        // Start quests in a stable order, rather than the order of the map
+        ids.sort();
+
+        ids.into_iter()
+            .map(|id| {
+                self.quests.push(QuestProgress {
+                    id: id.clone(),
+                    objective: 0,
+                });
+                QuestUpdate::Started(id.clone())
+            })
+            .collect()
+    }
+
+    /// Advances the started quests, and starts the quests of the NPC the player talked to
+    ///
+    /// Without an event, only objectives that depend on the inventory are checked.
+    pub fn update(
+        &mut self,
+        event: Option<&QuestEvent>,
+        quests: &Quests,
+        inventory: &Inventory,
+    ) -> Vec<QuestUpdate> {
+        let mut updates = Vec::new();
+
+        for progress in &mut self.quests {
+            let objectives = &quests.get(&progress.id).objectives;
+            let mut event = event;
+
+            // An objective can already be completed when the quest gets to it
+            while let Some(objective) = objectives.get(progress.objective) {
+                if !objective.is_completed_by(event, inventory) {
+                    break;
+                }
+
+                // The event only completes one objective
+                event = None;
+                progress.objective += 1;
+                updates.push(if progress.objective == objectives.len() {
+                    QuestUpdate::Completed(progress.id.clone())
+                } else {
+                    QuestUpdate::Advanced(progress.id.clone(), progress.objective)
+                });
+            }
+        }
+
+        // Quests start after progress is made, so talking to a giver doesn't complete an
+        // objective of the quest it just gave
+        if let Some(QuestEvent::TalkedTo(npc)) = event {
+            updates.extend(self.start_quests(Some(npc), quests));
+        }
+
+        updates
+    }
+
+    /// Starts the quests without a giver that aren't started yet
+    pub fn start_ungiven_quests(&mut self, quests: &Quests) -> Vec<QuestUpdate> {
+        self.start_quests(None, quests)
+    }
+}
+
+/// Advances quests with the gameplay events, and hands out the rewards of completed quests
+#[allow(clippy::too_many_arguments)]
+pub fn track_quests(
+    mut quest_events: EventReader<QuestEvent>,
+    quests: Res<Quests>,
+    items: Res<Items>,
+    mut log: ResMut<QuestLog>,
+    mut party: ResMut<Party>,
+    mut wallet: ResMut<Wallet>,
+    mut inventory: ResMut<Inventory>,
+    mut banner: ResMut<HudQuestBanner>,
+    state: Res<State<AppState>>,
+) {
+    let mut updates = Vec::new();
+    // Don't pop up banners over the main menu
+    if *state.current() == AppState::Overworld {
+        updates.extend(log.start_ungiven_quests(&quests));
+    }
+
+    updates.extend(log.update(None, &quests, &inventory));
+    for event in quest_events.iter() {
+        updates.extend(log.update(Some(event), &quests, &inventory));
+    }
+
+    for update in updates {
+        match update {
+            QuestUpdate::Started(id) => {
+                banner.show("New quest", &quests.get(&id).title);
+            }
+            QuestUpdate::Advanced(id, objective) => {
+                let quest = quests.get(&id);
+                if let Some(objective) = quest.objectives.get(objective) {
+                    banner.show(&quest.title, objective.describe());
+                }
+            }
+            QuestUpdate::Completed(id) => {
+                let quest = quests.get(&id);
+                banner.show("Quest completed", &quest.title);
+
+                party.gain_experience(quest.rewards.experience);
+                wallet.add(quest.rewards.gold);
+                for item in &quest.rewards.items {
+                    if inventory.add(&items, item, 1) > 0 {
+                        warn!("No room in the inventory for {}", item);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Tracks quest progress, and loads the quest definitions
+pub struct QuestPlugin;
+impl Plugin for QuestPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Quests>()
+            .add_event::<QuestEvent>()
+            .add_system(track_quests.system());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_quests() -> Quests {
+        let quest = |giver: Option<&str>, objectives| Quest {
+            title: String::new(),
+            description: String::new(),
+            giver: giver.map(str::to_string),
+            objectives,
+            rewards: QuestRewards::default(),
+        };
+
+        let mut quests = HashMap::new();
+        quests.insert(
+            "errand".to_string(),
+            quest(
+                Some("Ferris"),
+                vec![
+                    Objective::WinBattle("Goblins".to_string()),
+                    Objective::Collect {
+                        item: "Healing Herb".to_string(),
+                        count: 2,
+                    },
+                    Objective::TalkTo("Ferris".to_string()),
+                ],
+            ),
+        );
+        quests.insert(
+            "walk".to_string(),
+            quest(None, vec![Objective::ReachArea("Town".to_string())]),
+        );
+        Quests(quests)
+    }
+
+    #[test]
+    fn quest_files_are_valid() {
+        let items = Items::default();
+
+        for quest in Quests::default().0.values() {
+            assert!(!quest.objectives.is_empty());
+            for objective in &quest.objectives {
+                if let Objective::Collect { item, .. } = objective {
+                    items.get(item);
+                }
+            }
+            for item in &quest.rewards.items {
+                items.get(item);
+            }
+        }
+    }
+
+    #[test]
+    fn quests_start_from_their_giver() {
+        let quests = test_quests();
+        let inventory = Inventory::default();
+        let mut log = QuestLog::default();
+
+        let talk = |npc: &str| QuestEvent::TalkedTo(npc.to_string());
+
+        assert!(log.update(None, &quests, &inventory).is_empty());
+        assert_eq!(
+            log.start_ungiven_quests(&quests),
+            vec![QuestUpdate::Started("walk".to_string())]
+        );
+        assert!(log.start_ungiven_quests(&quests).is_empty());
+        assert!(log
+            .update(Some(&talk("Someone")), &quests, &inventory)
+            .is_empty());
+        assert_eq!(
+            log.update(Some(&talk("Ferris")), &quests, &inventory),
+            vec![QuestUpdate::Started("errand".to_string())]
+        );
+        assert_eq!(log.quests()[1].objective, 0);
+        assert!(log
+            .update(Some(&talk("Ferris")), &quests, &inventory)
+            .is_empty());
+    }
+
+    #[test]
+    fn objectives_are_completed_in_order() {
+        let quests = test_quests();
+        let mut inventory = Inventory::default();
+        let mut log = QuestLog::default();
+        // Talking to Ferris is the last objective, so it doesn't count yet
+        log.update(
+            Some(&QuestEvent::TalkedTo("Ferris".to_string())),
+            &quests,
+            &inventory,
+        );
+
+        // The inventory starts with the 2 Healing Herbs to collect, so drop one to keep
+        // winning the battle from also completing the next objective
+        inventory.remove("Healing Herb", 1);
+        let won = QuestEvent::BattleWon("Goblins".to_string());
+        assert_eq!(
+            log.update(Some(&won), &quests, &inventory),
+            vec![QuestUpdate::Advanced("errand".to_string(), 1)]
+        );
+        assert!(log.update(None, &quests, &inventory).is_empty());
+
+        inventory.add(&Items::default(), "Healing Herb", 1);
+        assert_eq!(
+            log.update(None, &quests, &inventory),
+            vec![QuestUpdate::Advanced("errand".to_string(), 2)]
+        );
+
+        let talk = QuestEvent::TalkedTo("Ferris".to_string());
+        assert_eq!(
+            log.update(Some(&talk), &quests, &inventory),
+            vec![QuestUpdate::Completed("errand".to_string())]
+        );
+        assert!(log.is_completed(&log.quests()[0], &quests));
+    }
+
+    #[test]
+    fn one_event_can_advance_a_quest_twice() {
+        let quests = test_quests();
+        let inventory = Inventory::default();
+        let mut log = QuestLog::default();
+        log.update(
+            Some(&QuestEvent::TalkedTo("Ferris".to_string())),
+            &quests,
+            &inventory,
+        );
+
+        // The Healing Herbs are already in the inventory
+        let won = QuestEvent::BattleWon("Goblins".to_string());
+        assert_eq!(
+            log.update(Some(&won), &quests, &inventory),
+            vec![
+                QuestUpdate::Advanced("errand".to_string(), 1),
+                QuestUpdate::Advanced("errand".to_string(), 2),
+            ]
+        );
+    }
+}
@@ -0,0 +1,177 @@
+//! Seeded randomness for gameplay
+//!
+//! All gameplay randomness comes from the [`GameRng`] resource, so a game can be
+//! reproduced from its seed. Every system draws from its own [`RngStream`], which
+//! means that, for example, rolling for loot does not change the outcome of the
+//! next attack.
+//!
+//! The seed is logged at startup and can be set with the `--seed <number>` command
+//! line argument.
+
+use std::collections::BTreeMap;
+use std::env;
+
+use bevy::prelude::*;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+/// Independent sequences of random numbers, one per kind of gameplay
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum RngStream {
+    Combat,
+    Loot,
+    Encounters,
+    Ai,
+}
+
+/// The source of all gameplay randomness
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "RngState", into = "RngState")]
+pub struct GameRng {
+    seed: u64,
+    streams: BTreeMap<RngStream, ChaCha8Rng>,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        GameRng {
+            seed,
+            streams: BTreeMap::new(),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The random number generator for the given stream
+    pub fn stream(&mut self, stream: RngStream) -> &mut ChaCha8Rng {
+        let seed = self.seed;
+
+        self.streams.entry(stream).or_insert_with(|| {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            rng.set_stream(stream as u64);
+            rng
+        })
+    }
+}
+
+/// The serialized form of a [`GameRng`]
+///
+/// Every stream can be restored from the seed and how far it has been used.
+#[derive(Serialize, Deserialize)]
+struct RngState {
+    seed: u64,
+    word_positions: BTreeMap<RngStream, u64>,
+}
+
+impl From<GameRng> for RngState {
+    fn from(rng: GameRng) -> Self {
+        RngState {
+            seed: rng.seed,
+            word_positions: rng
+                .streams
+                .iter()
+                .map(|(stream, rng)| (*stream, rng.get_word_pos() as u64))
+                .collect(),
+        }
+    }
+}
+
+impl From<RngState> for GameRng {
+    fn from(state: RngState) -> Self {
+        let mut rng = GameRng::new(state.seed);
+
+        for (stream, word_position) in state.word_positions {
+            rng.stream(stream).set_word_pos(word_position.into());
+        }
+
+        rng
+    }
+}
+
+/// The seed given on the command line, which takes precedence over the one in the save game
+pub fn seed_from_command_line() -> Option<u64> {
+    parse_seed(env::args())
+}
+
+/// Finds the value of the `--seed` argument
+fn parse_seed<I: Iterator<Item = String>>(args: I) -> Option<u64> {
+    crate::argument_value(args, "--seed")
+        .map(|value| value.parse().expect("--seed must be a positive number"))
+}
+
+pub fn log_rng_seed(rng: Res<GameRng>) {
+    info!("Game RNG seed: {}", rng.seed());
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+
+    fn draw(rng: &mut GameRng, stream: RngStream) -> Vec<u32> {
+        (0..8).map(|_| rng.stream(stream).gen()).collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_numbers() {
+        let mut a = GameRng::new(42);
+        let mut b = GameRng::new(42);
+
+        assert_eq!(
+            draw(&mut a, RngStream::Combat),
+            draw(&mut b, RngStream::Combat)
+        );
+        assert_ne!(
+            draw(&mut GameRng::new(1), RngStream::Combat),
+            draw(&mut GameRng::new(2), RngStream::Combat)
+        );
+    }
+
+    #[test]
+    fn streams_are_independent() {
+        let mut a = GameRng::new(7);
+        let mut b = GameRng::new(7);
+
+        draw(&mut a, RngStream::Loot);
+        draw(&mut a, RngStream::Encounters);
+
+        let combat = draw(&mut a, RngStream::Combat);
+        assert_eq!(combat, draw(&mut b, RngStream::Combat));
+        assert_ne!(combat, draw(&mut b, RngStream::Loot));
+    }
+
+    #[test]
+    fn serialized_rng_continues_where_it_left_off() {
+        let mut rng = GameRng::new(1234);
+        draw(&mut rng, RngStream::Combat);
+        draw(&mut rng, RngStream::Loot);
+
+        let serialized = ron::ser::to_string(&rng).unwrap();
+        let mut restored: GameRng = ron::de::from_str(&serialized).unwrap();
+
+        assert_eq!(restored.seed(), 1234);
+        assert_eq!(
+            draw(&mut restored, RngStream::Combat),
+            draw(&mut rng, RngStream::Combat)
+        );
+        assert_eq!(
+            draw(&mut restored, RngStream::Loot),
+            draw(&mut rng, RngStream::Loot)
+        );
+    }
+
+    #[test]
+    fn seed_is_read_from_arguments() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(parse_seed(args(&["sotora"]).into_iter()), None);
+        assert_eq!(
+            parse_seed(args(&["sotora", "--seed", "99"]).into_iter()),
+            Some(99)
+        );
+    }
+}
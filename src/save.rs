@@ -0,0 +1,153 @@
+//! The save game, persisted to the user data directory as a RON file
+
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::{BufReader, BufWriter, ErrorKind};
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+
+use crate::inventory::Inventory;
+use crate::overworld::interactables::battle_starter::DefeatedBattleStarters;
+use crate::overworld::level::{CurrentLevel, Location};
+use crate::party::Party;
+use crate::quest::QuestLog;
+use crate::rng::{self, GameRng};
+use crate::shop::{ShopStocks, Wallet};
+use crate::world_time::WorldTime;
+use crate::AppState;
+use crate::Stage;
+
+const SAVE_FILE_NAME: &str = "save.ron";
+
+/// Everything needed to continue a game later
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SaveGame {
+    pub rng: GameRng,
+    #[serde(default)]
+    pub party: Party,
+    #[serde(default)]
+    pub inventory: Inventory,
+    #[serde(default)]
+    pub wallet: Wallet,
+    #[serde(default)]
+    pub world_time: WorldTime,
+    #[serde(default)]
+    pub shop_stocks: ShopStocks,
+    #[serde(default)]
+    pub quest_log: QuestLog,
+    #[serde(default)]
+    pub defeated_battle_starters: DefeatedBattleStarters,
+    #[serde(default)]
+    pub location: Location,
+}
+
+impl Default for SaveGame {
+    fn default() -> Self {
+        SaveGame {
+            rng: GameRng::new(rand::random()),
+            party: Default::default(),
+            inventory: Default::default(),
+            wallet: Default::default(),
+            world_time: Default::default(),
+            shop_stocks: Default::default(),
+            quest_log: Default::default(),
+            defeated_battle_starters: Default::default(),
+            location: Default::default(),
+        }
+    }
+}
+
+/// Loads the save game into resources, and saves the game when leaving the overworld
+pub struct SavePlugin;
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let mut save = SaveGame::load().unwrap_or_default();
+
+        if let Some(seed) = rng::seed_from_command_line() {
+            save.rng = GameRng::new(seed);
+        }
+
+        app.insert_resource(save.rng)
+            .insert_resource(save.party)
+            .insert_resource(save.inventory)
+            .insert_resource(save.wallet)
+            .insert_resource(save.world_time)
+            .insert_resource(save.shop_stocks)
+            .insert_resource(save.quest_log)
+            .insert_resource(save.defeated_battle_starters)
+            .insert_resource(CurrentLevel::new(save.location))
+            .on_state_exit(Stage::AppState, AppState::Overworld, save_game.system());
+    }
+}
+
+impl SaveGame {
+    /// Loads the save game, if there is one
+    pub fn load() -> Option<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .open(get_data_file_path(SAVE_FILE_NAME));
+
+        match file {
+            Ok(file) => {
+                let reader = BufReader::new(file);
+                Some(ron::de::from_reader(reader).expect("Could not read save file"))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Saves the game, overwriting the previous save
+    pub fn save(&self) {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(get_data_file_path(SAVE_FILE_NAME))
+            .expect("Could not open save file for writing");
+
+        let writer = BufWriter::new(file);
+        ron::ser::to_writer_pretty(writer, self, Default::default())
+            .expect("Could not write save file");
+    }
+}
+
+/// Saves the current state of the game
+#[allow(clippy::too_many_arguments)]
+pub fn save_game(
+    rng: Res<GameRng>,
+    party: Res<Party>,
+    inventory: Res<Inventory>,
+    wallet: Res<Wallet>,
+    world_time: Res<WorldTime>,
+    shop_stocks: Res<ShopStocks>,
+    quest_log: Res<QuestLog>,
+    defeated_battle_starters: Res<DefeatedBattleStarters>,
+    current_level: Res<CurrentLevel>,
+) {
+    SaveGame {
+        rng: rng.clone(),
+        party: party.clone(),
+        inventory: inventory.clone(),
+        wallet: *wallet,
+        world_time: *world_time,
+        shop_stocks: shop_stocks.clone(),
+        quest_log: quest_log.clone(),
+        defeated_battle_starters: defeated_battle_starters.clone(),
+        location: current_level.location.clone(),
+    }
+    .save();
+}
+
+/// Gets the platform-specific path of a file in the user data directory
+pub fn get_data_file_path(file_name: &str) -> PathBuf {
+    let dirs =
+        ProjectDirs::from("", "bevy-community", "sotora").expect("Could not access user data dirs");
+
+    fs::create_dir_all(dirs.data_dir()).expect("Could not create user data directory");
+
+    dirs.data_dir().join(file_name)
+}
@@ -0,0 +1,270 @@
+//! Shops run by NPCs, and the currency used to pay them
+//!
+//! Shops are defined in `assets/data/shops`. A shop sells items for their base price times
+//! its buy modifier, and buys items from the inventory for their price times its sell
+//! modifier. Items with a limit sell out, and are restocked every `restock_hours` of world
+//! time.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::inventory::{Inventory, Item, Items};
+use crate::world_time::WorldTime;
+
+/// The party's money, earned in battles
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Wallet(pub u32);
+
+impl Wallet {
+    /// Adds the gold, keeping the most gold there can be if it doesn't fit
+    pub fn add(&mut self, gold: u32) {
+        self.0 = self.0.saturating_add(gold);
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Shop {
+    /// Shown as the title of the shop screen
+    pub name: String,
+    /// Multiplies the base price of items bought from the shop
+    pub buy_modifier: f32,
+    /// Multiplies the base price of items sold to the shop
+    pub sell_modifier: f32,
+    /// World time between restocks, in hours
+    pub restock_hours: f64,
+    pub stock: Vec<ShopEntry>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ShopEntry {
+    pub item: String,
+    /// How many can be bought until the next restock, unlimited if not set
+    #[serde(default)]
+    pub limit: Option<u32>,
+}
+
+impl Shop {
+    /// Loads a shop from `assets/data/shops`
+    pub fn load(id: &str) -> Self {
+        crate::data::load(&format!("shops/{}.ron", id))
+    }
+
+    pub fn buy_price(&self, item: &Item) -> u32 {
+        (item.price as f32 * self.buy_modifier).ceil() as u32
+    }
+
+    /// What the shop pays for the item, 0 if it doesn't want it
+    pub fn sell_price(&self, item: &Item) -> u32 {
+        (item.price as f32 * self.sell_modifier).floor() as u32
+    }
+}
+
+/// How much of each shop's limited stock was bought since the shop's last restock
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ShopStocks(HashMap<String, ShopStock>);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ShopStock {
+    /// The restock period the purchases were made in
+    period: u64,
+    bought: HashMap<String, u32>,
+}
+
+impl ShopStocks {
+    /// Forgets the purchases made before the shop's latest restock
+    pub fn restock(&mut self, shop_id: &str, shop: &Shop, time: &WorldTime) {
+        let period = (time.hours / shop.restock_hours).floor() as u64;
+
+        let stock = self.0.entry(shop_id.to_string()).or_default();
+        if stock.period != period {
+            stock.period = period;
+            stock.bought.clear();
+        }
+    }
+
+    /// How many of the entry can still be bought, `None` if there is no limit
+    pub fn remaining(&self, shop_id: &str, entry: &ShopEntry) -> Option<u32> {
+        let bought = self
+            .0
+            .get(shop_id)
+            .and_then(|stock| stock.bought.get(&entry.item))
+            .copied()
+            .unwrap_or(0);
+
+        entry.limit.map(|limit| limit.saturating_sub(bought))
+    }
+
+    fn record_purchase(&mut self, shop_id: &str, item: &str) {
+        let stock = self.0.entry(shop_id.to_string()).or_default();
+        *stock.bought.entry(item.to_string()).or_insert(0) += 1;
+    }
+}
+
+/// Buys one of the shop's entry, returning whether it was in stock, affordable and fit in
+/// the inventory
+pub fn buy(
+    shop_id: &str,
+    shop: &Shop,
+    entry: usize,
+    items: &Items,
+    wallet: &mut Wallet,
+    inventory: &mut Inventory,
+    stocks: &mut ShopStocks,
+) -> bool {
+    let entry = match shop.stock.get(entry) {
+        Some(entry) => entry,
+        None => return false,
+    };
+
+    let price = shop.buy_price(items.get(&entry.item));
+    if stocks.remaining(shop_id, entry) == Some(0) || wallet.0 < price {
+        return false;
+    }
+
+    let mut changed = inventory.clone();
+    if changed.add(items, &entry.item, 1) > 0 {
+        return false;
+    }
+
+    *inventory = changed;
+    wallet.0 -= price;
+    stocks.record_purchase(shop_id, &entry.item);
+    true
+}
+
+/// Sells one of the item, returning whether the shop bought it
+pub fn sell(
+    shop: &Shop,
+    item: &str,
+    items: &Items,
+    wallet: &mut Wallet,
+    inventory: &mut Inventory,
+) -> bool {
+    let price = shop.sell_price(items.get(item));
+    if price == 0 || !inventory.remove(item, 1) {
+        return false;
+    }
+
+    wallet.add(price);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_shop() -> Shop {
+        Shop {
+            name: "Test".to_string(),
+            buy_modifier: 1.5,
+            sell_modifier: 0.5,
+            restock_hours: 24.0,
+            stock: vec![
+                ShopEntry {
+                    item: "Healing Herb".to_string(),
+                    limit: None,
+                },
+                ShopEntry {
+                    item: "Tome of Insight".to_string(),
+                    limit: Some(1),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn shop_files_are_valid() {
+        let items = Items::default();
+        let shop = Shop::load("ferris");
+
+        assert!(shop.restock_hours > 0.0);
+        for entry in &shop.stock {
+            assert!(shop.buy_price(items.get(&entry.item)) > 0);
+        }
+    }
+
+    #[test]
+    fn prices_follow_the_modifiers() {
+        let items = Items::default();
+        let shop = test_shop();
+        let herb = items.get("Healing Herb");
+
+        assert_eq!(shop.buy_price(herb), (herb.price * 3 + 1) / 2);
+        assert_eq!(shop.sell_price(herb), herb.price / 2);
+    }
+
+    #[test]
+    fn buying_needs_money_and_stock() {
+        let items = Items::default();
+        let shop = test_shop();
+        let tome_price = shop.buy_price(items.get("Tome of Insight"));
+        let mut wallet = Wallet(tome_price * 2);
+        let mut inventory = Inventory::default();
+        let mut stocks = ShopStocks::default();
+        let mut time = WorldTime { hours: 30.0 };
+        stocks.restock("test", &shop, &time);
+
+        let mut buy_tome = |wallet: &mut Wallet, stocks: &mut ShopStocks| {
+            buy("test", &shop, 1, &items, wallet, &mut inventory, stocks)
+        };
+
+        assert!(buy_tome(&mut wallet, &mut stocks));
+        assert!(!buy_tome(&mut wallet, &mut stocks));
+        assert_eq!(wallet.0, tome_price);
+
+        // Restocked the next day
+        time.hours = 47.0;
+        stocks.restock("test", &shop, &time);
+        assert!(!buy_tome(&mut wallet, &mut stocks));
+        time.hours = 48.0;
+        stocks.restock("test", &shop, &time);
+        assert!(buy_tome(&mut wallet, &mut stocks));
+        assert_eq!(wallet.0, 0);
+
+        // Out of money
+        assert!(!buy(
+            "test",
+            &shop,
+            0,
+            &items,
+            &mut wallet,
+            &mut inventory,
+            &mut stocks
+        ));
+        assert_eq!(inventory.count("Tome of Insight"), 2);
+    }
+
+    #[test]
+    fn selling_pays_part_of_the_price() {
+        let items = Items::default();
+        let shop = test_shop();
+        let mut wallet = Wallet(0);
+        let mut inventory = Inventory::default();
+        let herbs = inventory.count("Healing Herb");
+
+        assert!(sell(
+            &shop,
+            "Healing Herb",
+            &items,
+            &mut wallet,
+            &mut inventory
+        ));
+        assert_eq!(wallet.0, shop.sell_price(items.get("Healing Herb")));
+        assert_eq!(inventory.count("Healing Herb"), herbs - 1);
+        assert!(!sell(
+            &shop,
+            "Antidote",
+            &items,
+            &mut wallet,
+            &mut inventory
+        ));
+    }
+
+    #[test]
+    fn wallets_saturate() {
+        let mut wallet = Wallet(u32::MAX - 1);
+        wallet.add(5);
+        assert_eq!(wallet.0, u32::MAX);
+    }
+}
@@ -16,8 +16,17 @@ pub struct KeyBinds {
     pub move_left: KeyCode,
     pub move_backward: KeyCode,
     pub move_right: KeyCode,
+    pub sprint: KeyCode,
+    pub jump: KeyCode,
 
     pub interact: KeyCode,
+
+    pub end_turn: KeyCode,
+    pub undo_move: KeyCode,
+    pub use_item: KeyCode,
+    pub export_combat_log: KeyCode,
+    /// Held while moving the mouse to rotate the battle camera
+    pub rotate_camera: MouseButton,
 }
 
 impl Default for KeyBinds {
@@ -27,8 +36,16 @@ impl Default for KeyBinds {
             move_left: KeyCode::A,
             move_backward: KeyCode::S,
             move_right: KeyCode::D,
+            sprint: KeyCode::LShift,
+            jump: KeyCode::Space,
 
             interact: KeyCode::E,
+
+            end_turn: KeyCode::Space,
+            undo_move: KeyCode::Z,
+            use_item: KeyCode::Q,
+            export_combat_log: KeyCode::F9,
+            rotate_camera: MouseButton::Right,
         }
     }
 }
@@ -0,0 +1,24 @@
+//! In-game time, which only passes while the player walks around the overworld
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// In-game hours that pass per real second, so a day lasts 24 minutes
+const HOURS_PER_SECOND: f64 = 1.0 / 60.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct WorldTime {
+    /// In-game hours since the game started
+    pub hours: f64,
+}
+
+impl WorldTime {
+    /// The current day, starting at 1
+    pub fn day(&self) -> u32 {
+        (self.hours / 24.0) as u32 + 1
+    }
+}
+
+pub fn advance_world_time(time: Res<Time>, mut world_time: ResMut<WorldTime>) {
+    world_time.hours += time.delta_seconds_f64() * HOURS_PER_SECOND;
+}